@@ -0,0 +1,69 @@
+use clineup::path::duplicates_finder::{get_hash_of_file_with, DuplicatesFinder, HashAlgorithm};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
+
+/// Photos of a few hundred kilobytes, most of them of a unique size, with some copies
+/// and some files of the same size with another content, like the bursts of a camera
+fn create_corpus(folder: &Path) -> Vec<PathBuf> {
+    let mut rng = fastrand::Rng::with_seed(42);
+    let mut files = Vec::new();
+
+    for index in 0..200 {
+        let size = rng.usize(100_000..1_000_000);
+        let content: Vec<u8> = (0..size).map(|_| rng.u8(..)).collect();
+        let path = folder.join(format!("IMG_{:04}.jpg", index));
+        std::fs::write(&path, &content).unwrap();
+        files.push(path);
+
+        if index % 10 == 0 {
+            let copy = folder.join(format!("IMG_{:04}_copy.jpg", index));
+            std::fs::write(&copy, &content).unwrap();
+            files.push(copy);
+        }
+        if index % 10 == 5 {
+            let burst: Vec<u8> = (0..size).map(|_| rng.u8(..)).collect();
+            let path = folder.join(format!("IMG_{:04}_burst.jpg", index));
+            std::fs::write(&path, &burst).unwrap();
+            files.push(path);
+        }
+    }
+    files
+}
+
+fn bench_duplicates(c: &mut Criterion) {
+    let tmp_dir = TempDir::new("corpus").unwrap();
+    let files = create_corpus(tmp_dir.path());
+
+    let mut group = c.benchmark_group("duplicates");
+    group.sample_size(10);
+
+    // What the duplicates finder did before : every file fully hashed with SHA-256
+    group.bench_function("full sha256", |b| {
+        b.iter(|| {
+            for file in &files {
+                get_hash_of_file_with(&File::open(file).unwrap(), HashAlgorithm::Sha256).unwrap();
+            }
+        })
+    });
+
+    for (name, algorithm) in [
+        ("staged sha256", HashAlgorithm::Sha256),
+        ("staged blake3", HashAlgorithm::Blake3),
+        ("staged xxh3", HashAlgorithm::Xxh3),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut duplicates_finder = DuplicatesFinder::new().with_hash_algorithm(algorithm);
+                for file in &files {
+                    duplicates_finder.is_duplicate(file).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_duplicates);
+criterion_main!(benches);
@@ -0,0 +1,554 @@
+use chrono::Duration;
+use chrono::NaiveDateTime;
+use image::imageops::FilterType;
+use log::debug;
+use log::warn;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::errors::ClineupError;
+use crate::exif_extractor::ExifExtractor;
+use crate::path::sanitizer::Sanitizer;
+
+/// Default maximum time between two consecutive frames of a burst, in milliseconds
+pub const DEFAULT_BURST_INTERVAL: u64 = 1000;
+
+/// Largest side of the thumbnail whose sharpness is measured
+const SHARPNESS_THUMBNAIL_SIZE: u32 = 512;
+
+/// What is done with the frames of a burst
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BurstMode {
+    /// Every frame is organized, `%burst_id` telling the bursts apart
+    KeepAll,
+    /// Every frame is organized in a subfolder named after its burst
+    Subfolder,
+    /// Only the first frame of each burst is organized
+    First,
+    /// Only the sharpest frame of each burst is organized
+    Sharpest,
+}
+
+impl BurstMode {
+    pub fn from_string(mode: &str) -> Result<BurstMode, ClineupError> {
+        match mode {
+            "keep-all" => Ok(BurstMode::KeepAll),
+            "subfolder" => Ok(BurstMode::Subfolder),
+            "first" => Ok(BurstMode::First),
+            "sharpest" => Ok(BurstMode::Sharpest),
+            _ => Err(ClineupError::InvalidBurstMode(mode.to_string())),
+        }
+    }
+}
+
+/// Photo which may be a frame of a burst
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub path: PathBuf,
+    /// Make, model and serial number, since the bursts of two cameras are never mixed
+    pub camera: String,
+    /// Capture date, with the fraction of second when it is recorded
+    pub date: NaiveDateTime,
+    /// Taken by an automatic exposure bracketing
+    pub is_bracketed: bool,
+    /// Number recorded by some cameras, a gap in the numbers ending a burst
+    pub image_number: Option<u32>,
+    /// Identifier shared by the frames of an iPhone burst, from the Apple maker note
+    pub burst_uuid: Option<String>,
+    /// Number of the frame in a continuous shooting, from the maker note, 0 for a single shot
+    pub sequence_number: Option<u32>,
+}
+
+impl Frame {
+    /// Reads the EXIF data telling whether a photo belongs to a burst.
+    /// The burst identifier and the sequence number of the maker note are read when present.
+    ///
+    /// # Returns
+    ///
+    /// The frame, or an error if the photo has no capture date.
+    pub fn from_file(path: &Path) -> Result<Frame, ClineupError> {
+        let exif_extractor = ExifExtractor::new(&path.to_path_buf())?;
+        let camera = [
+            exif_extractor.get_camera_brand(),
+            exif_extractor.get_camera_model(),
+            exif_extractor.get_serial_number(),
+        ]
+        .into_iter()
+        .map(|value| value.unwrap_or_default())
+        .collect::<Vec<String>>()
+        .join(" ");
+
+        Ok(Frame {
+            path: path.to_path_buf(),
+            camera,
+            date: exif_extractor.get_exif_date_with_subseconds()?,
+            is_bracketed: exif_extractor.is_auto_bracketed().unwrap_or(false),
+            image_number: exif_extractor.get_image_number().ok(),
+            burst_uuid: exif_extractor.get_burst_uuid().ok(),
+            sequence_number: exif_extractor.get_sequence_number().ok(),
+        })
+    }
+}
+
+/// Whether `frame` is the next frame of the burst ending with `previous`, both being sorted by date.
+/// The frames with a burst identifier are grouped before, so that they are never part of these bursts.
+fn continues_burst(previous: &Frame, frame: &Frame, interval: Duration) -> bool {
+    if previous.camera != frame.camera {
+        return false;
+    }
+    // The sequence number written by the camera decides, the capture dates only when it is missing
+    if let (Some(previous_number), Some(number)) = (previous.sequence_number, frame.sequence_number)
+    {
+        return previous_number > 0 && previous_number.checked_add(1) == Some(number);
+    }
+    let consecutive_numbers = match (previous.image_number, frame.image_number) {
+        (Some(previous_number), Some(number)) if previous_number.checked_add(1) != Some(number) => {
+            return false
+        }
+        (Some(_), Some(_)) => true,
+        _ => false,
+    };
+    // The long exposures of a bracketing may exceed the interval
+    (previous.is_bracketed && frame.is_bracketed && consecutive_numbers)
+        || frame.date - previous.date <= interval
+}
+
+/// Groups photos into bursts, sorting them by camera and capture date.
+/// The frames sharing a burst identifier, like the ones of an iPhone, are grouped whatever the time between them.
+/// The other ones are grouped by sequence number, or by capture date when the camera does not record it.
+///
+/// # Arguments
+///
+/// * `frames` - The photos, in any order.
+/// * `interval` - The maximum time between two consecutive frames of a burst.
+///   The consecutive frames of an automatic bracketing are grouped whatever the time between them.
+///
+/// # Returns
+///
+/// The bursts of at least two frames, each one sorted by capture date.
+pub fn group_bursts(mut frames: Vec<Frame>, interval: Duration) -> Vec<Vec<Frame>> {
+    frames.sort_by(|left, right| {
+        (&left.camera, left.date, &left.path).cmp(&(&right.camera, right.date, &right.path))
+    });
+
+    let mut bursts: Vec<Vec<Frame>> = Vec::new();
+    let mut tagged_bursts: HashMap<(String, String), usize> = HashMap::new();
+    let mut burst: Vec<Frame> = Vec::new();
+    for frame in frames {
+        if let Some(burst_uuid) = &frame.burst_uuid {
+            let key = (frame.camera.clone(), burst_uuid.clone());
+            match tagged_bursts.get(&key) {
+                Some(&index) => bursts[index].push(frame),
+                None => {
+                    tagged_bursts.insert(key, bursts.len());
+                    bursts.push(vec![frame]);
+                }
+            }
+            continue;
+        }
+        if let Some(previous) = burst.last() {
+            if !continues_burst(previous, &frame, interval) {
+                bursts.push(std::mem::take(&mut burst));
+            }
+        }
+        burst.push(frame);
+    }
+    bursts.push(burst);
+
+    bursts.retain(|burst| burst.len() > 1);
+    bursts.sort_by(|left, right| {
+        (&left[0].camera, left[0].date, &left[0].path).cmp(&(
+            &right[0].camera,
+            right[0].date,
+            &right[0].path,
+        ))
+    });
+    bursts
+}
+
+/// Names a burst after the capture date and the name of its first frame, like `20230304-101112_IMG_0001`
+pub fn get_burst_id(burst: &[Frame]) -> String {
+    let first = &burst[0];
+    format!(
+        "{}_{}",
+        first.date.format("%Y%m%d-%H%M%S"),
+        first
+            .path
+            .file_stem()
+            .map_or(String::new(), |stem| stem.to_string_lossy().to_string())
+    )
+}
+
+/// Measures the sharpness of an image as the variance of its Laplacian in grayscale,
+/// the blurred frames of a burst having fewer edges.
+///
+/// # Returns
+///
+/// The sharpness, the higher the sharper, or an error if the image can not be decoded.
+pub fn get_sharpness(path: &Path) -> Result<f64, ClineupError> {
+    let image = image::open(path)?;
+    let image =
+        if image.width() > SHARPNESS_THUMBNAIL_SIZE || image.height() > SHARPNESS_THUMBNAIL_SIZE {
+            image.resize(
+                SHARPNESS_THUMBNAIL_SIZE,
+                SHARPNESS_THUMBNAIL_SIZE,
+                FilterType::Triangle,
+            )
+        } else {
+            image
+        };
+    let gray = image.to_luma8();
+    let (width, height) = gray.dimensions();
+    if width < 3 || height < 3 {
+        return Ok(0.0);
+    }
+
+    let pixel = |x: u32, y: u32| gray.get_pixel(x, y)[0] as f64;
+    let laplacians: Vec<f64> = (1..height - 1)
+        .flat_map(|y| (1..width - 1).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            4.0 * pixel(x, y)
+                - pixel(x - 1, y)
+                - pixel(x + 1, y)
+                - pixel(x, y - 1)
+                - pixel(x, y + 1)
+        })
+        .collect();
+    let mean = laplacians.iter().sum::<f64>() / laplacians.len() as f64;
+    Ok(laplacians
+        .iter()
+        .map(|laplacian| (laplacian - mean).powi(2))
+        .sum::<f64>()
+        / laplacians.len() as f64)
+}
+
+/// Index of the sharpest frame of a burst which is not a duplicate, the first one on a tie
+fn get_sharpest_frame(burst: &[Frame], duplicates: &HashSet<PathBuf>) -> Option<usize> {
+    let mut sharpest = None;
+    for (index, frame) in burst.iter().enumerate() {
+        if duplicates.contains(&frame.path) {
+            continue;
+        }
+        let sharpness = get_sharpness(&frame.path).unwrap_or_else(|err| {
+            warn!("{}", err);
+            0.0
+        });
+        if sharpest.is_none_or(|(_, sharpest_sharpness)| sharpness > sharpest_sharpness) {
+            sharpest = Some((index, sharpness));
+        }
+    }
+    sharpest.map(|(index, _)| index)
+}
+
+/// Bursts of the files to organize, detected before organizing any of them
+#[derive(Debug, Clone)]
+pub struct Bursts {
+    mode: BurstMode,
+    ids: HashMap<PathBuf, String>,
+    dropped: HashSet<PathBuf>,
+    sanitizer: Sanitizer,
+}
+
+impl Default for Bursts {
+    fn default() -> Self {
+        Bursts {
+            mode: BurstMode::KeepAll,
+            ids: HashMap::new(),
+            dropped: HashSet::new(),
+            sanitizer: Sanitizer::default(),
+        }
+    }
+}
+
+impl Bursts {
+    /// Detects the bursts among the files to organize.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The files to organize, those without a capture date being never part of a burst.
+    /// * `interval` - The maximum time between two consecutive frames of a burst.
+    /// * `mode` - What is done with the frames, `first` and `sharpest` dropping all the frames but one.
+    /// * `duplicates` - The files dropped as duplicates, which are never the kept frame of a burst.
+    pub fn detect(
+        files: &[PathBuf],
+        interval: Duration,
+        mode: BurstMode,
+        duplicates: &HashSet<PathBuf>,
+    ) -> Bursts {
+        let frames = files
+            .iter()
+            .filter_map(|path| match Frame::from_file(path) {
+                Ok(frame) => Some(frame),
+                Err(err) => {
+                    debug!("{:?} is not part of a burst: {}", path, err);
+                    None
+                }
+            })
+            .collect();
+
+        let mut bursts = Bursts {
+            mode,
+            ..Bursts::default()
+        };
+        for burst in group_bursts(frames, interval) {
+            let id = get_burst_id(&burst);
+            debug!("Find burst {} of {} frame(s)", id, burst.len());
+            let kept = match mode {
+                BurstMode::First => burst
+                    .iter()
+                    .position(|frame| !duplicates.contains(&frame.path)),
+                BurstMode::Sharpest => get_sharpest_frame(&burst, duplicates),
+                BurstMode::KeepAll | BurstMode::Subfolder => None,
+            };
+            for (index, frame) in burst.into_iter().enumerate() {
+                if kept.is_some_and(|kept| kept != index) {
+                    bursts.dropped.insert(frame.path.clone());
+                }
+                bursts.ids.insert(frame.path, id.clone());
+            }
+        }
+        bursts
+    }
+
+    /// Sanitizes the subfolders of the bursts like the rest of the destination path.
+    pub fn with_sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        self.sanitizer = sanitizer;
+        self
+    }
+
+    /// The burst of a file, if it is part of one
+    pub fn get_burst_id(&self, path: &Path) -> Option<&String> {
+        self.ids.get(path)
+    }
+
+    /// Whether the file is a frame of a burst which is not kept
+    pub fn is_dropped(&self, path: &Path) -> bool {
+        self.dropped.contains(path)
+    }
+
+    /// Moves the destination of a frame into the subfolder of its burst, with `--bursts subfolder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to organize.
+    /// * `destination` - Its formatted destination, returned as is if it is not part of a burst.
+    pub fn get_destination(&self, path: &Path, destination: PathBuf) -> PathBuf {
+        match (self.mode, self.get_burst_id(path), destination.file_name()) {
+            (BurstMode::Subfolder, Some(burst_id), Some(file_name)) => destination
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(self.sanitizer.sanitize_folder_name(burst_id))
+                .join(file_name),
+            _ => destination,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::sanitizer::SanitizeProfile;
+    use image::GrayImage;
+    use image::Luma;
+    use tempdir::TempDir;
+
+    fn frame(name: &str, camera: &str, time: &str) -> Frame {
+        Frame {
+            path: PathBuf::from(name),
+            camera: camera.to_string(),
+            date: NaiveDateTime::parse_from_str(
+                &format!("2023-03-04 {}", time),
+                "%Y-%m-%d %H:%M:%S%.f",
+            )
+            .unwrap(),
+            is_bracketed: false,
+            image_number: None,
+            burst_uuid: None,
+            sequence_number: None,
+        }
+    }
+
+    fn names(bursts: &[Vec<Frame>]) -> Vec<Vec<String>> {
+        bursts
+            .iter()
+            .map(|burst| {
+                burst
+                    .iter()
+                    .map(|frame| frame.path.to_string_lossy().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_group_bursts() {
+        let frames = vec![
+            frame("IMG_0003.jpg", "Canon", "10:00:01.100"),
+            frame("IMG_0001.jpg", "Canon", "10:00:00.000"),
+            frame("IMG_0002.jpg", "Canon", "10:00:00.600"),
+            // Too late to be part of the burst
+            frame("IMG_0004.jpg", "Canon", "10:00:05.000"),
+            // Same time, but another camera
+            frame("DSC_0001.jpg", "Nikon", "10:00:00.300"),
+            frame("DSC_0002.jpg", "Nikon", "10:00:00.500"),
+        ];
+        assert_eq!(
+            names(&group_bursts(frames, Duration::milliseconds(1000))),
+            vec![
+                vec!["IMG_0001.jpg", "IMG_0002.jpg", "IMG_0003.jpg"],
+                vec!["DSC_0001.jpg", "DSC_0002.jpg"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_bursts_with_exif_tags() {
+        let mut frames = vec![
+            frame("BKT_0001.jpg", "Sony", "10:00:00"),
+            frame("BKT_0002.jpg", "Sony", "10:00:04"),
+            frame("BKT_0003.jpg", "Sony", "10:00:09"),
+            frame("IMG_0010.jpg", "Sony", "11:00:00.000"),
+            frame("IMG_0012.jpg", "Sony", "11:00:00.100"),
+        ];
+        for (frame, number) in frames.iter_mut().zip([1, 2, 3, 10, 12]) {
+            frame.image_number = Some(number);
+        }
+        for frame in frames.iter_mut().take(3) {
+            frame.is_bracketed = true;
+        }
+
+        // The bracketing is longer than the interval, and a gap in the numbers splits the burst
+        assert_eq!(
+            names(&group_bursts(frames, Duration::milliseconds(1000))),
+            vec![vec!["BKT_0001.jpg", "BKT_0002.jpg", "BKT_0003.jpg"]]
+        );
+    }
+
+    #[test]
+    fn test_group_bursts_with_maker_note() {
+        let mut frames = vec![
+            // Far apart, but sharing the identifier of an iPhone burst
+            frame("IMG_0001.jpg", "Apple", "10:00:00"),
+            frame("IMG_0002.jpg", "Apple", "10:00:03"),
+            frame("IMG_0003.jpg", "Apple", "10:00:03.100"),
+            frame("DSCF0001.jpg", "Fujifilm", "11:00:00.000"),
+            frame("DSCF0002.jpg", "Fujifilm", "11:00:00.100"),
+            frame("DSCF0003.jpg", "Fujifilm", "11:00:00.200"),
+            frame("DSCF0004.jpg", "Fujifilm", "11:00:00.300"),
+            frame("DSCF0005.jpg", "Fujifilm", "11:00:00.400"),
+        ];
+        frames[0].burst_uuid = Some("5D9A3C1E".to_string());
+        frames[1].burst_uuid = Some("5D9A3C1E".to_string());
+        // Single shots, then a new continuous shooting starting again at 1
+        for (frame, number) in frames.iter_mut().skip(3).zip([0, 0, 1, 2, 1]) {
+            frame.sequence_number = Some(number);
+        }
+
+        // The last frame of the iPhone has no identifier, so its date is not enough
+        assert_eq!(
+            names(&group_bursts(frames, Duration::milliseconds(1000))),
+            vec![
+                vec!["IMG_0001.jpg", "IMG_0002.jpg"],
+                vec!["DSCF0003.jpg", "DSCF0004.jpg"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frame_from_file_with_burst_uuid() {
+        let frames: Vec<Frame> = ["IMG_0001.jpg", "IMG_0002.jpg"]
+            .iter()
+            .map(|name| {
+                Frame::from_file(
+                    &PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                        .join("tests/data/bursts")
+                        .join(name),
+                )
+                .unwrap()
+            })
+            .collect();
+        assert_eq!(
+            frames[0].burst_uuid.as_deref(),
+            Some("5D9A3C1E-7B2F-4A61-9E08-C4F1B2A3D456")
+        );
+        assert_eq!(frames[0].sequence_number, None);
+
+        // Three seconds apart, grouped by their identifier only
+        assert_eq!(group_bursts(frames, Duration::milliseconds(1000)).len(), 1);
+    }
+
+    #[test]
+    fn test_get_burst_id() {
+        let burst = vec![
+            frame("IMG_0001.jpg", "Canon", "10:11:12.250"),
+            frame("IMG_0002.jpg", "Canon", "10:11:12.500"),
+        ];
+        assert_eq!(get_burst_id(&burst), "20230304-101112_IMG_0001");
+    }
+
+    #[test]
+    fn test_get_sharpness() {
+        let tmp_dir = TempDir::new("sharpness").unwrap();
+        let sharp = tmp_dir.path().join("sharp.png");
+        GrayImage::from_fn(32, 32, |x, y| {
+            Luma([if (x / 4 + y / 4) % 2 == 0 { 0 } else { 255 }])
+        })
+        .save(&sharp)
+        .unwrap();
+        let blurred = tmp_dir.path().join("blurred.png");
+        GrayImage::from_fn(32, 32, |x, _| Luma([(x * 8) as u8]))
+            .save(&blurred)
+            .unwrap();
+
+        assert!(get_sharpness(&sharp).unwrap() > get_sharpness(&blurred).unwrap());
+        assert!(get_sharpness(&tmp_dir.path().join("missing.png")).is_err());
+    }
+
+    #[test]
+    fn test_detect_with_duplicates() {
+        // The two copies of the Paris photo have the same camera and capture date
+        let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let first = data.join("Paris-20230304-duplicated.jpg");
+        let second = data.join("Paris-20230304.jpg");
+        let files = [first.clone(), second.clone()];
+        let interval = Duration::milliseconds(DEFAULT_BURST_INTERVAL as i64);
+
+        let bursts = Bursts::detect(&files, interval, BurstMode::First, &HashSet::new());
+        assert!(!bursts.is_dropped(&first));
+        assert!(bursts.is_dropped(&second));
+
+        // The first frame is dropped as a duplicate, so the next one is kept
+        let duplicates = HashSet::from([first.clone()]);
+        for mode in [BurstMode::First, BurstMode::Sharpest] {
+            let bursts = Bursts::detect(&files, interval, mode, &duplicates);
+            assert!(bursts.is_dropped(&first));
+            assert!(!bursts.is_dropped(&second));
+        }
+    }
+
+    #[test]
+    fn test_get_destination_sanitized() {
+        let path = PathBuf::from("IMG:0001.jpg");
+        let bursts = Bursts {
+            mode: BurstMode::Subfolder,
+            ids: HashMap::from([(path.clone(), "20230304-101112_IMG:0001".to_string())]),
+            dropped: HashSet::new(),
+            sanitizer: Sanitizer::new(SanitizeProfile::Windows, None),
+        };
+        assert_eq!(
+            bursts.get_destination(&path, PathBuf::from("/destination/2023/IMG_0001.jpg")),
+            PathBuf::from("/destination/2023/20230304-101112_IMG_0001/IMG_0001.jpg")
+        );
+    }
+
+    #[test]
+    fn test_burst_mode_from_string() {
+        assert_eq!(
+            BurstMode::from_string("sharpest").unwrap(),
+            BurstMode::Sharpest
+        );
+        assert!(BurstMode::from_string("last").is_err());
+    }
+}
@@ -1,23 +1,44 @@
 use indexmap::IndexMap;
-use std::process::exit;
 
+use crate::bursts::BurstMode;
+use crate::bursts::DEFAULT_BURST_INTERVAL;
+use crate::clock_correction::ClockCorrection;
+use crate::config_file::ConfigFile;
 use crate::errors::ClineupError;
 use crate::gps::gpsenum::GpsResolutionProviderImpl;
 use crate::organizer::OrganizationMode;
+use crate::path::companions::DEFAULT_COMPANION_EXTENSIONS;
+use crate::path::duplicates_finder::HashAlgorithm;
+use crate::path::iterator::get_exclude_globs;
+use crate::path::iterator::read_file_list;
+use crate::path::sanitizer::{SanitizeProfile, Sanitizer, UnicodeNormalization};
 use crate::placeholders::Placeholder;
-use crate::utils::is_there_a_location_placeholder;
+use crate::plan::PlanFormat;
+use crate::rules::Rule;
+use crate::summary::SummaryMode;
+use crate::template_command::check_reverse_geocoding;
+use crate::timezone::TimezoneTarget;
 use crate::utils::print_error;
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, SubCommand};
 use env_logger;
-use log::{error, LevelFilter};
+use ignore::overrides::Override;
+use log::LevelFilter;
 use regex::Regex;
+use std::path::Path;
+use std::path::PathBuf;
 
 // Configuration struct for the photo organizer
 #[derive(Debug, Clone)]
 pub struct Config {
-    pub source: String,
+    pub sources: Vec<String>,
+    pub listed_files: Vec<PathBuf>,
     pub destination: String,
     pub recursive: bool,
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    pub skip_hidden: bool,
+    pub one_file_system: bool,
+    pub exclude_globs: Vec<Override>,
     pub extensions: Option<Vec<String>>,
     pub exclude_extensions: Option<Vec<String>>,
     pub include_regex: Option<Regex>,
@@ -25,28 +46,62 @@ pub struct Config {
     pub size_greater: Option<u64>,
     pub size_lower: Option<u64>,
     pub dry_run: bool,
-    pub dry_run_number_of_files: u64,
+    pub dry_run_number_of_files: Option<usize>,
+    pub summary_mode: SummaryMode,
+    pub limit: Option<usize>,
+    pub offset: usize,
+    pub sample: Option<usize>,
+    pub seed: Option<u64>,
+    pub plan_output: Option<String>,
     pub log_file: Option<String>,
     pub verbosity: u64,
     pub gps_optimization: bool,
     pub drop_duplicates: bool,
+    pub dedupe_against: Option<String>,
+    pub duplicates_hash_algorithm: HashAlgorithm,
     pub strategy: Option<OrganizationMode>,
     pub reverse_geocoding: Option<GpsResolutionProviderImpl>,
     pub nominatim_email: Option<String>,
     pub folder_format: Option<String>,
     pub filename_format: Option<String>,
+    pub timezone: Option<TimezoneTarget>,
+    pub clock_corrections: Vec<ClockCorrection>,
+    pub hash_algorithm: HashAlgorithm,
+    pub hash_length: Option<usize>,
+    pub counter_width: usize,
+    pub pair_live_photos: bool,
+    pub companion_extensions: Vec<String>,
+    pub burst_mode: BurstMode,
+    pub burst_interval: u64,
+    pub sanitizer: Sanitizer,
+    pub rules: Vec<Rule>,
 }
 
 // Define the command-line parameters using the 'clap' crate
 fn define_cli_parameters() -> App<'static, 'static> {
     App::new("Clineup").about("Utility tool for organizing media")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("source")
                 .long("source")
                 .value_name("SOURCE")
                 .help("Specifies the source directory or file to be organized")
+                .long_help("Specifies the source directory or file to be organized \n
+                The option can be repeated to organize several sources, like '--source ~/DCIM --source ~/Downloads/photo.jpg'")
                 .takes_value(true)
-                .required(true),
+                .multiple(true)
+                .number_of_values(1)
+                .required_unless("files-from"),
+        )
+        .arg(
+            Arg::with_name("files-from")
+                .long("files-from")
+                .value_name("FILE")
+                .help("Organizes the files listed in the file, or in the standard input for '-'")
+                .long_help("Organizes the files listed in the file, or in the standard input for '-' \n
+                The paths are separated by new lines, or by NUL characters like the output of 'find -print0', \n
+                like 'find ~/DCIM -newer last_run | clineup --files-from - ...'. The listed directories are skipped")
+                .takes_value(true),
         )
         .arg(
             Arg::with_name("destination")
@@ -63,6 +118,41 @@ fn define_cli_parameters() -> App<'static, 'static> {
                 .long("recursive")
                 .help("Performs the organization process recursively on subdirectories"),
         )
+        .arg(
+            Arg::with_name("max-depth")
+                .long("max-depth")
+                .value_name("DEPTH")
+                .help("Specifies the maximum depth of the recursion, the files of the source directory being at depth 1")
+                .requires("recursive")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("follow-symlinks")
+                .long("follow-symlinks")
+                .help("Follows the symbolic links to directories, the loops being skipped"),
+        )
+        .arg(
+            Arg::with_name("skip-hidden")
+                .long("skip-hidden")
+                .help("Skips the hidden files and directories, whose name starts with a dot"),
+        )
+        .arg(
+            Arg::with_name("one-file-system")
+                .long("one-file-system")
+                .help("Does not descend into directories on other file systems than the source"),
+        )
+        .arg(
+            Arg::with_name("exclude-glob")
+                .long("exclude-glob")
+                .value_name("PATTERN")
+                .help("Excludes the files and directories matching the pattern, with the gitignore syntax")
+                .long_help("Excludes the files and directories matching the pattern, with the gitignore syntax \n
+                The matching directories are skipped without walking through them, like '--exclude-glob .thumbnails' or \n
+                '--exclude-glob \"@eaDir/\"'. The patterns are relative to the source directory. \n
+                The patterns of the .clineupignore files of the source directory and its subdirectories are excluded as well")
+                .takes_value(true)
+                .multiple(true),
+        )
         .arg(
             Arg::with_name("extension")
                 .long("extension")
@@ -118,10 +208,81 @@ fn define_cli_parameters() -> App<'static, 'static> {
                 .long("dry-run")
                 .help("Performs a dry run without actually moving or renaming any files"),
         )
+        .arg(
+            Arg::with_name("plan-output")
+                .long("plan-output")
+                .value_name("FILE")
+                .help("Writes the planned organization of every file to a .json or .csv file instead of organizing them")
+                .long_help("Writes the planned organization of every file to a .json or .csv file instead of organizing them. \n
+                Each file has its source, destination, strategy, whether it is a duplicate, whether its destination \n
+                already exists or is planned for another file, and the placeholders which fell back. \n
+                The plan can be edited, then executed with 'clineup apply --plan <FILE>'")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("dry-run-number-of-files")
                 .long("dry-run-number-of-files")
-                .help("Specifies the number of files to be processed by the dry run"),
+                .value_name("NUMBER")
+                .help("Specifies the number of planned destinations printed by the dry run, or 'all'. The summary covers every file [default: 10]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("summary")
+                .long("summary")
+                .value_name("MODE")
+                .help("Specifies how the dry run is summarized")
+                .long_help("Specifies how the dry run is summarized \n
+                - stats : Prints the first planned destinations, then the number of files per destination folder, \n
+                the placeholders which fell back and the number of duplicates \n
+                - tree : Prints the tree of the destination folders with their number of files and total size \n
+                instead of the planned destinations, the folders named after a fallback being highlighted \n")
+                .possible_values(&["stats", "tree"])
+                .requires("dry-run")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("limit")
+                .long("limit")
+                .value_name("NUMBER")
+                .help("Organizes at most this number of files, in dry and real runs")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("offset")
+                .long("offset")
+                .value_name("NUMBER")
+                .help("Skips this number of files, sorted by path, to resume a copy stopped by --limit. A move is resumed without offset")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .long("sample")
+                .value_name("NUMBER")
+                .help("Organizes a random sample of this number of files")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("NUMBER")
+                .help("Seed of the random sample, to get the same sample again")
+                .requires("sample")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .help("TOML configuration file")
+                .long_help("TOML configuration file. \n
+                The clock of a camera can be corrected with a 'clock_corrections' entry, matched by \n
+                'camera_brand', 'camera_model' and optionally 'serial_number'. The correction is either a \n
+                'delta' like '-2h13m', or a 'camera_time' and 'actual_time' reference pair : \n
+                [[clock_corrections]] \n
+                camera_model = \"Canon EOS R5\" \n
+                delta = \"-2h13m\"
+                ")
+                .takes_value(true),
         )
         .arg(
             Arg::with_name("verbose")
@@ -141,6 +302,118 @@ fn define_cli_parameters() -> App<'static, 'static> {
                 .takes_value(true)
                 .help("Specifies the filename format to create"),
         )
+        .arg(
+            Arg::with_name("timezone")
+                .long("timezone")
+                .value_name("TIMEZONE")
+                .help("Converts the dates to the given timezone before computing the date placeholders")
+                .long_help("Converts the dates to the given timezone before computing the date placeholders \n
+                - local : Timezone of the machine \n
+                - utc : Coordinated Universal Time \n
+                - gps : Time zone of the GPS position, found offline, with its daylight saving time \n
+                - A fixed offset like '+02:00' or '-0530' \n
+                The offset of EXIF dates is read from the OffsetTimeOriginal tag or deduced from the GPS timestamp.
+                EXIF dates with an unknown offset are considered to already be in the given timezone.
+                ")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hash-algorithm")
+                .long("hash-algorithm")
+                .help("Specifies the hash algorithm of the %hash placeholder")
+                .possible_values(&["sha256", "sha512", "xxh3", "blake3"])
+                .default_value("sha256")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hash-length")
+                .long("hash-length")
+                .value_name("LENGTH")
+                .help("Specifies the number of characters kept by the %hash placeholder. The full hash is kept by default")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("counter-width")
+                .long("counter-width")
+                .value_name("WIDTH")
+                .help("Specifies the minimum number of digits of the %counter placeholder, which is padded with zeros")
+                .default_value("4")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no-live-photo-pairing")
+                .long("no-live-photo-pairing")
+                .help("Organizes the videos of Live Photos and motion photos on their own instead of next to their photo")
+                .long_help("Organizes the videos of Live Photos and motion photos on their own instead of next to their photo \n
+                By default, a video is paired with the photo of the same folder with the same stem, like IMG_0001.HEIC \n
+                and IMG_0001.MOV, or else with the same Apple content identifier. It is then organized next to the photo, \n
+                with the name of the photo and its own extension")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("companion-extension")
+                .long("companion-extension")
+                .value_name("EXTENSION")
+                .help("Specifies the extensions of the sidecars which follow the file of the same stem [default: aae, lrv, srt, thm, xmp]")
+                .long_help("Specifies the extensions of the sidecars which follow the file of the same stem [default: aae, lrv, srt, thm, xmp] \n
+                A sidecar, like IMG_0001.xmp or IMG_0001.CR2.xmp, is organized by the same strategy as the file of the same folder \n
+                with the same stem, or named after its full name, with the resolved name of this file and its own extension. \n
+                It replaces the companion_extensions of the configuration file. Use 'none' to organize the sidecars on their own")
+                .takes_value(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("bursts")
+                .long("bursts")
+                .value_name("MODE")
+                .help("Specifies what is done with the frames of the bursts and exposure brackets")
+                .long_help("Specifies what is done with the frames of the bursts and exposure brackets \n
+                A burst is a sequence of photos of the same camera taken within --burst-interval of each other, \n
+                or the consecutive frames of an automatic exposure bracketing. A gap in the EXIF image numbers ends a burst. \n
+                - keep-all : Organizes every frame, the %burst_id placeholder naming their burst \n
+                - subfolder : Organizes every frame in a subfolder named after its burst \n
+                - first : Organizes only the first frame of each burst \n
+                - sharpest : Organizes only the sharpest frame of each burst, which is slower since every frame is decoded \n")
+                .possible_values(&["keep-all", "subfolder", "first", "sharpest"])
+                .default_value("keep-all")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("burst-interval")
+                .long("burst-interval")
+                .value_name("MS")
+                .help("Specifies the maximum time between two consecutive frames of a burst, in milliseconds [default: 1000]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sanitize")
+                .long("sanitize")
+                .help("Specifies the file system the destination paths must be valid on")
+                .long_help("Specifies the file system the destination paths must be valid on \n
+                - posix : Replaces '/' in the placeholder values and truncates names to 255 bytes \n
+                - windows : Also replaces <>:\"\\|?* and control characters, removes trailing dots and spaces, renames reserved names like CON and limits paths to 260 characters \n
+                - exfat : Same as windows, with paths limited to 32767 characters
+                ")
+                .possible_values(&["posix", "windows", "exfat"])
+                .default_value("posix")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sanitize-replacement")
+                .long("sanitize-replacement")
+                .value_name("REPLACEMENT")
+                .help("Specifies the string replacing the reserved characters. They are stripped if it is empty")
+                .default_value("_")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("unicode-normalization")
+                .long("unicode-normalization")
+                .help("Specifies the Unicode normalization form of the destination paths, which are kept as they are by default")
+                .possible_values(&["nfc", "nfd", "none"])
+                .default_value("none")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("gps-optimization")
                 .long("gps-optimization")
@@ -162,9 +435,31 @@ fn define_cli_parameters() -> App<'static, 'static> {
                 .long_help("Drop duplicates depending on the strategy \n
                 - Copy : Do not copy the duplicates \n
                 - Symlink : Do not symlink the duplicates \n
-                - Move : Do not move the duplicates
+                - Move : Do not move the duplicates \n
+                Only the files with an identical content are duplicates, the similar images being found by `clineup duplicates --perceptual`
                 ")
         )
+        .arg(
+            Arg::with_name("duplicates-hash-algorithm")
+                .long("duplicates-hash-algorithm")
+                .value_name("ALGORITHM")
+                .help("Specifies the hash algorithm used to find the duplicates, xxh3 being the fastest")
+                .possible_values(&["sha256", "sha512", "xxh3", "blake3"])
+                .default_value("sha256")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("dedupe-against")
+                .long("dedupe-against")
+                .value_name("DIR")
+                .help("Drops the files already in this library, the destination by default, whatever their name")
+                .long_help("Drops the files already in this library, the destination by default, whatever their name \n
+                The files of the library are indexed by size, and hashed only when a file of the same size is organized. \n
+                The duplicates of the current run are dropped as well, as with --drop-duplicates")
+                .takes_value(true)
+                .min_values(0)
+                .max_values(1),
+        )
         .arg(
             Arg::with_name("reverse-geocoding")
                 .long("reverse-geocoding")
@@ -178,9 +473,178 @@ fn define_cli_parameters() -> App<'static, 'static> {
                 .help("Email to use for nominatim API. This is mandatory following the nominatim usage policy")
                 .takes_value(true),
         )
+        .subcommand(
+            SubCommand::with_name("index")
+                .about("Manages the persistent hash index of the destination, updated by every run")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("rebuild")
+                        .about("Hashes every file of the destination again")
+                        .arg(index_destination_arg())
+                        .arg(
+                            Arg::with_name("hash-algorithm")
+                                .long("hash-algorithm")
+                                .value_name("ALGORITHM")
+                                .help("Specifies the hash algorithm of the index, the one given to --duplicates-hash-algorithm")
+                                .possible_values(&["sha256", "sha512", "xxh3", "blake3"])
+                                .default_value("sha256")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("verify")
+                        .about("Hashes the indexed files again to detect corrupted files, without updating the index")
+                        .arg(index_destination_arg()),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("duplicates")
+                .about("Finds the files with the same content in a folder, then reports, quarantines or hard links them")
+                .arg(
+                    Arg::with_name("folder")
+                        .value_name("FOLDER")
+                        .help("The folder to check, recursively")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("action")
+                        .long("action")
+                        .help("What is done with the duplicates")
+                        .long_help("What is done with the duplicates \n
+                        - report : Only prints the duplicate groups \n
+                        - quarantine : Moves the duplicates to the _duplicates folder, keeping their relative path \n
+                        - hardlink : Replaces the duplicates with a hard link to the kept copy")
+                        .possible_values(&["report", "quarantine", "hardlink"])
+                        .default_value("report")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("report")
+                        .long("report")
+                        .value_name("FILE")
+                        .help("Writes the duplicate groups to a .json or .csv file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("keep")
+                        .long("keep")
+                        .help("Which copy of each group is kept, the ties being broken by the oldest one [default: oldest]")
+                        .long_help("Which copy of each group is kept, the ties being broken by the oldest one \n
+                        - oldest : The oldest modification time \n
+                        - shortest-path : The shortest path \n
+                        - preferred : The first path matching --prefer-regex \n
+                        - most-exif : The most EXIF metadata \n
+                        - highest-resolution : The most pixels, the default with --perceptual \n
+                        The default is oldest")
+                        .possible_values(&["oldest", "shortest-path", "preferred", "most-exif", "highest-resolution"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("prefer-regex")
+                        .long("prefer-regex")
+                        .value_name("REGEX")
+                        .help("The regex matched against the full path of the copy to keep")
+                        .required_if("keep", "preferred")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("perceptual")
+                        .long("perceptual")
+                        .help("Finds the similar JPEG and PNG images, like the same photo resized or re-encoded, instead of the identical files. Only done by this subcommand, an import dropping the identical files"),
+                )
+                .arg(
+                    Arg::with_name("max-distance")
+                        .long("max-distance")
+                        .value_name("BITS")
+                        .help("Specifies the maximum number of different bits, out of 64, between the perceptual hashes of similar images [default: 10]")
+                        .requires("perceptual")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("hash-algorithm")
+                        .long("hash-algorithm")
+                        .help("Specifies the hash algorithm used to compare the files")
+                        .possible_values(&["sha256", "sha512", "xxh3", "blake3"])
+                        .default_value("sha256")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("apply")
+                .about("Organizes the files exactly as planned by --plan-output")
+                .arg(
+                    Arg::with_name("plan")
+                        .long("plan")
+                        .value_name("FILE")
+                        .help("The .json or .csv plan to execute, duplicates being skipped")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("template")
+                .about("Checks or previews a format without organizing any file")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("check")
+                        .about("Lists the placeholders of a format and where their values come from")
+                        .arg(template_format_arg())
+                        .arg(
+                            Arg::with_name("reverse-geocoding")
+                                .long("reverse-geocoding")
+                                .help("Reverse geocoding provider that would be used")
+                                .possible_values(&["nominatim"])
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("preview")
+                        .about("Prints the destination of sample files and the value of each placeholder")
+                        .arg(template_format_arg())
+                        .arg(
+                            Arg::with_name("files")
+                                .value_name("FILE")
+                                .help("Sample files to resolve")
+                                .required(true)
+                                .multiple(true)
+                                .index(2),
+                        )
+                        .arg(
+                            Arg::with_name("reverse-geocoding")
+                                .long("reverse-geocoding")
+                                .help("Reverse geocoding provider to use")
+                                .possible_values(&["nominatim"])
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("nominatim-email")
+                                .long("nominatim-email")
+                                .help("Email to use for nominatim API. This is mandatory following the nominatim usage policy")
+                                .takes_value(true),
+                        ),
+                ),
+        )
+}
+
+fn index_destination_arg() -> Arg<'static, 'static> {
+    Arg::with_name("destination")
+        .long("destination")
+        .value_name("DESTINATION")
+        .help("Specifies the destination directory, where the index is stored")
+        .required(true)
+        .takes_value(true)
 }
 
-fn convert_size_to_bytes(size: &str) -> Result<u64, ClineupError> {
+fn template_format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("format")
+        .value_name("FORMAT")
+        .help("Format of the destination path, like '%year/{%city|Unknown}/%original_filename'")
+        .required(true)
+        .index(1)
+}
+
+pub fn convert_size_to_bytes(size: &str) -> Result<u64, ClineupError> {
     let re = Regex::new(r"(?P<number>[0-9]+)(?P<unit>[KMGTP]?)[Bo]?")?;
 
     if let Some(capture) = re.captures(size) {
@@ -227,7 +691,7 @@ pub fn init_logger(verbosity: u64) {
     }
 }
 
-fn get_geocoding_enum(
+pub fn get_geocoding_enum(
     _enum: Option<&str>,
 ) -> Result<Option<GpsResolutionProviderImpl>, ClineupError> {
     if let Some(_good_enum) = _enum {
@@ -243,12 +707,7 @@ fn get_geocoding_enum(
 }
 fn get_strategy_enum(_enum: Option<&str>) -> Result<Option<OrganizationMode>, ClineupError> {
     if let Some(_good_enum) = _enum {
-        match _good_enum {
-            "copy" => Ok(Some(OrganizationMode::Copy)),
-            "symlink" => Ok(Some(OrganizationMode::Symlinks)),
-            "move" => Ok(Some(OrganizationMode::Move)),
-            _ => Err(ClineupError::InvalidOrganization(_good_enum.to_string())),
-        }
+        OrganizationMode::from_string(_good_enum).map(Some)
     } else {
         Ok(None)
     }
@@ -268,6 +727,33 @@ fn get_size_lower(size_lower: Option<&str>) -> Result<Option<u64>, ClineupError>
     }
 }
 
+pub fn get_number(number: Option<&str>) -> Result<Option<usize>, ClineupError> {
+    if let Some(number_str) = number {
+        number_str
+            .parse::<usize>()
+            .map(Some)
+            .map_err(|_| ClineupError::InvalidNumberFormat(number_str.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+fn get_config_file(config_file: Option<&str>) -> Result<ConfigFile, ClineupError> {
+    if let Some(config_file_path) = config_file {
+        ConfigFile::from_file(Path::new(config_file_path))
+    } else {
+        Ok(ConfigFile::default())
+    }
+}
+
+fn get_timezone(timezone: Option<&str>) -> Result<Option<TimezoneTarget>, ClineupError> {
+    if let Some(timezone_str) = timezone {
+        TimezoneTarget::from_string(timezone_str).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
 fn convert_to_regex(regex: Option<&str>) -> Result<Option<Regex>, ClineupError> {
     if let Some(regex_str) = regex {
         Ok(Some(Regex::new(regex_str)?))
@@ -288,9 +774,101 @@ pub fn get_cli_config(matches: clap::ArgMatches) -> Config {
     let reverse_geocoding =
         get_geocoding_enum(matches.value_of("reverse-geocoding")).unwrap_or_else(print_error);
     let strategy = get_strategy_enum(matches.value_of("strategy")).unwrap_or_else(print_error);
+    let timezone = get_timezone(matches.value_of("timezone")).unwrap_or_else(print_error);
+    let config_file = get_config_file(matches.value_of("config")).unwrap_or_else(print_error);
+    let hash_algorithm = HashAlgorithm::from_string(matches.value_of("hash-algorithm").unwrap())
+        .unwrap_or_else(print_error);
+    let duplicates_hash_algorithm =
+        HashAlgorithm::from_string(matches.value_of("duplicates-hash-algorithm").unwrap())
+            .unwrap_or_else(print_error);
+    let hash_length = get_number(matches.value_of("hash-length")).unwrap_or_else(print_error);
+    let counter_width = get_number(matches.value_of("counter-width"))
+        .unwrap_or_else(print_error)
+        .unwrap_or(4);
+    // The command line replaces the configuration file, which replaces the default extensions
+    let companion_extensions: Vec<String> = match matches.values_of("companion-extension") {
+        Some(values) => values.map(|value| value.to_string()).collect(),
+        None => config_file.companion_extensions.clone().unwrap_or(
+            DEFAULT_COMPANION_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+        ),
+    }
+    .into_iter()
+    .filter(|extension| extension != "none")
+    .map(|extension| extension.replace('.', "").to_ascii_lowercase())
+    .collect();
+    let burst_mode = BurstMode::from_string(matches.value_of("bursts").unwrap_or("keep-all"))
+        .unwrap_or_else(print_error);
+    let burst_interval = get_number(matches.value_of("burst-interval"))
+        .unwrap_or_else(print_error)
+        .map_or(DEFAULT_BURST_INTERVAL, |interval| interval as u64);
+    let sanitize_profile =
+        SanitizeProfile::from_string(matches.value_of("sanitize").unwrap_or("posix"))
+            .unwrap_or_else(print_error);
+    let unicode_normalization = UnicodeNormalization::from_string(
+        matches.value_of("unicode-normalization").unwrap_or("none"),
+    )
+    .unwrap_or_else(print_error);
+    let sanitizer = Sanitizer::new(sanitize_profile, unicode_normalization).with_replacement(
+        matches
+            .value_of("sanitize-replacement")
+            .unwrap_or("_")
+            .to_string(),
+    );
 
-    let dry_number_of_files_str = matches.value_of("dry-run-number-of-files").unwrap_or("10");
-    let dry_number_of_files = dry_number_of_files_str.parse::<u64>().unwrap_or(10);
+    // Checked before organizing anything, the plan being written at the end
+    if let Some(plan_output) = matches.value_of("plan-output") {
+        PlanFormat::from_path(Path::new(plan_output)).unwrap_or_else(print_error);
+    }
+
+    let dry_run_number_of_files = match matches.value_of("dry-run-number-of-files") {
+        Some("all") => None,
+        Some(number) => get_number(Some(number)).unwrap_or_else(print_error),
+        None => Some(10),
+    };
+    let summary_mode = SummaryMode::from_string(matches.value_of("summary").unwrap_or("stats"))
+        .unwrap_or_else(print_error);
+    let limit = get_number(matches.value_of("limit")).unwrap_or_else(print_error);
+    let offset = get_number(matches.value_of("offset"))
+        .unwrap_or_else(print_error)
+        .unwrap_or(0);
+    let sample = get_number(matches.value_of("sample")).unwrap_or_else(print_error);
+    let seed = get_number(matches.value_of("seed"))
+        .unwrap_or_else(print_error)
+        .map(|seed| seed as u64);
+
+    // Without a value, the library to dedupe against is the destination
+    let destination = matches.value_of("destination").unwrap().to_string();
+    let dedupe_against = if matches.is_present("dedupe-against") {
+        let library = matches.value_of("dedupe-against");
+        if let Some(library) = library.filter(|library| !Path::new(library).is_dir()) {
+            print_error(ClineupError::InvalidLibrary(library.to_string()))
+        }
+        Some(library.unwrap_or(&destination).to_string())
+    } else {
+        None
+    };
+
+    let max_depth = get_number(matches.value_of("max-depth")).unwrap_or_else(print_error);
+    let exclude_globs: Vec<String> = matches
+        .values_of("exclude-glob")
+        .map_or_else(Vec::new, |values| {
+            values.map(|value| value.to_string()).collect()
+        });
+    let sources: Vec<String> = matches.values_of("source").map_or_else(Vec::new, |values| {
+        values.map(|value| value.to_string()).collect()
+    });
+    // The patterns are relative to each source
+    let exclude_globs = sources
+        .iter()
+        .map(|source| get_exclude_globs(source, &exclude_globs).unwrap_or_else(print_error))
+        .collect();
+    let listed_files = matches
+        .value_of("files-from")
+        .map_or_else(|| Ok(Vec::new()), read_file_list)
+        .unwrap_or_else(print_error);
 
     let include_regex =
         convert_to_regex(matches.value_of("include-regex")).unwrap_or_else(print_error);
@@ -298,9 +876,15 @@ pub fn get_cli_config(matches: clap::ArgMatches) -> Config {
         convert_to_regex(matches.value_of("exclude-regex")).unwrap_or_else(print_error);
 
     Config {
-        source: matches.value_of("source").unwrap().to_string(),
-        destination: matches.value_of("destination").unwrap().to_string(),
+        sources,
+        listed_files,
+        destination,
         recursive: matches.is_present("recursive"),
+        max_depth,
+        follow_symlinks: matches.is_present("follow-symlinks"),
+        skip_hidden: matches.is_present("skip-hidden"),
+        one_file_system: matches.is_present("one-file-system"),
+        exclude_globs,
         extensions: matches.values_of("extension").map(|values| {
             values
                 .map(|e| e.replace('.', "").to_ascii_lowercase())
@@ -316,12 +900,22 @@ pub fn get_cli_config(matches: clap::ArgMatches) -> Config {
         size_greater,
         size_lower,
         dry_run: matches.is_present("dry-run"),
-        dry_run_number_of_files: dry_number_of_files,
+        dry_run_number_of_files,
+        summary_mode,
+        limit,
+        offset,
+        sample,
+        seed,
+        plan_output: matches
+            .value_of("plan-output")
+            .map(|plan_output| plan_output.to_string()),
         log_file: matches.value_of("log").map(|log| log.to_string()),
         verbosity: matches.occurrences_of("verbose"),
         strategy,
         gps_optimization: matches.is_present("gps-optimization"),
-        drop_duplicates: matches.is_present("drop-duplicates"),
+        drop_duplicates: matches.is_present("drop-duplicates") || dedupe_against.is_some(),
+        dedupe_against,
+        duplicates_hash_algorithm,
         reverse_geocoding,
         nominatim_email: matches
             .value_of("nominatim-email")
@@ -332,6 +926,17 @@ pub fn get_cli_config(matches: clap::ArgMatches) -> Config {
         filename_format: matches
             .value_of("filename-format")
             .map(|filename_format| filename_format.to_string()),
+        timezone,
+        clock_corrections: config_file.clock_corrections,
+        rules: config_file.rules,
+        hash_algorithm,
+        hash_length,
+        counter_width,
+        pair_live_photos: !matches.is_present("no-live-photo-pairing"),
+        companion_extensions,
+        burst_mode,
+        burst_interval,
+        sanitizer,
     }
 }
 
@@ -339,8 +944,6 @@ pub fn check_cli_config_from_placeholders(
     config: &Config,
     placeholders: &IndexMap<String, IndexMap<String, Placeholder>>,
 ) {
-    if is_there_a_location_placeholder(placeholders) && config.reverse_geocoding.is_none() {
-        error!("Location tag found but reverse geocoding provider is not set");
-        exit(1)
-    }
+    check_reverse_geocoding(placeholders, config.reverse_geocoding.as_ref())
+        .unwrap_or_else(print_error);
 }
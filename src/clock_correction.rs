@@ -0,0 +1,258 @@
+use chrono::{Duration, NaiveDateTime};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::errors::ClineupError;
+use crate::exif_extractor::ExifExtractor;
+
+/// Raw clock correction as written in the configuration file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawClockCorrection {
+    camera_brand: Option<String>,
+    camera_model: Option<String>,
+    serial_number: Option<String>,
+    delta: Option<String>,
+    camera_time: Option<String>,
+    actual_time: Option<String>,
+}
+
+/// Time shift to apply to the EXIF dates of a given camera
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "RawClockCorrection")]
+pub struct ClockCorrection {
+    camera_brand: Option<String>,
+    camera_model: Option<String>,
+    serial_number: Option<String>,
+    delta: Duration,
+}
+
+impl TryFrom<RawClockCorrection> for ClockCorrection {
+    type Error = ClineupError;
+
+    fn try_from(raw: RawClockCorrection) -> Result<Self, Self::Error> {
+        if raw.camera_brand.is_none() && raw.camera_model.is_none() {
+            return Err(ClineupError::InvalidClockCorrection(
+                "camera_brand or camera_model must be set".to_string(),
+            ));
+        }
+
+        let delta = match (raw.delta, raw.camera_time, raw.actual_time) {
+            (Some(delta), None, None) => parse_duration(&delta)?,
+            (None, Some(camera_time), Some(actual_time)) => {
+                get_delta_from_reference(&camera_time, &actual_time)?
+            }
+            _ => {
+                return Err(ClineupError::InvalidClockCorrection(
+                    "set either delta, or both camera_time and actual_time".to_string(),
+                ))
+            }
+        };
+
+        Ok(ClockCorrection::new(
+            raw.camera_brand,
+            raw.camera_model,
+            raw.serial_number,
+            delta,
+        ))
+    }
+}
+
+fn is_same_value(expected: &Option<String>, actual: Option<&str>) -> bool {
+    match expected {
+        None => true,
+        Some(expected) => {
+            actual.is_some_and(|actual| expected.trim().eq_ignore_ascii_case(actual.trim()))
+        }
+    }
+}
+
+impl ClockCorrection {
+    pub fn new(
+        camera_brand: Option<String>,
+        camera_model: Option<String>,
+        serial_number: Option<String>,
+        delta: Duration,
+    ) -> Self {
+        ClockCorrection {
+            camera_brand,
+            camera_model,
+            serial_number,
+            delta,
+        }
+    }
+
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    /// Checks whether the correction applies to the given camera.
+    /// Every criterion set in the correction must be equal, ignoring the case.
+    pub fn matches(
+        &self,
+        camera_brand: Option<&str>,
+        camera_model: Option<&str>,
+        serial_number: Option<&str>,
+    ) -> bool {
+        is_same_value(&self.camera_brand, camera_brand)
+            && is_same_value(&self.camera_model, camera_model)
+            && is_same_value(&self.serial_number, serial_number)
+    }
+}
+
+/// Parses a duration like `+2h13m`, `-1d`, `90s` or `-02:13:00`.
+///
+/// # Arguments
+///
+/// * `duration` - The duration to parse. It is positive if the sign is omitted.
+///
+/// # Returns
+///
+/// The parsed `Duration`, or an error if the format is not recognized.
+pub fn parse_duration(duration: &str) -> Result<Duration, ClineupError> {
+    let invalid = || ClineupError::InvalidDuration(duration.to_string());
+    let trimmed = duration.trim();
+
+    let clock_re = Regex::new(r"^(?P<sign>[+-]?)(?P<h>\d+):(?P<m>\d{2})(?::(?P<s>\d{2}))?$")?;
+    let units_re = Regex::new(
+        r"^(?P<sign>[+-]?)(?:(?P<d>\d+)d)?(?:(?P<h>\d+)h)?(?:(?P<m>\d+)m)?(?:(?P<s>\d+)s)?$",
+    )?;
+
+    let captures = clock_re
+        .captures(trimmed)
+        .or_else(|| units_re.captures(trimmed))
+        .ok_or_else(invalid)?;
+
+    let get = |name: &str| -> Result<i64, ClineupError> {
+        captures
+            .name(name)
+            .map_or(Ok(0), |value| value.as_str().parse().map_err(|_| invalid()))
+    };
+
+    if ["d", "h", "m", "s"]
+        .iter()
+        .all(|name| captures.name(name).is_none())
+    {
+        return Err(invalid());
+    }
+
+    let seconds = get("d")? * 86400 + get("h")? * 3600 + get("m")? * 60 + get("s")?;
+    let sign = if &captures["sign"] == "-" { -1 } else { 1 };
+
+    Ok(Duration::seconds(sign * seconds))
+}
+
+/// Computes the delta to add to the camera clock from a reference pair,
+/// like a photo of a clock showing `actual_time` taken at `camera_time`.
+pub fn get_delta_from_reference(
+    camera_time: &str,
+    actual_time: &str,
+) -> Result<Duration, ClineupError> {
+    let parse = |date: &str| {
+        NaiveDateTime::parse_from_str(date.trim(), "%Y-%m-%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(date.trim(), "%Y:%m:%d %H:%M:%S"))
+    };
+    Ok(parse(actual_time)?.signed_duration_since(parse(camera_time)?))
+}
+
+/// Returns the delta of the first correction matching the camera of the media,
+/// or a zero delta if none matches.
+pub fn find_clock_delta(
+    clock_corrections: &[ClockCorrection],
+    exif_extractor: &ExifExtractor,
+) -> Duration {
+    if clock_corrections.is_empty() {
+        return Duration::zero();
+    }
+
+    let camera_brand = exif_extractor.get_camera_brand().ok();
+    let camera_model = exif_extractor.get_camera_model().ok();
+    let serial_number = exif_extractor.get_serial_number().ok();
+
+    clock_corrections
+        .iter()
+        .find(|correction| {
+            correction.matches(
+                camera_brand.as_deref(),
+                camera_model.as_deref(),
+                serial_number.as_deref(),
+            )
+        })
+        .map_or_else(Duration::zero, |correction| correction.delta())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(
+            parse_duration("-2h13m").unwrap(),
+            -Duration::seconds(2 * 3600 + 13 * 60)
+        );
+        assert_eq!(parse_duration("1d").unwrap(), Duration::days(1));
+        assert_eq!(parse_duration("+90s").unwrap(), Duration::seconds(90));
+        assert_eq!(
+            parse_duration("-02:13:00").unwrap(),
+            -Duration::seconds(2 * 3600 + 13 * 60)
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("+").is_err());
+        assert!(parse_duration("2 hours").is_err());
+    }
+
+    #[test]
+    fn test_get_delta_from_reference() {
+        let delta = get_delta_from_reference("2016-01-01 00:00:00", "2016-01-02 02:13:00").unwrap();
+        assert_eq!(delta, Duration::seconds(86400 + 2 * 3600 + 13 * 60));
+    }
+
+    #[test]
+    fn test_matches() {
+        let correction = ClockCorrection::new(
+            Some("Canon".to_string()),
+            Some("Canon EOS R5".to_string()),
+            None,
+            Duration::zero(),
+        );
+        assert!(correction.matches(Some("CANON"), Some("Canon EOS R5"), Some("1234")));
+        assert!(!correction.matches(Some("Canon"), Some("Canon EOS R6"), None));
+        assert!(!correction.matches(None, Some("Canon EOS R5"), None));
+
+        let with_serial = ClockCorrection::new(
+            None,
+            Some("X".to_string()),
+            Some("42".to_string()),
+            Duration::zero(),
+        );
+        assert!(with_serial.matches(None, Some("X"), Some("42")));
+        assert!(!with_serial.matches(None, Some("X"), None));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let correction: ClockCorrection = toml::from_str(
+            r#"
+            camera_model = "HERO9 Black"
+            camera_time = "2016-01-01 00:00:00"
+            actual_time = "2016-01-01 01:00:00"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(correction.delta(), Duration::hours(1));
+
+        let missing_camera = toml::from_str::<ClockCorrection>(r#"delta = "+1h""#);
+        assert!(missing_camera.is_err());
+
+        let both_deltas = toml::from_str::<ClockCorrection>(
+            r#"
+            camera_brand = "Canon"
+            delta = "+1h"
+            camera_time = "2016-01-01 00:00:00"
+            actual_time = "2016-01-01 01:00:00"
+            "#,
+        );
+        assert!(both_deltas.is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::clock_correction::ClockCorrection;
+use crate::errors::ClineupError;
+use crate::rules::Rule;
+
+/// Content of the TOML configuration file given with `--config`
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub clock_corrections: Vec<ClockCorrection>,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// Extensions of the sidecars which follow the file of the same stem, replacing the default ones
+    pub companion_extensions: Option<Vec<String>>,
+}
+
+impl ConfigFile {
+    pub fn from_file(path: &Path) -> Result<ConfigFile, ClineupError> {
+        let content = std::fs::read_to_string(path)?;
+        ConfigFile::from_string(&content)
+    }
+
+    pub fn from_string(content: &str) -> Result<ConfigFile, ClineupError> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_string() {
+        let config_file = ConfigFile::from_string(
+            r#"
+            companion_extensions = ["xmp", "dop"]
+
+            [[clock_corrections]]
+            camera_brand = "Canon"
+            delta = "-2h13m"
+
+            [[clock_corrections]]
+            camera_model = "HERO9 Black"
+            serial_number = "C3441325"
+            delta = "+1d"
+
+            [[rules]]
+            name = "raw"
+            media_types = ["raw"]
+            folder_format = "raw/%year"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config_file.clock_corrections.len(), 2);
+        assert_eq!(config_file.rules.len(), 1);
+        assert_eq!(
+            config_file.companion_extensions,
+            Some(vec!["xmp".to_string(), "dop".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_from_string_unknown_field() {
+        assert!(ConfigFile::from_string("unknown = 1").is_err());
+    }
+}
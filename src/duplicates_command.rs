@@ -0,0 +1,657 @@
+use clap::ArgMatches;
+use indexmap::IndexMap;
+use log::{debug, error, info};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::SystemTime;
+
+use crate::cli::get_number;
+use crate::errors::ClineupError;
+use crate::exif_extractor::ExifExtractor;
+use crate::path::duplicates_finder::list_regular_files;
+use crate::path::duplicates_finder::DuplicatesFinder;
+use crate::path::duplicates_finder::HashAlgorithm;
+use crate::path::perceptual_hash::get_dhash;
+use crate::path::perceptual_hash::get_resolution;
+use crate::path::perceptual_hash::hamming_distance;
+use crate::path::perceptual_hash::DEFAULT_MAX_DISTANCE;
+use crate::plan::PlanFormat;
+use crate::utils::format_size_human;
+use crate::utils::print_error;
+
+/// Folder of the quarantined duplicates, at the root of the checked folder
+pub const QUARANTINE_FOLDER: &str = "_duplicates";
+
+/// Which copy of a duplicate group is kept, the others being the duplicates
+#[derive(Debug, Clone)]
+pub enum KeepPolicy {
+    /// The oldest modification time
+    Oldest,
+    ShortestPath,
+    /// The first path matching the regex, the oldest one if none matches
+    Preferred(Regex),
+    /// The most EXIF fields, the oldest one on a tie
+    MostExif,
+    /// The most pixels, which suits the near-duplicates
+    HighestResolution,
+}
+
+impl KeepPolicy {
+    pub fn from_string(
+        policy: &str,
+        prefer_regex: Option<&str>,
+    ) -> Result<KeepPolicy, ClineupError> {
+        match (policy, prefer_regex) {
+            ("oldest", _) => Ok(KeepPolicy::Oldest),
+            ("shortest-path", _) => Ok(KeepPolicy::ShortestPath),
+            ("preferred", Some(prefer_regex)) => {
+                Ok(KeepPolicy::Preferred(Regex::new(prefer_regex)?))
+            }
+            ("most-exif", _) => Ok(KeepPolicy::MostExif),
+            ("highest-resolution", _) => Ok(KeepPolicy::HighestResolution),
+            _ => Err(ClineupError::InvalidKeepPolicy(policy.to_string())),
+        }
+    }
+}
+
+/// What is done with the duplicates once they are found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DuplicatesAction {
+    Report,
+    /// Moved to `_duplicates/`, keeping their path relative to the checked folder
+    Quarantine,
+    /// Replaced with a hard link to the kept copy
+    Hardlink,
+}
+
+impl DuplicatesAction {
+    pub fn from_string(action: &str) -> Result<DuplicatesAction, ClineupError> {
+        match action {
+            "report" => Ok(DuplicatesAction::Report),
+            "quarantine" => Ok(DuplicatesAction::Quarantine),
+            "hardlink" => Ok(DuplicatesAction::Hardlink),
+            _ => Err(ClineupError::InvalidDuplicatesAction(action.to_string())),
+        }
+    }
+}
+
+/// Files with the same content, or similar images
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub kept: PathBuf,
+    pub duplicates: Vec<PathBuf>,
+    /// Space taken by the duplicates
+    pub duplicates_size: u64,
+}
+
+impl DuplicateGroup {
+    /// Builds a group, choosing the copy to keep with the policy.
+    fn new(mut files: Vec<PathBuf>, policy: &KeepPolicy) -> Self {
+        let kept = choose_kept(&files, policy);
+        files.retain(|file| *file != kept);
+        DuplicateGroup {
+            duplicates_size: files.iter().map(|file| get_size(file)).sum(),
+            kept,
+            duplicates: files,
+        }
+    }
+}
+
+/// Row of a CSV report, one for each file of a group
+#[derive(Debug, Serialize)]
+struct CsvDuplicateRow<'a> {
+    group: usize,
+    path: &'a Path,
+    size: u64,
+    kept: bool,
+}
+
+fn get_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |metadata| metadata.len())
+}
+
+fn get_modified(path: &Path) -> SystemTime {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+fn get_number_of_exif_fields(path: &Path) -> usize {
+    ExifExtractor::new(&path.to_path_buf()).map_or(0, |extractor| extractor.get_number_of_fields())
+}
+
+/// Chooses the copy to keep, the ties being broken by the oldest modification time, then by path.
+fn choose_kept(files: &[PathBuf], policy: &KeepPolicy) -> PathBuf {
+    let oldest = |file: &PathBuf| (get_modified(file), file.clone());
+    let kept = match policy {
+        KeepPolicy::Oldest => files.iter().min_by_key(|file| oldest(file)),
+        KeepPolicy::ShortestPath => files
+            .iter()
+            .min_by_key(|file| (file.as_os_str().len(), file.to_path_buf())),
+        KeepPolicy::Preferred(regex) => files
+            .iter()
+            .min_by_key(|file| (!regex.is_match(&file.to_string_lossy()), oldest(file))),
+        KeepPolicy::MostExif => files.iter().min_by_key(|file| {
+            (
+                std::cmp::Reverse(get_number_of_exif_fields(file)),
+                oldest(file),
+            )
+        }),
+        KeepPolicy::HighestResolution => files
+            .iter()
+            .min_by_key(|file| (std::cmp::Reverse(get_resolution(file)), oldest(file))),
+    };
+    // A group has at least two files
+    kept.unwrap().clone()
+}
+
+/// Finds the groups of files with the same content in a folder, recursively.
+/// The quarantine folder is skipped, so that the quarantined duplicates are not found again.
+///
+/// # Arguments
+///
+/// * `folder` - The folder to check.
+/// * `hash_algorithm` - The hash algorithm used to compare the files.
+/// * `policy` - Which copy of each group is kept.
+///
+/// # Returns
+///
+/// The duplicate groups, sorted by path of their first file.
+pub fn find_duplicate_groups(
+    folder: &Path,
+    hash_algorithm: HashAlgorithm,
+    policy: &KeepPolicy,
+) -> Result<Vec<DuplicateGroup>, ClineupError> {
+    let mut duplicates_finder = DuplicatesFinder::new().with_hash_algorithm(hash_algorithm);
+    let mut groups: IndexMap<PathBuf, Vec<PathBuf>> = IndexMap::new();
+
+    for path in list_checked_files(folder)? {
+        if let Some(original) = duplicates_finder.find_duplicate(&path)? {
+            groups
+                .entry(original.clone())
+                .or_insert_with(|| vec![original])
+                .push(path);
+        }
+    }
+
+    Ok(groups
+        .into_values()
+        .map(|files| DuplicateGroup::new(files, policy))
+        .collect())
+}
+
+/// Finds the groups of similar images in a folder, recursively, like the same photo resized or re-encoded.
+/// The files which are not images, or can not be decoded, are skipped.
+///
+/// # Arguments
+///
+/// * `folder` - The folder to check.
+/// * `max_distance` - The maximum number of different bits between the perceptual hashes of similar images.
+/// * `policy` - Which copy of each group is kept.
+///
+/// # Returns
+///
+/// The near-duplicate groups, sorted by path of their first image.
+pub fn find_near_duplicate_groups(
+    folder: &Path,
+    max_distance: u32,
+    policy: &KeepPolicy,
+) -> Result<Vec<DuplicateGroup>, ClineupError> {
+    // Each group is compared through its first image, so that a group does not drift from image to image
+    let mut groups: Vec<(u64, Vec<PathBuf>)> = Vec::new();
+
+    for path in list_checked_files(folder)? {
+        let dhash = match get_dhash(&path) {
+            Ok(dhash) => dhash,
+            Err(err) => {
+                debug!("Skipping {:?}: {}", path, err);
+                continue;
+            }
+        };
+        match groups
+            .iter_mut()
+            .find(|(group_dhash, _)| hamming_distance(*group_dhash, dhash) <= max_distance)
+        {
+            Some((_, files)) => files.push(path),
+            None => groups.push((dhash, vec![path])),
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(_, files)| DuplicateGroup::new(files, policy))
+        .collect())
+}
+
+/// Lists the files of the checked folder, without the quarantined duplicates, so that they are not found again.
+/// The symlinks are skipped, so that a file outside the folder is never moved or replaced.
+fn list_checked_files(folder: &Path) -> Result<Vec<PathBuf>, ClineupError> {
+    let quarantine = folder.canonicalize()?.join(QUARANTINE_FOLDER);
+    Ok(list_regular_files(folder)?
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| !path.starts_with(&quarantine))
+        .collect())
+}
+
+/// Describes the groups, with the space taken by the duplicates.
+pub fn describe_duplicate_groups(groups: &[DuplicateGroup]) -> String {
+    let duplicates: usize = groups.iter().map(|group| group.duplicates.len()).sum();
+    let reclaimable: u64 = groups.iter().map(|group| group.duplicates_size).sum();
+    let mut report = format!(
+        "{} duplicate group(s), {} duplicate(s) taking {}\n",
+        groups.len(),
+        duplicates,
+        format_size_human(reclaimable)
+    );
+    for group in groups {
+        report.push_str(&format!("{} (kept)\n", group.kept.display()));
+        for duplicate in &group.duplicates {
+            report.push_str(&format!("    {}\n", duplicate.display()));
+        }
+    }
+    report
+}
+
+/// Writes the groups as JSON or CSV, depending on the extension of the path.
+/// A CSV report has a row for each file, with the index of its group.
+pub fn write_duplicates_report(groups: &[DuplicateGroup], path: &Path) -> Result<(), ClineupError> {
+    match PlanFormat::from_path(path)? {
+        PlanFormat::Json => {
+            serde_json::to_writer_pretty(File::create(path)?, groups)?;
+        }
+        PlanFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)?;
+            for (index, group) in groups.iter().enumerate() {
+                let files = std::iter::once((&group.kept, true))
+                    .chain(group.duplicates.iter().map(|duplicate| (duplicate, false)));
+                for (file, kept) in files {
+                    writer.serialize(CsvDuplicateRow {
+                        group: index + 1,
+                        path: file,
+                        size: get_size(file),
+                        kept,
+                    })?;
+                }
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks that a file is a regular file of the checked folder, which is canonical, and not reached through a symlink.
+fn is_inside_folder(path: &Path, folder: &Path) -> bool {
+    let is_file = path
+        .symlink_metadata()
+        .is_ok_and(|metadata| metadata.is_file());
+    let parent = path.parent().and_then(|parent| parent.canonicalize().ok());
+    is_file && parent.is_some_and(|parent| parent.starts_with(folder))
+}
+
+/// Moves the duplicates to the quarantine folder, keeping their path relative to the checked folder.
+/// The files outside the folder are left untouched.
+///
+/// # Returns
+///
+/// The number of files moved.
+pub fn quarantine_duplicates(
+    folder: &Path,
+    groups: &[DuplicateGroup],
+) -> Result<usize, ClineupError> {
+    let folder = folder.canonicalize()?;
+    let mut moved_files = 0;
+    for duplicate in groups.iter().flat_map(|group| &group.duplicates) {
+        let relative_path = match duplicate.strip_prefix(&folder) {
+            Ok(relative_path) if is_inside_folder(duplicate, &folder) => relative_path,
+            _ => {
+                error!(
+                    "Not quarantining {:?}, which is outside {:?}",
+                    duplicate, folder
+                );
+                continue;
+            }
+        };
+        let quarantined = folder.join(QUARANTINE_FOLDER).join(relative_path);
+        if quarantined.exists() {
+            error!("{:?} is already quarantined", quarantined);
+            continue;
+        }
+        if let Some(parent) = quarantined.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        info!("Quarantining {:?} to {:?}", duplicate, quarantined);
+        std::fs::rename(duplicate, &quarantined)?;
+        moved_files += 1;
+    }
+    Ok(moved_files)
+}
+
+/// Replaces the duplicates with a hard link to the kept copy, which frees their space.
+/// The link is created next to the duplicate, then renamed over it, so that no file is lost on error.
+/// The files outside the checked folder are left untouched.
+///
+/// # Returns
+///
+/// The number of files replaced.
+pub fn hardlink_duplicates(
+    folder: &Path,
+    groups: &[DuplicateGroup],
+) -> Result<usize, ClineupError> {
+    let folder = folder.canonicalize()?;
+    let mut linked_files = 0;
+    for group in groups {
+        if !is_inside_folder(&group.kept, &folder) {
+            error!(
+                "Not linking to {:?}, which is outside {:?}",
+                group.kept, folder
+            );
+            continue;
+        }
+        for duplicate in &group.duplicates {
+            if !is_inside_folder(duplicate, &folder) {
+                error!(
+                    "Not replacing {:?}, which is outside {:?}",
+                    duplicate, folder
+                );
+                continue;
+            }
+            let mut link = duplicate.clone().into_os_string();
+            link.push(".clineup-link");
+            let link = PathBuf::from(link);
+
+            info!("Hard linking {:?} to {:?}", duplicate, group.kept);
+            std::fs::hard_link(&group.kept, &link)?;
+            if let Err(err) = std::fs::rename(&link, duplicate) {
+                std::fs::remove_file(&link)?;
+                return Err(err.into());
+            }
+            linked_files += 1;
+        }
+    }
+    Ok(linked_files)
+}
+
+/// Runs `clineup duplicates <FOLDER>`, then exits.
+pub fn run_duplicates_command(matches: &ArgMatches) -> ! {
+    let folder = Path::new(matches.value_of("folder").unwrap());
+    let hash_algorithm = HashAlgorithm::from_string(matches.value_of("hash-algorithm").unwrap())
+        .unwrap_or_else(print_error);
+    let perceptual = matches.is_present("perceptual");
+    let max_distance = get_number(matches.value_of("max-distance"))
+        .unwrap_or_else(print_error)
+        .map_or(DEFAULT_MAX_DISTANCE, |max_distance| max_distance as u32);
+    // The highest resolution is kept by default among near-duplicates, since they differ
+    let default_policy = if perceptual {
+        "highest-resolution"
+    } else {
+        "oldest"
+    };
+    let policy = KeepPolicy::from_string(
+        matches.value_of("keep").unwrap_or(default_policy),
+        matches.value_of("prefer-regex"),
+    )
+    .unwrap_or_else(print_error);
+    let action = DuplicatesAction::from_string(matches.value_of("action").unwrap())
+        .unwrap_or_else(print_error);
+    if perceptual && action == DuplicatesAction::Hardlink {
+        print_error::<()>(ClineupError::InvalidDuplicatesAction(
+            "hardlink, since near-duplicates are not identical".to_string(),
+        ));
+    }
+    // Checked before reading any file, the report being written at the end
+    if let Some(report_path) = matches.value_of("report") {
+        PlanFormat::from_path(Path::new(report_path)).unwrap_or_else(print_error);
+    }
+    if !folder.is_dir() {
+        print_error::<()>(ClineupError::InvalidLibrary(
+            folder.to_string_lossy().to_string(),
+        ));
+    }
+
+    let groups = if perceptual {
+        find_near_duplicate_groups(folder, max_distance, &policy)
+    } else {
+        find_duplicate_groups(folder, hash_algorithm, &policy)
+    }
+    .unwrap_or_else(print_error);
+    print!("{}", describe_duplicate_groups(&groups));
+
+    if let Some(report_path) = matches.value_of("report") {
+        write_duplicates_report(&groups, Path::new(report_path)).unwrap_or_else(print_error);
+        println!("Report written to {}", report_path);
+    }
+
+    match action {
+        DuplicatesAction::Report => {}
+        DuplicatesAction::Quarantine => {
+            let moved_files = quarantine_duplicates(folder, &groups).unwrap_or_else(print_error);
+            println!(
+                "{} duplicate(s) moved to {}",
+                moved_files,
+                folder.join(QUARANTINE_FOLDER).display()
+            );
+        }
+        DuplicatesAction::Hardlink => {
+            let linked_files = hardlink_duplicates(folder, &groups).unwrap_or_else(print_error);
+            println!("{} duplicate(s) replaced with a hard link", linked_files);
+        }
+    }
+    exit(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    /// Two groups : `a.jpg` with its copies in `b/` and `c/d/`, and `e.txt` with its copy `f.txt`
+    fn create_folder() -> TempDir {
+        let tmp_dir = TempDir::new("duplicates").unwrap();
+        let folder = tmp_dir.path();
+        std::fs::create_dir_all(folder.join("b")).unwrap();
+        std::fs::create_dir_all(folder.join("c/d")).unwrap();
+        for (name, content) in [
+            ("c/d/a.jpg", "photo"),
+            ("b/long_name.jpg", "photo"),
+            ("a.jpg", "photo"),
+            ("e.txt", "text"),
+            ("f.txt", "text"),
+            ("unique.jpg", "unique"),
+        ] {
+            std::fs::write(folder.join(name), content).unwrap();
+        }
+        tmp_dir
+    }
+
+    fn canonical(folder: &Path, name: &str) -> PathBuf {
+        folder.canonicalize().unwrap().join(name)
+    }
+
+    #[test]
+    fn test_find_duplicate_groups() {
+        let tmp_dir = create_folder();
+        let folder = tmp_dir.path();
+
+        let groups =
+            find_duplicate_groups(folder, HashAlgorithm::Xxh3, &KeepPolicy::ShortestPath).unwrap();
+        assert_eq!(
+            groups,
+            vec![
+                DuplicateGroup {
+                    kept: canonical(folder, "a.jpg"),
+                    duplicates: vec![
+                        canonical(folder, "b/long_name.jpg"),
+                        canonical(folder, "c/d/a.jpg")
+                    ],
+                    duplicates_size: 10,
+                },
+                DuplicateGroup {
+                    kept: canonical(folder, "e.txt"),
+                    duplicates: vec![canonical(folder, "f.txt")],
+                    duplicates_size: 4,
+                },
+            ]
+        );
+
+        let policy = KeepPolicy::from_string("preferred", Some("/c/")).unwrap();
+        let groups = find_duplicate_groups(folder, HashAlgorithm::Sha256, &policy).unwrap();
+        assert_eq!(groups[0].kept, canonical(folder, "c/d/a.jpg"));
+        // Without a preferred path, the oldest copy is kept
+        assert_eq!(groups[1].kept, canonical(folder, "e.txt"));
+
+        assert!(KeepPolicy::from_string("preferred", None).is_err());
+    }
+
+    #[test]
+    fn test_quarantine_duplicates() {
+        let tmp_dir = create_folder();
+        let folder = tmp_dir.path();
+
+        let groups =
+            find_duplicate_groups(folder, HashAlgorithm::Sha256, &KeepPolicy::ShortestPath)
+                .unwrap();
+        assert_eq!(quarantine_duplicates(folder, &groups).unwrap(), 3);
+        assert!(folder.join("a.jpg").exists());
+        assert!(!folder.join("c/d/a.jpg").exists());
+        assert!(folder.join("_duplicates/c/d/a.jpg").exists());
+        assert!(folder.join("_duplicates/b/long_name.jpg").exists());
+
+        // The quarantined duplicates are not found again
+        assert!(
+            find_duplicate_groups(folder, HashAlgorithm::Sha256, &KeepPolicy::Oldest)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_hardlink_duplicates() {
+        use std::os::unix::fs::MetadataExt;
+
+        let tmp_dir = create_folder();
+        let folder = tmp_dir.path();
+
+        let groups =
+            find_duplicate_groups(folder, HashAlgorithm::Sha256, &KeepPolicy::ShortestPath)
+                .unwrap();
+        assert_eq!(hardlink_duplicates(folder, &groups).unwrap(), 3);
+        let inode = |name: &str| std::fs::metadata(folder.join(name)).unwrap().ino();
+        assert_eq!(inode("a.jpg"), inode("c/d/a.jpg"));
+        assert_eq!(inode("e.txt"), inode("f.txt"));
+        assert_ne!(inode("a.jpg"), inode("unique.jpg"));
+        assert_eq!(
+            std::fs::read_to_string(folder.join("c/d/a.jpg")).unwrap(),
+            "photo"
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_duplicates_symlinks() {
+        let tmp_dir = TempDir::new("symlinks").unwrap();
+        let folder = tmp_dir.path().join("folder");
+        let outside = tmp_dir.path().join("outside");
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(folder.join("a.jpg"), "photo").unwrap();
+        std::fs::write(folder.join("b.jpg"), "photo").unwrap();
+        std::fs::write(outside.join("c.jpg"), "photo").unwrap();
+        std::os::unix::fs::symlink(outside.join("c.jpg"), folder.join("link.jpg")).unwrap();
+        std::os::unix::fs::symlink(&outside, folder.join("linked")).unwrap();
+
+        // The symlinks are not followed
+        let groups =
+            find_duplicate_groups(&folder, HashAlgorithm::Sha256, &KeepPolicy::ShortestPath)
+                .unwrap();
+        assert_eq!(
+            groups,
+            vec![DuplicateGroup {
+                kept: canonical(&folder, "a.jpg"),
+                duplicates: vec![canonical(&folder, "b.jpg")],
+                duplicates_size: 5,
+            }]
+        );
+
+        // The files outside the folder are never moved or replaced
+        let outside_groups = vec![
+            DuplicateGroup {
+                kept: canonical(&folder, "a.jpg"),
+                duplicates: vec![canonical(&outside, "c.jpg"), folder.join("link.jpg")],
+                duplicates_size: 10,
+            },
+            DuplicateGroup {
+                kept: canonical(&outside, "c.jpg"),
+                duplicates: vec![canonical(&folder, "b.jpg")],
+                duplicates_size: 5,
+            },
+        ];
+        assert_eq!(hardlink_duplicates(&folder, &outside_groups).unwrap(), 0);
+        assert_eq!(quarantine_duplicates(&folder, &outside_groups).unwrap(), 1);
+        assert!(outside.join("c.jpg").exists());
+        assert!(folder.join("link.jpg").is_symlink());
+        assert!(folder.join("_duplicates/b.jpg").exists());
+    }
+
+    #[test]
+    fn test_write_duplicates_report() {
+        let tmp_dir = create_folder();
+        let folder = tmp_dir.path();
+        let groups =
+            find_duplicate_groups(folder, HashAlgorithm::Sha256, &KeepPolicy::ShortestPath)
+                .unwrap();
+
+        let report_path = folder.join("report.json");
+        write_duplicates_report(&groups, &report_path).unwrap();
+        let read_groups: Vec<DuplicateGroup> =
+            serde_json::from_reader(File::open(&report_path).unwrap()).unwrap();
+        assert_eq!(read_groups, groups);
+
+        let report_path = folder.join("report.csv");
+        write_duplicates_report(&groups, &report_path).unwrap();
+        let content = std::fs::read_to_string(report_path).unwrap();
+        assert!(content.starts_with("group,path,size,kept\n"));
+        assert_eq!(content.lines().count(), 6);
+        assert!(content.contains("/f.txt,4,false\n"));
+    }
+
+    #[test]
+    fn test_find_near_duplicate_groups() {
+        let tmp_dir = TempDir::new("near_duplicates").unwrap();
+        let folder = tmp_dir.path();
+        let photo = image::RgbImage::from_fn(64, 48, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8])
+        });
+        photo.save(folder.join("original.png")).unwrap();
+        // The same photo, smaller and re-encoded, as sent by a messaging app
+        image::DynamicImage::ImageRgb8(photo.clone())
+            .resize(32, 24, image::imageops::FilterType::Triangle)
+            .save(folder.join("resized.jpg"))
+            .unwrap();
+        image::imageops::flip_horizontal(&photo)
+            .save(folder.join("other.png"))
+            .unwrap();
+        std::fs::write(folder.join("notes.txt"), "not an image").unwrap();
+
+        let groups = find_near_duplicate_groups(
+            folder,
+            DEFAULT_MAX_DISTANCE,
+            &KeepPolicy::HighestResolution,
+        )
+        .unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].kept, canonical(folder, "original.png"));
+        assert_eq!(groups[0].duplicates, vec![canonical(folder, "resized.jpg")]);
+
+        assert!(find_near_duplicate_groups(folder, 0, &KeepPolicy::Oldest)
+            .unwrap()
+            .iter()
+            .all(|group| !group.duplicates.contains(&canonical(folder, "other.png"))));
+    }
+}
@@ -12,6 +12,18 @@ pub enum ClineupError {
     #[error("Serde Json error: {0}")]
     SerdeJsonError(#[from] serde_json::Error),
 
+    #[error("Hash index error: {0}")]
+    HashIndexError(#[from] rusqlite::Error),
+
+    #[error("Image error: {0}")]
+    ImageError(#[from] image::ImageError),
+
+    #[error("Walk error: {0}")]
+    WalkError(#[from] ignore::Error),
+
+    #[error("CSV error: {0}")]
+    CsvError(#[from] csv::Error),
+
     #[error("Date time parsing error: {0}")]
     DateTimeParseError(#[from] chrono::ParseError),
 
@@ -51,8 +63,68 @@ pub enum ClineupError {
     #[error("Invalid organization strategy: {0}")]
     InvalidOrganization(String),
 
+    #[error("Invalid timezone: {0}. Use 'local', 'utc', 'gps' or an offset like '+02:00'")]
+    InvalidTimezone(String),
+
+    #[error("Timezone of the date is unknown")]
+    MissingTimezone,
+
+    #[error("Invalid configuration file: {0}")]
+    TomlError(#[from] toml::de::Error),
+
+    #[error("Invalid clock correction: {0}")]
+    InvalidClockCorrection(String),
+
+    #[error("Invalid duration: {0}. Use a duration like '-2h13m', '+1d' or '-02:13:00'")]
+    InvalidDuration(String),
+
+    #[error("Invalid hash algorithm: {0}")]
+    InvalidHashAlgorithm(String),
+
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+
+    #[error("Invalid rule: {0}")]
+    InvalidRule(String),
+
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+
+    #[error("Location tag found but reverse geocoding provider is not set")]
+    MissingReverseGeocoding,
+
+    #[error("Invalid file format: {0}. Use a .json or .csv file")]
+    InvalidPlanFormat(String),
+
+    #[error("The library to dedupe against is not a folder: {0}")]
+    InvalidLibrary(String),
+
+    #[error("Invalid keep policy: {0}. The preferred policy requires --prefer-regex")]
+    InvalidKeepPolicy(String),
+
+    #[error("Invalid duplicates action: {0}")]
+    InvalidDuplicatesAction(String),
+
+    #[error("Invalid summary mode: {0}")]
+    InvalidSummaryMode(String),
+
+    #[error("Invalid burst mode: {0}")]
+    InvalidBurstMode(String),
+
+    #[error("Invalid sanitize profile: {0}")]
+    InvalidSanitizeProfile(String),
+
+    #[error("Invalid unicode normalization: {0}")]
+    InvalidUnicodeNormalization(String),
+
+    #[error("Path too long for the sanitize profile: {0}")]
+    PathTooLong(String),
+
     #[error("Invalid placeholder mapping for {0} : It is likely to be a typo in implementation ")]
     InvalidPlaceholderMapping(String),
+
+    #[error("No video track found in {0}")]
+    MissingVideoTrack(String),
 }
 
 impl From<exif::Error> for ClineupError {
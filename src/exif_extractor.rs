@@ -1,8 +1,137 @@
 use exif::Exif;
 
 use crate::errors::ClineupError;
+use crate::timezone::get_gps_datetime;
+use crate::timezone::offset_from_utc_reference;
+use crate::timezone::parse_offset;
+use crate::timezone::CaptureDate;
+use chrono::{Duration, FixedOffset};
 use std::path::PathBuf;
 
+/// Tag of the Apple maker note holding the identifier shared by the frames of a burst
+const APPLE_BURST_UUID_TAG: u16 = 0x000b;
+
+/// Tag of the Fujifilm maker note numbering the frames of a continuous shooting, 0 for a single shot
+const FUJIFILM_SEQUENCE_NUMBER_TAG: u16 = 0x1101;
+
+/// Finds the value of an entry in an IFD of a maker note, whose offsets are relative to its start.
+///
+/// # Arguments
+///
+/// * `maker_note` - The raw maker note.
+/// * `ifd_offset` - Where the IFD starts in the maker note.
+/// * `tag` - The tag of the entry.
+/// * `big_endian` - Whether the numbers are written in big endian, like in the Apple maker note.
+///
+/// # Returns
+///
+/// The bytes of the value, or `None` if the entry is missing or truncated.
+fn find_maker_note_entry(
+    maker_note: &[u8],
+    ifd_offset: usize,
+    tag: u16,
+    big_endian: bool,
+) -> Option<&[u8]> {
+    let read_u16 = |offset: usize| {
+        let bytes = [*maker_note.get(offset)?, *maker_note.get(offset + 1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let read_u32 = |offset: usize| {
+        let bytes: [u8; 4] = maker_note.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        } as usize)
+    };
+
+    let number_of_entries = read_u16(ifd_offset)? as usize;
+    let entry = (0..number_of_entries)
+        .map(|index| ifd_offset + 2 + index * 12)
+        .find(|&entry| read_u16(entry) == Some(tag))?;
+    let type_size = match read_u16(entry + 2)? {
+        3 | 8 => 2,
+        4 | 9 | 11 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1,
+    };
+    let size = read_u32(entry + 4)?.checked_mul(type_size)?;
+    // A value of at most 4 bytes is written in the entry instead of its offset
+    let offset = if size <= 4 {
+        entry + 8
+    } else {
+        read_u32(entry + 8)?
+    };
+    maker_note.get(offset..offset.checked_add(size)?)
+}
+
+/// Reads a string from the Apple maker note of a photo.
+///
+/// # Arguments
+///
+/// * `maker_note` - The raw maker note, starting with `Apple iOS`.
+/// * `tag` - The tag of the string, like the one of the content identifier of a Live Photo.
+///
+/// # Returns
+///
+/// The string, or `None` if the maker note is not an Apple one or if the string is missing or empty.
+pub fn parse_apple_maker_note_string(maker_note: &[u8], tag: u16) -> Option<String> {
+    if !maker_note.starts_with(b"Apple iOS\0") || maker_note.get(12..14) != Some(b"MM") {
+        return None;
+    }
+    let value = find_maker_note_entry(maker_note, 14, tag, true)?;
+    let value = String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+/// Reads the number of a frame in a continuous shooting from the Fujifilm maker note of a photo.
+///
+/// # Arguments
+///
+/// * `maker_note` - The raw maker note, starting with `FUJIFILM` and the offset of its IFD.
+///
+/// # Returns
+///
+/// The number, 0 for a single shot, or `None` if the maker note is not a Fujifilm one or if the number is missing.
+pub fn parse_fujifilm_sequence_number(maker_note: &[u8]) -> Option<u32> {
+    if !maker_note.starts_with(b"FUJIFILM") {
+        return None;
+    }
+    let ifd_offset = u32::from_le_bytes(maker_note.get(8..12)?.try_into().ok()?) as usize;
+    let value = find_maker_note_entry(maker_note, ifd_offset, FUJIFILM_SEQUENCE_NUMBER_TAG, false)?;
+    Some(u16::from_le_bytes(value.get(..2)?.try_into().ok()?) as u32)
+}
+
+/// Formats a number with at most one decimal, like `2.8` or `50`
+fn format_decimal(value: f32) -> String {
+    format!("{}", (value * 10.0).round() / 10.0)
+}
+
+/// Formats a focal length in millimeters, like `50mm` or `4.2mm`
+pub fn format_focal_length(focal_length: f32) -> String {
+    format!("{}mm", format_decimal(focal_length))
+}
+
+/// Formats an F number, like `f2.8`
+pub fn format_aperture(aperture: f32) -> String {
+    format!("f{}", format_decimal(aperture))
+}
+
+/// Formats an exposure time as a path friendly fraction, like `1-250s` or `2s`
+pub fn format_shutter_speed(exposure_time: f32) -> String {
+    if exposure_time <= 0.0 || exposure_time >= 1.0 {
+        format!("{}s", format_decimal(exposure_time))
+    } else {
+        format!("1-{}s", (1.0 / exposure_time).round())
+    }
+}
+
 pub struct ExifExtractor {
     exif: Exif,
 }
@@ -21,6 +150,11 @@ impl ExifExtractor {
         Ok(ExifExtractor { exif })
     }
 
+    /// Number of EXIF fields, to tell which copy of a photo has the most metadata
+    pub fn get_number_of_fields(&self) -> usize {
+        self.exif.fields().count()
+    }
+
     pub fn get_float_value(&self, tag: exif::Tag) -> Result<f32, ClineupError> {
         if let Some(field) = self.exif.get_field(tag, exif::In::PRIMARY) {
             if let exif::Value::Rational(ref v) = field.value {
@@ -28,12 +162,25 @@ impl ExifExtractor {
                     return Ok(v.iter().map(|s| s.to_f32()).sum());
                 }
             }
+            // Dimensions are stored as integers
+            if let Some(v) = field.value.get_uint(0) {
+                return Ok(v as f32);
+            }
         }
         Err(ClineupError::ExifMissingTag {
             tag: tag.to_string(),
         })
     }
 
+    pub fn get_uint_value(&self, tag: exif::Tag) -> Result<u32, ClineupError> {
+        self.exif
+            .get_field(tag, exif::In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+            .ok_or(ClineupError::ExifMissingTag {
+                tag: tag.to_string(),
+            })
+    }
+
     pub fn get_gps_float_value(&self, tag: exif::Tag) -> Result<f32, ClineupError> {
         if let Some(field) = self.exif.get_field(tag, exif::In::PRIMARY) {
             if let exif::Value::Rational(ref v) = field.value {
@@ -69,6 +216,27 @@ impl ExifExtractor {
         })
     }
 
+    /// Finds a tag of the primary image by its name, like `LensModel`, or by its number,
+    /// like `0xa434` or `42036`. The name is compared ignoring the case.
+    pub fn find_tag(&self, name_or_number: &str) -> Result<exif::Tag, ClineupError> {
+        let number = match name_or_number.strip_prefix("0x") {
+            Some(hexadecimal) => u16::from_str_radix(hexadecimal, 16).ok(),
+            None => name_or_number.parse::<u16>().ok(),
+        };
+
+        self.exif
+            .fields()
+            .filter(|field| field.ifd_num == exif::In::PRIMARY)
+            .map(|field| field.tag)
+            .find(|tag| match number {
+                Some(number) => tag.number() == number,
+                None => tag.to_string().eq_ignore_ascii_case(name_or_number),
+            })
+            .ok_or(ClineupError::ExifMissingTag {
+                tag: name_or_number.to_string(),
+            })
+    }
+
     pub fn get_latitude(&self) -> Result<f32, ClineupError> {
         self.get_gps_float_value(exif::Tag::GPSLatitude)
     }
@@ -97,12 +265,134 @@ impl ExifExtractor {
         })
     }
 
+    /// Returns `DateTimeOriginal` with the fraction of second of the `SubSecTimeOriginal` tag,
+    /// which tells apart the frames of a burst taken within the same second.
+    pub fn get_exif_date_with_subseconds(&self) -> Result<chrono::NaiveDateTime, ClineupError> {
+        let date = self.get_exif_date()?;
+        let subseconds = match self.get_string_value(exif::Tag::SubSecTimeOriginal) {
+            Ok(digits) => digits.trim().to_string(),
+            Err(_) => return Ok(date),
+        };
+        // The digits are the decimals of the second, like `25` for 250 ms
+        let nanoseconds = format!("{:0<9}", subseconds)
+            .get(..9)
+            .and_then(|digits| digits.parse::<i64>().ok())
+            .unwrap_or(0);
+        Ok(date + Duration::nanoseconds(nanoseconds))
+    }
+
+    /// Returns whether the photo was taken by an automatic exposure bracketing
+    pub fn is_auto_bracketed(&self) -> Result<bool, ClineupError> {
+        self.get_uint_value(exif::Tag::ExposureMode)
+            .map(|exposure_mode| exposure_mode == 2)
+    }
+
+    /// Returns the number of the photo, which some cameras record to tell the frames of a sequence apart
+    pub fn get_image_number(&self) -> Result<u32, ClineupError> {
+        self.get_uint_value(exif::Tag(exif::Context::Exif, 0x9211))
+    }
+
+    /// Returns the raw maker note, whose format is specific to each camera brand
+    pub fn get_maker_note(&self) -> Result<&[u8], ClineupError> {
+        match self.exif.get_field(exif::Tag::MakerNote, exif::In::PRIMARY) {
+            Some(exif::Field {
+                value: exif::Value::Undefined(ref maker_note, _),
+                ..
+            }) => Ok(maker_note),
+            _ => Err(ClineupError::ExifMissingTag {
+                tag: exif::Tag::MakerNote.to_string(),
+            }),
+        }
+    }
+
+    /// Returns the identifier shared by the frames of an iPhone burst, from the Apple maker note
+    pub fn get_burst_uuid(&self) -> Result<String, ClineupError> {
+        self.get_maker_note()
+            .ok()
+            .and_then(|maker_note| parse_apple_maker_note_string(maker_note, APPLE_BURST_UUID_TAG))
+            .ok_or(ClineupError::ExifMissingTag {
+                tag: "BurstUUID".to_string(),
+            })
+    }
+
+    /// Returns the number of the frame in a continuous shooting, from the maker note, 0 for a single shot
+    pub fn get_sequence_number(&self) -> Result<u32, ClineupError> {
+        self.get_maker_note()
+            .ok()
+            .and_then(parse_fujifilm_sequence_number)
+            .ok_or(ClineupError::ExifMissingTag {
+                tag: "SequenceNumber".to_string(),
+            })
+    }
+
+    /// Returns the offset of `DateTimeOriginal` from the `OffsetTimeOriginal` tag,
+    /// or from the `OffsetTime` tag if the former is missing.
+    pub fn get_exif_offset(&self) -> Result<FixedOffset, ClineupError> {
+        self.get_string_value(exif::Tag::OffsetTimeOriginal)
+            .or_else(|_| self.get_string_value(exif::Tag::OffsetTime))
+            .and_then(|offset| parse_offset(&offset))
+    }
+
+    /// Returns the UTC date time recorded by the GPS receiver.
+    pub fn get_gps_datetime(&self) -> Result<chrono::NaiveDateTime, ClineupError> {
+        let date = self.get_string_value(exif::Tag::GPSDateStamp)?;
+        let time = self
+            .exif
+            .get_field(exif::Tag::GPSTimeStamp, exif::In::PRIMARY)
+            .and_then(|field| match field.value {
+                exif::Value::Rational(ref v) if v.len() >= 3 => {
+                    Some(v.iter().map(|r| r.to_f64()).collect::<Vec<f64>>())
+                }
+                _ => None,
+            })
+            .ok_or(ClineupError::ExifMissingTag {
+                tag: exif::Tag::GPSTimeStamp.to_string(),
+            })?;
+
+        get_gps_datetime(&date, &time)
+    }
+
+    /// Returns the capture date along with its offset from UTC.
+    /// The offset is read from the EXIF offset tags, or deduced from the GPS timestamp.
+    /// If none of them is present, the offset is unknown.
+    pub fn get_capture_date(&self) -> Result<CaptureDate, ClineupError> {
+        self.get_corrected_capture_date(Duration::zero())
+    }
+
+    /// Same as `get_capture_date` but the camera clock is shifted by `clock_delta`
+    /// before deducing the offset from the GPS timestamp.
+    pub fn get_corrected_capture_date(
+        &self,
+        clock_delta: Duration,
+    ) -> Result<CaptureDate, ClineupError> {
+        let date = self.get_exif_date()? + clock_delta;
+        let offset = self.get_exif_offset().ok().or_else(|| {
+            self.get_gps_datetime()
+                .ok()
+                .and_then(|utc| offset_from_utc_reference(&date, &utc))
+        });
+        Ok(CaptureDate::new(date, offset))
+    }
+
     pub fn get_width(&self) -> Result<f32, ClineupError> {
         self.get_float_value(exif::Tag::ImageWidth)
+            .or_else(|_| self.get_float_value(exif::Tag::PixelXDimension))
     }
 
     pub fn get_height(&self) -> Result<f32, ClineupError> {
         self.get_float_value(exif::Tag::ImageLength)
+            .or_else(|_| self.get_float_value(exif::Tag::PixelYDimension))
+    }
+
+    /// Returns the width and the height of the media as displayed,
+    /// which means they are swapped if the EXIF orientation rotates the media by 90 degrees.
+    pub fn get_displayed_dimensions(&self) -> Result<(f32, f32), ClineupError> {
+        let width = self.get_width()?;
+        let height = self.get_height()?;
+        match self.get_orientation() {
+            Ok(5..=8) => Ok((height, width)),
+            _ => Ok((width, height)),
+        }
     }
 
     pub fn get_camera_model(&self) -> Result<String, ClineupError> {
@@ -112,4 +402,66 @@ impl ExifExtractor {
     pub fn get_camera_brand(&self) -> Result<String, ClineupError> {
         self.get_string_value(exif::Tag::Make)
     }
+
+    pub fn get_serial_number(&self) -> Result<String, ClineupError> {
+        self.get_string_value(exif::Tag::BodySerialNumber)
+    }
+
+    pub fn get_lens_model(&self) -> Result<String, ClineupError> {
+        self.get_string_value(exif::Tag::LensModel)
+    }
+
+    /// Returns the focal length in millimeters
+    pub fn get_focal_length(&self) -> Result<f32, ClineupError> {
+        self.get_float_value(exif::Tag::FocalLength)
+    }
+
+    /// Returns the focal length in millimeters, converted to a 35mm film camera
+    pub fn get_focal_length_35mm(&self) -> Result<u32, ClineupError> {
+        self.get_uint_value(exif::Tag::FocalLengthIn35mmFilm)
+            .and_then(|focal_length| match focal_length {
+                // 0 means that the focal length is unknown
+                0 => Err(ClineupError::ExifMissingTag {
+                    tag: exif::Tag::FocalLengthIn35mmFilm.to_string(),
+                }),
+                _ => Ok(focal_length),
+            })
+    }
+
+    /// Returns the F number
+    pub fn get_aperture(&self) -> Result<f32, ClineupError> {
+        self.get_float_value(exif::Tag::FNumber)
+    }
+
+    /// Returns the exposure time in seconds
+    pub fn get_exposure_time(&self) -> Result<f32, ClineupError> {
+        self.get_float_value(exif::Tag::ExposureTime)
+    }
+
+    pub fn get_iso(&self) -> Result<u32, ClineupError> {
+        self.get_uint_value(exif::Tag::PhotographicSensitivity)
+    }
+
+    /// Returns whether the flash fired, which is stored in the first bit of the `Flash` tag
+    pub fn get_flash_fired(&self) -> Result<bool, ClineupError> {
+        self.get_uint_value(exif::Tag::Flash)
+            .map(|flash| flash & 1 == 1)
+    }
+
+    /// Returns the EXIF orientation, from 1 to 8
+    pub fn get_orientation(&self) -> Result<u32, ClineupError> {
+        self.get_uint_value(exif::Tag::Orientation)
+    }
+
+    pub fn get_software(&self) -> Result<String, ClineupError> {
+        self.get_string_value(exif::Tag::Software)
+    }
+
+    pub fn get_artist(&self) -> Result<String, ClineupError> {
+        self.get_string_value(exif::Tag::Artist)
+    }
+
+    pub fn get_copyright(&self) -> Result<String, ClineupError> {
+        self.get_string_value(exif::Tag::Copyright)
+    }
 }
@@ -1,7 +1,6 @@
 use super::location::LocationInfo;
 use crate::errors::ClineupError;
 use crate::gps::base::GpsResolutionProvider;
-use log::debug;
 use reqwest;
 use serde_json;
 use std::cell::Cell;
@@ -246,7 +245,7 @@ mod tests {
             ) -> Result<reqwest::blocking::Response, ClineupError> {
                 // Create a JSON response body
                 let error_message = "An error occurred";
-                let io_error = std::io::Error::new(std::io::ErrorKind::Other, error_message);
+                let io_error = std::io::Error::other(error_message);
                 Err(ClineupError::IoError(io_error))
             }
         }
@@ -1,10 +1,21 @@
 #![allow(clippy::match_like_matches_macro, clippy::redundant_closure_call)]
 
+pub mod bursts;
 pub mod cli;
+pub mod clock_correction;
+pub mod config_file;
+pub mod duplicates_command;
 pub mod errors;
 pub mod exif_extractor;
 pub mod gps;
+pub mod media_type;
 pub mod organizer;
 pub mod path;
 pub mod placeholders;
+pub mod plan;
+pub mod quicktime;
+pub mod rules;
+pub mod summary;
+pub mod template_command;
+pub mod timezone;
 pub mod utils;
@@ -1,21 +1,51 @@
+use chrono::Duration;
+use clineup::bursts::BurstMode;
+use clineup::bursts::Bursts;
 use clineup::cli::check_cli_config_from_placeholders;
 use clineup::cli::get_cli_config;
 use clineup::cli::init_logger;
 use clineup::cli::parse_cli;
+use clineup::duplicates_command::run_duplicates_command;
+use clineup::path::companions::get_companion_destination;
+use clineup::path::companions::get_companion_finder;
 use clineup::path::duplicates_finder::DuplicatesFinder;
+use clineup::path::formatter::Counters;
 use clineup::path::formatter::PathFormatter;
+use clineup::path::formatter::ReverseGeocoder;
+use clineup::path::hash_index::run_index_command;
+use clineup::path::hash_index::HashIndex;
+use clineup::path::iterator::is_excluded;
+use clineup::path::iterator::select_files;
 use clineup::path::iterator::FileIterator;
+use clineup::path::parser::check_placeholders;
 use clineup::path::parser::map_placeholders_to_enums;
 use clineup::path::parser::parse_placeholders;
+use clineup::path::parser::parse_template;
+use clineup::plan::get_fallbacks;
+use clineup::plan::run_apply_command;
+use clineup::plan::Plan;
+use clineup::rules::find_rule;
+use clineup::summary::get_fallback_names;
+use clineup::summary::Summary;
+use clineup::summary::SummaryMode;
+use clineup::template_command::run_template_command;
 use clineup::utils::get_full_format_path;
 use clineup::utils::get_organization_strategy;
 use clineup::utils::get_reverse_geocoding;
+use clineup::utils::is_there_a_burst_placeholder;
+use clineup::utils::print_error;
 use indicatif::ProgressBar;
 use log::debug;
 use log::error;
 use log::info;
+use log::warn;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::path::Path;
+use std::path::PathBuf;
 use std::process::exit;
+use std::rc::Rc;
 
 fn main() {
     let matches = parse_cli();
@@ -26,6 +56,19 @@ fn main() {
     } else {
         init_logger(verbosity);
     }
+
+    if let Some(template_matches) = matches.subcommand_matches("template") {
+        run_template_command(template_matches);
+    }
+    if let Some(apply_matches) = matches.subcommand_matches("apply") {
+        run_apply_command(apply_matches);
+    }
+    if let Some(duplicates_matches) = matches.subcommand_matches("duplicates") {
+        run_duplicates_command(duplicates_matches);
+    }
+    if let Some(index_matches) = matches.subcommand_matches("index") {
+        run_index_command(index_matches);
+    }
     debug!("Get configuration");
     let config = get_cli_config(matches);
 
@@ -38,45 +81,176 @@ fn main() {
         debug!("Get strategy {:?}", config.strategy);
     }
 
-    debug!("Get reverse geocoding strategy");
-    let reverse_geocoding = get_reverse_geocoding(&config);
-
-    let full_path = get_full_format_path(
+    // Each rule has its own formats, the command line formats being the default rule
+    let mut full_paths: Vec<String> = config
+        .rules
+        .iter()
+        .filter_map(|rule| {
+            get_full_format_path(rule.folder_format.as_ref(), rule.filename_format.as_ref())
+        })
+        .collect();
+    let default_full_path = get_full_format_path(
         config.folder_format.as_ref(),
         config.filename_format.as_ref(),
     );
-
-    if full_path.is_none() {
+    if default_full_path.is_none() && full_paths.is_empty() {
         println!("You should provide at least one of the folder or filename format.");
         exit(1);
-    } else {
-        debug!("Full path {:?}", full_path.as_ref().unwrap());
     }
+    full_paths.extend(default_full_path.clone());
+    debug!("Full paths {:?}", full_paths);
 
     let destination = Path::new(&config.destination);
 
+    debug!("Parsing templates");
+    for full_path in &full_paths {
+        let template = parse_template(full_path).unwrap_or_else(print_error);
+        check_placeholders(full_path, &template).unwrap_or_else(print_error);
+    }
+
     debug!("Parsing placeholders");
-    let _placeholders = parse_placeholders(full_path.as_ref().unwrap());
-    let placeholders = map_placeholders_to_enums(&_placeholders);
+    let placeholders: Vec<_> = full_paths
+        .iter()
+        .map(|full_path| {
+            map_placeholders_to_enums(&parse_placeholders(full_path).unwrap_or_else(print_error))
+        })
+        .collect();
     debug!("Placeholders found {:?}", placeholders);
 
-    check_cli_config_from_placeholders(&config, &placeholders);
+    for _placeholders in &placeholders {
+        check_cli_config_from_placeholders(&config, _placeholders);
+    }
+
+    // The strategy is always set, the default one being copy
+    let modes: Vec<_> = config
+        .rules
+        .iter()
+        .map(|rule| rule.strategy.as_ref().or(config.strategy.as_ref()))
+        .chain(default_full_path.iter().map(|_| config.strategy.as_ref()))
+        .map(|strategy| strategy.unwrap().clone())
+        .collect();
+    let strategies: Vec<_> = modes
+        .iter()
+        .map(|mode| get_organization_strategy(Some(mode)).unwrap())
+        .collect();
 
-    // It is mutable to be able to store the positions and location when optmizing gps positions
-    let mut path_formatter = PathFormatter::new(
-        full_path.as_ref().unwrap(),
-        &placeholders,
-        reverse_geocoding,
-        config.gps_optimization,
+    let files = select_files(
+        FileIterator::new(&config),
+        config.offset,
+        config.sample,
+        config.seed,
+        config.limit,
     );
-    let files = FileIterator::new(&config);
+
+    // Every run writing files records them in the index of the destination
+    let writes_files = !config.dry_run && config.plan_output.is_none();
+    let open_hash_index = |library: &Path| {
+        let hash_index = HashIndex::open(library)
+            .unwrap_or_else(print_error)
+            .with_hash_algorithm(config.duplicates_hash_algorithm);
+        Rc::new(RefCell::new(hash_index))
+    };
+    let destination_index = writes_files.then(|| open_hash_index(destination));
 
     let mut duplicates_finder = if config.drop_duplicates {
-        Some(DuplicatesFinder::new())
+        let mut duplicates_finder =
+            DuplicatesFinder::new().with_hash_algorithm(config.duplicates_hash_algorithm);
+        if let Some(library) = &config.dedupe_against {
+            // Each library keeps its own index, the destination one being shared
+            if let Some(destination_index) = &destination_index {
+                let library = Path::new(library);
+                let hash_index = if library.canonicalize().ok() == destination.canonicalize().ok() {
+                    destination_index.clone()
+                } else {
+                    open_hash_index(library)
+                };
+                duplicates_finder = duplicates_finder.with_hash_index(hash_index);
+            }
+            debug!("Indexing the library {:?}", library);
+            duplicates_finder
+                .index_library(Path::new(library))
+                .unwrap_or_else(print_error);
+        }
+        Some(duplicates_finder)
     } else {
         None
     };
 
+    // Whether each file is a duplicate, when they are checked before detecting the bursts
+    let mut known_duplicates: Option<HashMap<PathBuf, bool>> = None;
+    // The bursts are detected among every selected file before organizing the first one
+    let (files, bursts) = if config.burst_mode != BurstMode::KeepAll
+        || placeholders.iter().any(is_there_a_burst_placeholder)
+    {
+        let files: Vec<PathBuf> = files.collect();
+        // The duplicates are found first, so that the kept frame of a burst is never one of them
+        if let (Some(duplicates_finder), BurstMode::First | BurstMode::Sharpest) =
+            (duplicates_finder.as_mut(), config.burst_mode)
+        {
+            let mut checked_files = HashMap::new();
+            for file in &files {
+                match duplicates_finder.is_duplicate(file) {
+                    Ok(is_duplicate) => {
+                        checked_files.insert(file.clone(), is_duplicate);
+                    }
+                    Err(err) => error!("{}", err),
+                }
+            }
+            known_duplicates = Some(checked_files);
+        }
+        let duplicates: HashSet<PathBuf> = known_duplicates
+            .iter()
+            .flatten()
+            .filter(|(_, is_duplicate)| **is_duplicate)
+            .map(|(file, _)| file.clone())
+            .collect();
+
+        debug!("Detecting bursts among {} file(s)", files.len());
+        let bursts = Bursts::detect(
+            &files,
+            Duration::milliseconds(config.burst_interval as i64),
+            config.burst_mode,
+            &duplicates,
+        )
+        .with_sanitizer(config.sanitizer.clone());
+        (
+            Box::new(files.into_iter()) as Box<dyn Iterator<Item = PathBuf>>,
+            bursts,
+        )
+    } else {
+        (files, Bursts::default())
+    };
+
+    // The rules writing to the same folder share its counter
+    let counters = Counters::new(destination);
+
+    debug!("Get reverse geocoding strategy");
+    // The rules share the provider, which waits between two requests, and the resolved locations
+    let reverse_geocoder = get_reverse_geocoding(&config).map(ReverseGeocoder::new);
+    // They are mutable to be able to store the positions and location when optmizing gps positions
+    let mut path_formatters: Vec<PathFormatter> = full_paths
+        .iter()
+        .zip(&placeholders)
+        .map(|(full_path, _placeholders)| {
+            PathFormatter::new(
+                full_path,
+                _placeholders,
+                reverse_geocoder.clone(),
+                config.gps_optimization,
+            )
+            .unwrap_or_else(print_error)
+            .with_timezone(config.timezone.clone())
+            .with_clock_corrections(config.clock_corrections.clone())
+            .with_hash(config.hash_algorithm, config.hash_length)
+            .with_counter_width(config.counter_width)
+            .with_counters(counters.clone())
+            .with_bursts(bursts.clone())
+            .with_sanitizer(config.sanitizer.clone())
+        })
+        .collect();
+
+    let mut companion_finder = get_companion_finder(&config);
+
     if config.dry_run {
         info!("Configuration \n{:?}", config)
     }
@@ -86,55 +260,161 @@ fn main() {
     let bar = ProgressBar::new_spinner();
     bar.set_message("Start organizing files...");
 
+    let mut plan = config.plan_output.as_ref().map(|_| Plan::new());
+    let mut summary = Summary::new();
+
     for entry in files {
-        if config.drop_duplicates {
-            let is_duplicate = duplicates_finder.as_mut().unwrap().is_duplicate(&entry);
-
-            match is_duplicate {
-                Ok(is_duplicate) => {
-                    if is_duplicate {
-                        info!("Find duplicate {:?}", entry.display());
-                        continue;
-                    }
-                }
+        let is_duplicate = match (known_duplicates.as_ref(), duplicates_finder.as_mut()) {
+            // The files which could not be checked are skipped
+            (Some(known_duplicates), _) => match known_duplicates.get(&entry) {
+                Some(is_duplicate) => *is_duplicate,
+                None => continue,
+            },
+            (None, Some(duplicates_finder)) => match duplicates_finder.is_duplicate(&entry) {
+                Ok(is_duplicate) => is_duplicate,
                 Err(err) => {
                     error!("{}", err);
                     continue;
                 }
-            }
+            },
+            (None, None) => false,
+        };
+        // The duplicates are kept in the plan, to be reviewed
+        if is_duplicate && plan.is_none() {
+            info!("Find duplicate {:?}", entry.display());
+            summary.add_duplicate();
+            continue;
+        }
+
+        if bursts.is_dropped(&entry) {
+            info!("Drop burst frame {:?}", entry.display());
+            summary.add_dropped_burst_frame();
+            continue;
         }
 
-        let formatted_path = path_formatter.get_formatted_path(&entry);
+        // The default rule comes right after the rules of the configuration file
+        let route = match find_rule(&config.rules, &entry) {
+            Some(index) => index,
+            None if default_full_path.is_some() => config.rules.len(),
+            None => {
+                warn!("No rule matches {:?}", entry.display());
+                continue;
+            }
+        };
+
+        let formatted_path = path_formatters[route].get_formatted_path_with_values(&entry);
 
-        let good_formatted_path = match formatted_path {
-            Ok(path) => destination.join(path),
+        let (good_formatted_path, values) = match formatted_path {
+            Ok((path, values)) => (
+                bursts.get_destination(&entry, destination.join(path)),
+                values,
+            ),
             Err(err) => {
                 error!("{}", err);
                 continue;
             }
         };
 
+        if let Err(err) = config.sanitizer.check_path_length(&good_formatted_path) {
+            error!("{}", err);
+            continue;
+        }
+
         debug!("Get formatted path {:?}", good_formatted_path);
 
-        if config.dry_run {
-            if file_processed_count >= config.dry_run_number_of_files {
-                exit(0);
+        // The companions, like the video of a Live Photo, follow their primary file with its resolved name
+        let companions: Vec<(PathBuf, PathBuf)> = companion_finder
+            .as_mut()
+            .map_or_else(Vec::new, |companion_finder| {
+                companion_finder.get_companions(&entry)
+            })
+            .into_iter()
+            .filter(|companion| !is_excluded(companion, &config))
+            .map(|companion| {
+                let companion_destination =
+                    get_companion_destination(&companion, &entry, &good_formatted_path);
+                (companion, companion_destination)
+            })
+            .collect();
+        let mut organized_files = vec![(entry.clone(), good_formatted_path)];
+        organized_files.extend(companions);
+
+        if let Some(plan) = plan.as_mut() {
+            for (source, destination) in &organized_files {
+                plan.add(
+                    source,
+                    destination,
+                    modes[route].clone(),
+                    is_duplicate,
+                    get_fallbacks(&values),
+                );
             }
-            info!("{:?} -> {}", entry, good_formatted_path.display());
-            file_processed_count += 1;
             continue;
         }
 
-        strategy
-            .as_ref()
-            .unwrap()
-            .organize(&entry, &good_formatted_path);
+        if config.dry_run {
+            for (source, destination) in &organized_files {
+                // Every file is part of the summary, only the first ones are printed
+                if config.summary_mode == SummaryMode::Stats
+                    && config
+                        .dry_run_number_of_files
+                        .is_none_or(|number_of_files| file_processed_count < number_of_files)
+                {
+                    info!("{:?} -> {}", source, destination.display());
+                }
+                summary.add_file(
+                    source,
+                    destination,
+                    &get_fallbacks(&values),
+                    get_fallback_names(&values, &placeholders[route]),
+                );
+                file_processed_count += 1;
+            }
+            continue;
+        }
 
-        file_processed_count += 1;
+        for (source, destination) in &organized_files {
+            // An existing destination is never overwritten, so it is not recorded
+            let is_new_destination = !destination.exists();
+            strategies[route].organize(source, destination);
+            if let Some(destination_index) =
+                destination_index.as_ref().filter(|_| is_new_destination)
+            {
+                // The hash of the source is reused when it was just fully hashed
+                let known_hash = duplicates_finder
+                    .as_mut()
+                    .and_then(|duplicates_finder| duplicates_finder.take_hash_of(source));
+                if let Err(err) = destination_index
+                    .borrow_mut()
+                    .record(destination, known_hash)
+                {
+                    warn!("Can not index {:?}: {}", destination, err);
+                }
+            }
+            file_processed_count += 1;
+        }
 
         bar.set_message(format!("{:?} file(s) processed", file_processed_count));
         bar.tick();
     }
+    if let (Some(plan), Some(plan_output)) = (&plan, &config.plan_output) {
+        plan.write(Path::new(plan_output))
+            .unwrap_or_else(print_error);
+        println!(
+            "Plan of {} file(s) written to {}",
+            plan.entries().len(),
+            plan_output
+        );
+        return;
+    }
+
+    if config.dry_run {
+        match config.summary_mode {
+            SummaryMode::Stats => print!("{}", summary.report()),
+            SummaryMode::Tree => print!("{}", summary.tree_report(destination)),
+        }
+    }
+
     bar.finish_with_message(format!(
         "Done. {:?} file(s) processed.",
         file_processed_count
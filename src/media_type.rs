@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+const RAW_EXTENSIONS: [&str; 22] = [
+    "3fr", "arw", "cr2", "cr3", "crw", "dng", "erf", "iiq", "kdc", "mef", "mos", "mrw", "nef",
+    "nrw", "orf", "pef", "raf", "raw", "rw2", "rwl", "sr2", "srw",
+];
+const PHOTO_EXTENSIONS: [&str; 14] = [
+    "avif", "bmp", "gif", "heic", "heif", "jfif", "jpeg", "jpg", "jxl", "png", "svg", "tif",
+    "tiff", "webp",
+];
+const VIDEO_EXTENSIONS: [&str; 15] = [
+    "3gp", "avi", "flv", "insv", "m2ts", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "mts", "ogv",
+    "webm", "wmv",
+];
+const AUDIO_EXTENSIONS: [&str; 10] = [
+    "aac", "aiff", "amr", "flac", "m4a", "mp3", "ogg", "opus", "wav", "wma",
+];
+
+/// Broad class of a media
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    Photo,
+    Video,
+    Audio,
+    Raw,
+    Other,
+}
+
+impl MediaType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Photo => "photo",
+            MediaType::Video => "video",
+            MediaType::Audio => "audio",
+            MediaType::Raw => "raw",
+            MediaType::Other => "other",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<MediaType> {
+        let extension = extension.to_ascii_lowercase();
+        let extension = extension.as_str();
+        if RAW_EXTENSIONS.contains(&extension) {
+            Some(MediaType::Raw)
+        } else if PHOTO_EXTENSIONS.contains(&extension) {
+            Some(MediaType::Photo)
+        } else if VIDEO_EXTENSIONS.contains(&extension) {
+            Some(MediaType::Video)
+        } else if AUDIO_EXTENSIONS.contains(&extension) {
+            Some(MediaType::Audio)
+        } else {
+            None
+        }
+    }
+
+    /// Guesses the media type from the first bytes of a file.
+    pub fn from_magic_bytes(header: &[u8]) -> MediaType {
+        let starts_with = |magic: &[u8]| header.starts_with(magic);
+        let at = |offset: usize, magic: &[u8]| {
+            header.len() >= offset + magic.len() && &header[offset..offset + magic.len()] == magic
+        };
+
+        if at(4, b"ftyp") {
+            // ISO base media files share the same container for photos, videos and audio
+            return match header.get(8..12) {
+                Some(b"heic") | Some(b"heix") | Some(b"mif1") | Some(b"msf1") | Some(b"avif") => {
+                    MediaType::Photo
+                }
+                Some(b"crx ") => MediaType::Raw,
+                Some(b"M4A ") => MediaType::Audio,
+                _ => MediaType::Video,
+            };
+        }
+        if starts_with(b"RIFF") {
+            return match header.get(8..12) {
+                Some(b"WEBP") => MediaType::Photo,
+                Some(b"AVI ") => MediaType::Video,
+                Some(b"WAVE") => MediaType::Audio,
+                _ => MediaType::Other,
+            };
+        }
+        if starts_with(b"II*\0") || starts_with(b"MM\0*") {
+            // Canon CR2 files are TIFF files with a "CR" marker
+            return if at(8, b"CR") {
+                MediaType::Raw
+            } else {
+                MediaType::Photo
+            };
+        }
+        if starts_with(b"FUJIFILMCCD-RAW") || starts_with(b"IIRO") || starts_with(b"IIU\0") {
+            return MediaType::Raw;
+        }
+        if starts_with(&[0xFF, 0xD8, 0xFF])
+            || starts_with(b"\x89PNG")
+            || starts_with(b"GIF8")
+            || starts_with(b"BM")
+        {
+            return MediaType::Photo;
+        }
+        if starts_with(&[0x1A, 0x45, 0xDF, 0xA3])
+            || starts_with(&[0x00, 0x00, 0x01, 0xBA])
+            || starts_with(&[0x30, 0x26, 0xB2, 0x75])
+        {
+            return MediaType::Video;
+        }
+        if starts_with(b"ID3")
+            || starts_with(&[0xFF, 0xFB])
+            || starts_with(b"fLaC")
+            || starts_with(b"OggS")
+        {
+            return MediaType::Audio;
+        }
+        MediaType::Other
+    }
+}
+
+/// Gets the media type of a file from its extension, or from its first bytes
+/// if the extension is missing or unknown.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+///
+/// # Returns
+///
+/// The `MediaType` of the file, `MediaType::Other` if it can't be guessed.
+pub fn get_media_type(path: &Path) -> MediaType {
+    if let Some(media_type) = path
+        .extension()
+        .and_then(|extension| MediaType::from_extension(&extension.to_string_lossy()))
+    {
+        return media_type;
+    }
+
+    let mut header = [0u8; 16];
+    let read = File::open(path).and_then(|mut file| file.read(&mut header));
+    match read {
+        Ok(bytes_read) => MediaType::from_magic_bytes(&header[..bytes_read]),
+        Err(_) => MediaType::Other,
+    }
+}
+
+/// Orientation of a media computed from its dimensions
+pub fn get_orientation_class(width: f32, height: f32) -> &'static str {
+    if width > height {
+        "landscape"
+    } else if width < height {
+        "portrait"
+    } else {
+        "square"
+    }
+}
+
+/// Resolution class named after the usual video formats, computed from the shortest side
+pub fn get_resolution_class(width: f32, height: f32) -> &'static str {
+    match width.min(height) {
+        side if side >= 4320.0 => "8K",
+        side if side >= 2160.0 => "4K",
+        side if side >= 1440.0 => "1440p",
+        side if side >= 1080.0 => "1080p",
+        side if side >= 720.0 => "720p",
+        side if side >= 480.0 => "480p",
+        _ => "SD",
+    }
+}
+
+/// Number of megapixels rounded to one decimal
+pub fn get_megapixels(width: f32, height: f32) -> f32 {
+    (width * height / 100_000.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_extension() {
+        assert_eq!(MediaType::from_extension("JPG"), Some(MediaType::Photo));
+        assert_eq!(MediaType::from_extension("cr2"), Some(MediaType::Raw));
+        assert_eq!(MediaType::from_extension("mov"), Some(MediaType::Video));
+        assert_eq!(MediaType::from_extension("flac"), Some(MediaType::Audio));
+        assert_eq!(MediaType::from_extension("txt"), None);
+    }
+
+    #[test]
+    fn test_from_magic_bytes() {
+        assert_eq!(
+            MediaType::from_magic_bytes(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            MediaType::Photo
+        );
+        assert_eq!(
+            MediaType::from_magic_bytes(b"\0\0\0\x18ftypqt  "),
+            MediaType::Video
+        );
+        assert_eq!(
+            MediaType::from_magic_bytes(b"\0\0\0\x18ftypheic"),
+            MediaType::Photo
+        );
+        assert_eq!(
+            MediaType::from_magic_bytes(b"II*\0\x10\0\0\0CR\x02\0"),
+            MediaType::Raw
+        );
+        assert_eq!(MediaType::from_magic_bytes(b"ID3\x04"), MediaType::Audio);
+        assert_eq!(MediaType::from_magic_bytes(b""), MediaType::Other);
+    }
+
+    #[test]
+    fn test_classes() {
+        assert_eq!(get_orientation_class(4000.0, 3000.0), "landscape");
+        assert_eq!(get_orientation_class(3000.0, 4000.0), "portrait");
+        assert_eq!(get_orientation_class(100.0, 100.0), "square");
+        assert_eq!(get_resolution_class(3840.0, 2160.0), "4K");
+        assert_eq!(get_resolution_class(1080.0, 1920.0), "1080p");
+        assert_eq!(get_resolution_class(640.0, 360.0), "SD");
+        assert_eq!(get_megapixels(4000.0, 3000.0), 12.0);
+        assert_eq!(get_megapixels(4032.0, 3024.0), 12.2);
+    }
+}
@@ -1,6 +1,8 @@
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 
+use crate::errors::ClineupError;
+
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::symlink;
 #[cfg(target_family = "windows")]
@@ -8,15 +10,42 @@ use std::os::windows::fs::symlink_file;
 
 use std::path::Path;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub enum OrganizationMode {
     Symlinks,
     Move,
     Copy,
 }
 
+impl OrganizationMode {
+    pub fn from_string(mode: &str) -> Result<OrganizationMode, ClineupError> {
+        match mode {
+            "copy" => Ok(OrganizationMode::Copy),
+            "symlink" => Ok(OrganizationMode::Symlinks),
+            "move" => Ok(OrganizationMode::Move),
+            _ => Err(ClineupError::InvalidOrganization(mode.to_string())),
+        }
+    }
+
+    /// Name of the strategy as given on the command line
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrganizationMode::Copy => "copy",
+            OrganizationMode::Symlinks => "symlink",
+            OrganizationMode::Move => "move",
+        }
+    }
+}
+
 pub trait OrganizationStrategy {
-    fn organize(&self, _original_file: &Path, _destination: &Path) {}
+    /// Organizes a file, an existing destination being never overwritten.
+    ///
+    /// # Returns
+    ///
+    /// Whether the file was organized, the errors being logged.
+    fn organize(&self, _original_file: &Path, _destination: &Path) -> bool {
+        false
+    }
 }
 
 pub struct CopyStrategy {}
@@ -31,7 +60,7 @@ impl Default for CopyStrategy {
     }
 }
 impl OrganizationStrategy for CopyStrategy {
-    fn organize(&self, original_file: &Path, destination: &Path) {
+    fn organize(&self, original_file: &Path, destination: &Path) -> bool {
         info!(
             "Copying {} to {}",
             original_file.display(),
@@ -44,14 +73,20 @@ impl OrganizationStrategy for CopyStrategy {
 
         if destination.exists() {
             error!("Destination file already exists. Aborting copy.");
-            return;
+            return false;
         }
 
         let copy_result = std::fs::copy(original_file, destination);
 
         match copy_result {
-            Ok(_) => info!("File copied successfully"),
-            Err(e) => error!("Error copying file: {}", e),
+            Ok(_) => {
+                info!("File copied successfully");
+                true
+            }
+            Err(e) => {
+                error!("Error copying file: {}", e);
+                false
+            }
         }
     }
 }
@@ -67,23 +102,35 @@ impl Default for SymlinksStrategy {
     }
 }
 #[cfg(target_family = "unix")]
-fn make_symlink(original_file: &Path, destination: &Path) {
+fn make_symlink(original_file: &Path, destination: &Path) -> bool {
     let symlink_result = symlink(original_file, destination);
     match symlink_result {
-        Ok(_) => info!("File symlinked successfully"),
-        Err(e) => error!("Error symlinking file: {}", e),
+        Ok(_) => {
+            info!("File symlinked successfully");
+            true
+        }
+        Err(e) => {
+            error!("Error symlinking file: {}", e);
+            false
+        }
     }
 }
 #[cfg(target_family = "windows")]
-fn make_symlink(original_file: &Path, destination: &Path) {
+fn make_symlink(original_file: &Path, destination: &Path) -> bool {
     let symlink_result = symlink_file(original_file, destination);
     match symlink_result {
-        Ok(_) => info!("File symlinked successfully"),
-        Err(e) => error!("Error symlinking file: {}", e),
+        Ok(_) => {
+            info!("File symlinked successfully");
+            true
+        }
+        Err(e) => {
+            error!("Error symlinking file: {}", e);
+            false
+        }
     }
 }
 impl OrganizationStrategy for SymlinksStrategy {
-    fn organize(&self, original_file: &Path, destination: &Path) {
+    fn organize(&self, original_file: &Path, destination: &Path) -> bool {
         info!(
             "Symlinking {} to {}",
             destination.display(),
@@ -95,10 +142,10 @@ impl OrganizationStrategy for SymlinksStrategy {
 
         if destination.exists() {
             error!("Destination file already exists. Aborting copy.");
-            return;
+            return false;
         }
 
-        make_symlink(original_file, destination);
+        make_symlink(original_file, destination)
     }
 }
 pub struct MoveStrategy {}
@@ -114,7 +161,7 @@ impl Default for MoveStrategy {
     }
 }
 impl OrganizationStrategy for MoveStrategy {
-    fn organize(&self, original_file: &Path, destination: &Path) {
+    fn organize(&self, original_file: &Path, destination: &Path) -> bool {
         info!(
             "Moving {} to {}",
             original_file.display(),
@@ -126,12 +173,18 @@ impl OrganizationStrategy for MoveStrategy {
 
         if destination.exists() {
             error!("Destination file already exists. Aborting copy.");
-            return;
+            return false;
         }
         let move_result = std::fs::rename(original_file, destination);
         match move_result {
-            Ok(_) => info!("File moved successfully"),
-            Err(e) => error!("Error moving file: {}", e),
+            Ok(_) => {
+                info!("File moved successfully");
+                true
+            }
+            Err(e) => {
+                error!("Error moving file: {}", e);
+                false
+            }
         }
     }
 }
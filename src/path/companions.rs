@@ -0,0 +1,582 @@
+use log::debug;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cli::Config;
+use crate::errors::ClineupError;
+use crate::exif_extractor::parse_apple_maker_note_string;
+use crate::exif_extractor::ExifExtractor;
+use crate::media_type::MediaType;
+use crate::quicktime::find_atom;
+use crate::quicktime::read_moov;
+
+/// Key of the QuickTime metadata linking the video of a Live Photo to its photo
+const QUICKTIME_CONTENT_IDENTIFIER: &[u8] = b"com.apple.quicktime.content.identifier";
+
+/// Extensions of the sidecars which follow the file of the same stem by default
+pub const DEFAULT_COMPANION_EXTENSIONS: [&str; 5] = ["aae", "lrv", "srt", "thm", "xmp"];
+
+/// Tag of the Apple maker note holding the same identifier in the photo
+const APPLE_CONTENT_IDENTIFIER_TAG: u16 = 0x0011;
+
+/// Whether the file can be the photo of a Live Photo or of a motion photo
+fn is_primary_candidate(path: &Path) -> bool {
+    get_media_type_from_extension(path)
+        .is_some_and(|media_type| matches!(media_type, MediaType::Photo | MediaType::Raw))
+}
+
+/// Whether the file can be the video of a Live Photo or of a motion photo
+fn is_companion_candidate(path: &Path) -> bool {
+    get_media_type_from_extension(path) == Some(MediaType::Video)
+}
+
+fn get_media_type_from_extension(path: &Path) -> Option<MediaType> {
+    path.extension()
+        .and_then(|extension| MediaType::from_extension(&extension.to_string_lossy()))
+}
+
+/// Stems are compared ignoring the case, like `IMG_0001.HEIC` and `IMG_0001.mov`
+fn get_stem_key(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().to_lowercase())
+}
+
+/// Reads the content identifier from the Apple maker note of a photo.
+///
+/// # Arguments
+///
+/// * `maker_note` - The raw maker note, starting with `Apple iOS`.
+///
+/// # Returns
+///
+/// The identifier, a UUID shared with the video of the Live Photo, if any.
+pub fn parse_apple_content_identifier(maker_note: &[u8]) -> Option<String> {
+    parse_apple_maker_note_string(maker_note, APPLE_CONTENT_IDENTIFIER_TAG)
+}
+
+/// Reads the content identifier from the `keys` and `ilst` boxes of the QuickTime metadata.
+///
+/// # Arguments
+///
+/// * `moov` - The content of the `moov` box of the video.
+///
+/// # Returns
+///
+/// The identifier, a UUID shared with the photo of the Live Photo, if any.
+pub fn parse_quicktime_content_identifier(moov: &[u8]) -> Option<String> {
+    let meta = find_atom(moov, b"meta")?;
+    // The QuickTime `meta` box has no version and flags, unlike the ISO one
+    let meta = if meta.get(4..8) == Some(b"hdlr") {
+        meta
+    } else {
+        meta.get(4..)?
+    };
+
+    let keys = find_atom(meta, b"keys")?;
+    let number_of_keys = u32::from_be_bytes(keys.get(4..8)?.try_into().ok()?);
+    let mut offset = 8;
+    let mut key_index = None;
+    for index in 1..=number_of_keys {
+        let size = u32::from_be_bytes(keys.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        if size < 8 {
+            return None;
+        }
+        if keys.get(offset + 8..offset + size)? == QUICKTIME_CONTENT_IDENTIFIER {
+            key_index = Some(index);
+            break;
+        }
+        offset += size;
+    }
+    let key_index = key_index?.to_be_bytes();
+
+    // The items are boxes whose type is the index of their key
+    let item = find_atom(find_atom(meta, b"ilst")?, &key_index)?;
+    let data = find_atom(item, b"data")?;
+    let identifier = String::from_utf8_lossy(data.get(8..)?).to_string();
+    (!identifier.is_empty()).then_some(identifier)
+}
+
+/// Reads the content identifier of the video of a Live Photo, from its QuickTime metadata.
+pub fn get_video_content_identifier(path: &Path) -> Result<Option<String>, ClineupError> {
+    Ok(read_moov(path)?.and_then(|moov| parse_quicktime_content_identifier(&moov)))
+}
+
+/// Reads the content identifier of the photo of a Live Photo, from its Apple maker note.
+pub fn get_photo_content_identifier(path: &Path) -> Result<Option<String>, ClineupError> {
+    let exif_extractor = ExifExtractor::new(&path.to_path_buf())?;
+    Ok(exif_extractor
+        .get_maker_note()
+        .ok()
+        .and_then(parse_apple_content_identifier))
+}
+
+/// Companions of the files of a folder
+#[derive(Debug, Default)]
+struct FolderCompanions {
+    /// The companions of each primary file
+    companions: HashMap<PathBuf, Vec<PathBuf>>,
+    /// The primary file of each companion
+    primaries: HashMap<PathBuf, PathBuf>,
+}
+
+impl FolderCompanions {
+    fn add(&mut self, primary: &Path, companion: &Path) {
+        debug!("Pair {:?} with {:?}", companion, primary);
+        self.companions
+            .entry(primary.to_path_buf())
+            .or_default()
+            .push(companion.to_path_buf());
+        self.primaries
+            .insert(companion.to_path_buf(), primary.to_path_buf());
+    }
+}
+
+/// Pairs the companions of the files of a folder with their primary file, a companion
+/// following its primary file instead of being organized on its own.
+///
+/// The video of a Live Photo or of a motion photo is paired with the photo of the same folder
+/// with the same stem, like `IMG_0001.HEIC` and `IMG_0001.MOV`, or else with the same Apple
+/// content identifier. The sidecars, like `IMG_0001.xmp` or `IMG_0001.CR2.xmp`, are paired with
+/// the file of the same folder with the same stem, or named after its full name.
+#[derive(Debug)]
+pub struct CompanionFinder {
+    pair_live_photos: bool,
+    companion_extensions: Vec<String>,
+    folders: HashMap<PathBuf, FolderCompanions>,
+}
+
+impl CompanionFinder {
+    pub fn new() -> Self {
+        CompanionFinder {
+            pair_live_photos: true,
+            companion_extensions: DEFAULT_COMPANION_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            folders: HashMap::new(),
+        }
+    }
+
+    /// Sets whether the videos of Live Photos and motion photos follow their photo.
+    pub fn with_live_photos(mut self, pair_live_photos: bool) -> Self {
+        self.pair_live_photos = pair_live_photos;
+        self
+    }
+
+    /// Sets the extensions of the sidecars, in lowercase and without the dot.
+    pub fn with_companion_extensions(mut self, companion_extensions: Vec<String>) -> Self {
+        self.companion_extensions = companion_extensions;
+        self
+    }
+
+    fn get_folder_companions(&mut self, path: &Path) -> &FolderCompanions {
+        let folder = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        if !self.folders.contains_key(&folder) {
+            let folder_companions = self.find_folder_companions(&folder);
+            self.folders.insert(folder.clone(), folder_companions);
+        }
+        &self.folders[&folder]
+    }
+
+    /// Pairs the files of a folder, listing it as `FileIterator` does.
+    fn find_folder_companions(&self, folder: &Path) -> FolderCompanions {
+        let mut folder_companions = FolderCompanions::default();
+        let (sidecars, files): (Vec<PathBuf>, Vec<PathBuf>) = list_folder_files(folder)
+            .into_iter()
+            .partition(|path| is_sidecar(path, &self.companion_extensions));
+        if self.pair_live_photos {
+            pair_live_photos(&files, &mut folder_companions);
+        }
+        pair_sidecars(&files, &sidecars, &mut folder_companions);
+        folder_companions
+    }
+
+    /// The primary file a companion follows, if it is one
+    pub fn get_primary(&mut self, path: &Path) -> Option<PathBuf> {
+        self.get_folder_companions(path)
+            .primaries
+            .get(path)
+            .cloned()
+    }
+
+    /// The companions which follow a primary file
+    pub fn get_companions(&mut self, path: &Path) -> Vec<PathBuf> {
+        self.get_folder_companions(path)
+            .companions
+            .get(path)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for CompanionFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Builds the companion finder of the configuration, if any companion follows its primary file.
+pub fn get_companion_finder(config: &Config) -> Option<CompanionFinder> {
+    (config.pair_live_photos || !config.companion_extensions.is_empty()).then(|| {
+        CompanionFinder::new()
+            .with_live_photos(config.pair_live_photos)
+            .with_companion_extensions(config.companion_extensions.clone())
+    })
+}
+
+fn is_sidecar(path: &Path, companion_extensions: &[String]) -> bool {
+    path.extension().is_some_and(|extension| {
+        companion_extensions.contains(&extension.to_string_lossy().to_lowercase())
+    })
+}
+
+/// Lists the files of a folder, sorted by path.
+fn list_folder_files(folder: &Path) -> Vec<PathBuf> {
+    let listed_folder = if folder.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        folder
+    };
+    let mut files: Vec<PathBuf> = match std::fs::read_dir(listed_folder) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| folder.join(entry.file_name()))
+            .filter(|path| path.is_file())
+            .collect(),
+        Err(err) => {
+            debug!("Can not list {:?}: {}", folder, err);
+            Vec::new()
+        }
+    };
+    files.sort();
+    files
+}
+
+/// Pairs the videos of a folder with their photo, by stem or else by content identifier.
+fn pair_live_photos(files: &[PathBuf], folder_companions: &mut FolderCompanions) {
+    let mut primaries_by_stem: HashMap<String, &PathBuf> = HashMap::new();
+    for primary in files.iter().filter(|path| is_primary_candidate(path)) {
+        if let Some(stem) = get_stem_key(primary) {
+            primaries_by_stem.entry(stem).or_insert(primary);
+        }
+    }
+
+    let mut unpaired_videos = Vec::new();
+    for video in files.iter().filter(|path| is_companion_candidate(path)) {
+        match get_stem_key(video).and_then(|stem| primaries_by_stem.get(&stem)) {
+            Some(primary) => folder_companions.add(primary, video),
+            None => unpaired_videos.push(video),
+        }
+    }
+
+    // The content identifiers are only read when a video was renamed apart from its photo
+    let video_identifiers: Vec<(&PathBuf, String)> = unpaired_videos
+        .into_iter()
+        .filter_map(|video| match get_video_content_identifier(video) {
+            Ok(identifier) => identifier.map(|identifier| (video, identifier)),
+            Err(err) => {
+                debug!(
+                    "Can not read the content identifier of {:?}: {}",
+                    video, err
+                );
+                None
+            }
+        })
+        .collect();
+    if video_identifiers.is_empty() {
+        return;
+    }
+    let photo_identifiers: HashMap<String, &PathBuf> = files
+        .iter()
+        .filter(|path| {
+            is_primary_candidate(path) && !folder_companions.companions.contains_key(*path)
+        })
+        .filter_map(|photo| {
+            get_photo_content_identifier(photo)
+                .ok()
+                .flatten()
+                .map(|identifier| (identifier, photo))
+        })
+        .collect();
+    for (video, identifier) in video_identifiers {
+        if let Some(photo) = photo_identifiers.get(&identifier) {
+            folder_companions.add(photo, video);
+        }
+    }
+}
+
+/// Which file of the same stem a sidecar follows, the raw file before the photo, the video and the others
+fn get_primary_rank(path: &Path) -> u8 {
+    match get_media_type_from_extension(path) {
+        Some(MediaType::Raw) => 0,
+        Some(MediaType::Photo) => 1,
+        Some(MediaType::Video) => 2,
+        Some(MediaType::Audio) => 3,
+        _ => 4,
+    }
+}
+
+/// Pairs the sidecars of a folder with the file of the same stem, or named after its full name.
+fn pair_sidecars(
+    files: &[PathBuf],
+    sidecars: &[PathBuf],
+    folder_companions: &mut FolderCompanions,
+) {
+    let mut primaries_by_name: HashMap<String, &PathBuf> = HashMap::new();
+    let mut primaries_by_stem: HashMap<String, &PathBuf> = HashMap::new();
+    for primary in files
+        .iter()
+        .filter(|path| !folder_companions.primaries.contains_key(*path))
+    {
+        if let Some(name) = primary.file_name() {
+            primaries_by_name.insert(name.to_string_lossy().to_lowercase(), primary);
+        }
+        if let Some(stem) = get_stem_key(primary) {
+            let best = primaries_by_stem.entry(stem).or_insert(primary);
+            if get_primary_rank(primary) < get_primary_rank(best) {
+                *best = primary;
+            }
+        }
+    }
+
+    for sidecar in sidecars {
+        let Some(stem) = get_stem_key(sidecar) else {
+            continue;
+        };
+        let primary = primaries_by_name
+            .get(&stem)
+            .or_else(|| primaries_by_stem.get(&stem));
+        if let Some(primary) = primary {
+            folder_companions.add(primary, sidecar);
+        }
+    }
+}
+
+/// Destination of a companion next to the destination of its primary file, with the
+/// resolved name of the primary and the extension of the companion, like `Paris_0001.MOV`.
+/// A sidecar named after the full name of its primary file, like `IMG_0001.CR2.xmp`, keeps this style.
+///
+/// # Arguments
+///
+/// * `companion` - The companion to organize.
+/// * `primary` - The primary file it follows.
+/// * `primary_destination` - The destination of the primary file.
+pub fn get_companion_destination(
+    companion: &Path,
+    primary: &Path,
+    primary_destination: &Path,
+) -> PathBuf {
+    let is_named_after_full_name = match (get_stem_key(companion), primary.file_name()) {
+        (Some(stem), Some(name)) => stem == name.to_string_lossy().to_lowercase(),
+        _ => false,
+    };
+    let primary_name = if is_named_after_full_name {
+        primary_destination.file_name()
+    } else {
+        primary_destination.file_stem()
+    }
+    .map_or(String::new(), |name| name.to_string_lossy().to_string());
+
+    let file_name = match companion.extension() {
+        Some(extension) => format!("{}.{}", primary_name, extension.to_string_lossy()),
+        None => primary_name,
+    };
+    primary_destination.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn atom(kind: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut atom = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend_from_slice(content);
+        atom
+    }
+
+    /// A video with the QuickTime metadata of a Live Photo
+    fn live_photo_video(identifier: &str) -> Vec<u8> {
+        let mut keys = vec![0, 0, 0, 0, 0, 0, 0, 2];
+        for key in [
+            b"com.apple.quicktime.make".as_slice(),
+            QUICKTIME_CONTENT_IDENTIFIER,
+        ] {
+            keys.extend(atom(b"mdta", key));
+        }
+        let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend_from_slice(identifier.as_bytes());
+        let ilst = [
+            atom(
+                &1u32.to_be_bytes(),
+                &atom(b"data", b"\0\0\0\x01\0\0\0\0Apple"),
+            ),
+            atom(&2u32.to_be_bytes(), &atom(b"data", &data)),
+        ]
+        .concat();
+        let meta = [
+            atom(b"hdlr", &[0; 24]),
+            atom(b"keys", &keys),
+            atom(b"ilst", &ilst),
+        ]
+        .concat();
+
+        [
+            atom(b"ftyp", b"qt  \0\0\0\0qt  "),
+            atom(b"mdat", &[0; 64]),
+            atom(
+                b"moov",
+                &[atom(b"mvhd", &[0; 100]), atom(b"meta", &meta)].concat(),
+            ),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_parse_apple_content_identifier() {
+        let identifier = b"6F8A2D9C-1234-4E5F-8A9B-0C1D2E3F4A5B\0";
+        let mut maker_note = b"Apple iOS\0\0\x01MM".to_vec();
+        maker_note.extend_from_slice(&2u16.to_be_bytes());
+        // An entry of another tag, then the content identifier stored after the entries
+        maker_note.extend_from_slice(&[0, 1, 0, 9, 0, 0, 0, 1, 0, 0, 0, 14]);
+        let offset = 16 + 2 * 12 + 4;
+        maker_note.extend_from_slice(&APPLE_CONTENT_IDENTIFIER_TAG.to_be_bytes());
+        maker_note.extend_from_slice(&2u16.to_be_bytes());
+        maker_note.extend_from_slice(&(identifier.len() as u32).to_be_bytes());
+        maker_note.extend_from_slice(&(offset as u32).to_be_bytes());
+        maker_note.extend_from_slice(&[0; 4]);
+        maker_note.extend_from_slice(identifier);
+
+        assert_eq!(
+            parse_apple_content_identifier(&maker_note),
+            Some("6F8A2D9C-1234-4E5F-8A9B-0C1D2E3F4A5B".to_string())
+        );
+        assert_eq!(parse_apple_content_identifier(b"Nikon\0\x02"), None);
+    }
+
+    #[test]
+    fn test_get_video_content_identifier() {
+        let tmp_dir = TempDir::new("companions").unwrap();
+        let video = tmp_dir.path().join("IMG_0001.MOV");
+        std::fs::write(&video, live_photo_video("ABCD-0001")).unwrap();
+        assert_eq!(
+            get_video_content_identifier(&video).unwrap(),
+            Some("ABCD-0001".to_string())
+        );
+
+        let other = tmp_dir.path().join("other.mp4");
+        std::fs::write(&other, atom(b"ftyp", b"isom")).unwrap();
+        assert_eq!(get_video_content_identifier(&other).unwrap(), None);
+    }
+
+    #[test]
+    fn test_companion_finder() {
+        let tmp_dir = TempDir::new("companions").unwrap();
+        for name in [
+            "IMG_0001.HEIC",
+            "IMG_0001.MOV",
+            "PXL_0002.MP.jpg",
+            "PXL_0002.MP.mp4",
+            "IMG_0003.jpg",
+            "clip.mp4",
+        ] {
+            std::fs::write(tmp_dir.path().join(name), name).unwrap();
+        }
+        let path = |name: &str| tmp_dir.path().join(name);
+
+        let mut companion_finder = CompanionFinder::new();
+        assert_eq!(
+            companion_finder.get_companions(&path("IMG_0001.HEIC")),
+            vec![path("IMG_0001.MOV")]
+        );
+        assert_eq!(
+            companion_finder.get_primary(&path("PXL_0002.MP.mp4")),
+            Some(path("PXL_0002.MP.jpg"))
+        );
+        // A video without a photo is organized on its own
+        assert_eq!(companion_finder.get_primary(&path("clip.mp4")), None);
+        assert!(companion_finder
+            .get_companions(&path("IMG_0003.jpg"))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_companion_finder_sidecars() {
+        let tmp_dir = TempDir::new("companions").unwrap();
+        for name in [
+            "IMG_0001.CR2",
+            "IMG_0001.JPG",
+            "IMG_0001.xmp",
+            "IMG_0002.HEIC",
+            "IMG_0002.MOV",
+            "IMG_0002.AAE",
+            "IMG_0003.JPG",
+            "IMG_0003.JPG.xmp",
+            "DJI_0004.MP4",
+            "DJI_0004.SRT",
+            "GOPR0005.MP4",
+            "GOPR0005.LRV",
+            "GOPR0005.THM",
+            "orphan.xmp",
+        ] {
+            std::fs::write(tmp_dir.path().join(name), name).unwrap();
+        }
+        let path = |name: &str| tmp_dir.path().join(name);
+
+        let mut companion_finder = CompanionFinder::new();
+        // The sidecar follows the raw file rather than its JPEG
+        assert_eq!(
+            companion_finder.get_companions(&path("IMG_0001.CR2")),
+            vec![path("IMG_0001.xmp")]
+        );
+        assert_eq!(
+            companion_finder.get_companions(&path("IMG_0002.HEIC")),
+            vec![path("IMG_0002.MOV"), path("IMG_0002.AAE")]
+        );
+        assert_eq!(
+            companion_finder.get_primary(&path("IMG_0003.JPG.xmp")),
+            Some(path("IMG_0003.JPG"))
+        );
+        assert_eq!(
+            companion_finder.get_primary(&path("DJI_0004.SRT")),
+            Some(path("DJI_0004.MP4"))
+        );
+        assert_eq!(
+            companion_finder.get_companions(&path("GOPR0005.MP4")),
+            vec![path("GOPR0005.LRV"), path("GOPR0005.THM")]
+        );
+        assert_eq!(companion_finder.get_primary(&path("orphan.xmp")), None);
+
+        let mut companion_finder = CompanionFinder::new()
+            .with_live_photos(false)
+            .with_companion_extensions(vec!["srt".to_string()]);
+        assert_eq!(companion_finder.get_primary(&path("IMG_0002.MOV")), None);
+        assert_eq!(companion_finder.get_primary(&path("IMG_0001.xmp")), None);
+        assert_eq!(
+            companion_finder.get_primary(&path("DJI_0004.SRT")),
+            Some(path("DJI_0004.MP4"))
+        );
+    }
+
+    #[test]
+    fn test_get_companion_destination() {
+        assert_eq!(
+            get_companion_destination(
+                Path::new("/sd/IMG_0001.MOV"),
+                Path::new("/sd/IMG_0001.HEIC"),
+                Path::new("/photos/2023/Paris_0001.heic")
+            ),
+            PathBuf::from("/photos/2023/Paris_0001.MOV")
+        );
+        assert_eq!(
+            get_companion_destination(
+                Path::new("/sd/IMG_0001.CR2.xmp"),
+                Path::new("/sd/IMG_0001.CR2"),
+                Path::new("/photos/2023/Paris_0001.cr2")
+            ),
+            PathBuf::from("/photos/2023/Paris_0001.cr2.xmp")
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use regex::Regex;
+use std::cmp::Ordering;
+use std::sync::OnceLock;
+
+use crate::path::template::PlaceholderValue;
+use crate::path::template::PlaceholderValues;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Placeholder(String),
+    Literal(String),
+}
+
+impl Operand {
+    fn from_string(operand: &str) -> Operand {
+        let operand = operand.trim();
+        if operand.starts_with('%') {
+            return Operand::Placeholder(operand.to_string());
+        }
+        let unquoted = operand
+            .strip_prefix('"')
+            .and_then(|operand| operand.strip_suffix('"'))
+            .unwrap_or(operand);
+        Operand::Literal(unquoted.to_string())
+    }
+
+    fn resolve(&self, values: &PlaceholderValues) -> PlaceholderValue {
+        match self {
+            Operand::Placeholder(text) => values
+                .get(text)
+                .cloned()
+                .unwrap_or_else(|| PlaceholderValue::new(String::new(), true)),
+            Operand::Literal(literal) => PlaceholderValue::new(literal.clone(), false),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    LowerOrEqual,
+    GreaterOrEqual,
+    Lower,
+    Greater,
+}
+
+impl Operator {
+    // The two characters operators come first so that `<=` is not read as `<`
+    const ALL: [(&'static str, Operator); 6] = [
+        ("==", Operator::Equal),
+        ("!=", Operator::NotEqual),
+        ("<=", Operator::LowerOrEqual),
+        (">=", Operator::GreaterOrEqual),
+        ("<", Operator::Lower),
+        (">", Operator::Greater),
+    ];
+
+    fn matches(&self, ordering: Ordering) -> bool {
+        match self {
+            Operator::Equal => ordering == Ordering::Equal,
+            Operator::NotEqual => ordering != Ordering::Equal,
+            Operator::LowerOrEqual => ordering != Ordering::Greater,
+            Operator::GreaterOrEqual => ordering != Ordering::Less,
+            Operator::Lower => ordering == Ordering::Less,
+            Operator::Greater => ordering == Ordering::Greater,
+        }
+    }
+}
+
+/// Condition of a `{if ...}` block or of a ternary, like `%media_type == video` or `%has_gps`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    left: Operand,
+    comparison: Option<(Operator, Operand)>,
+}
+
+impl Condition {
+    pub fn from_string(condition: &str) -> Condition {
+        for (symbol, operator) in Operator::ALL {
+            if let Some(index) = condition.find(symbol) {
+                return Condition {
+                    left: Operand::from_string(&condition[..index]),
+                    comparison: Some((
+                        operator,
+                        Operand::from_string(&condition[index + symbol.len()..]),
+                    )),
+                };
+            }
+        }
+        Condition {
+            left: Operand::from_string(condition),
+            comparison: None,
+        }
+    }
+
+    /// Texts of the placeholders the condition depends on
+    pub fn placeholders(&self) -> Vec<&str> {
+        let right = self.comparison.as_ref().map(|(_, right)| right);
+        [Some(&self.left), right]
+            .into_iter()
+            .flatten()
+            .filter_map(|operand| match operand {
+                Operand::Placeholder(text) => Some(text.as_str()),
+                Operand::Literal(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn evaluate(&self, values: &PlaceholderValues) -> bool {
+        let left = self.left.resolve(values);
+        match &self.comparison {
+            None => !left.is_fallback() && is_truthy(left.text()),
+            Some((operator, right)) => {
+                compare(*operator, left.text(), right.resolve(values).text())
+            }
+        }
+    }
+}
+
+fn is_truthy(value: &str) -> bool {
+    let value = value.trim().to_ascii_lowercase();
+    !(value.is_empty() || value == "false" || value == "no" || value == "0")
+}
+
+/// Sizes like `10MB`, compiled once since conditions are evaluated for every file
+static SIZE_REGEX: OnceLock<Regex> = OnceLock::new();
+
+/// Parses a number, which may be a size like `10MB` or `5.3KB`
+fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Ok(number) = value.parse::<f64>() {
+        return Some(number);
+    }
+    let captures = SIZE_REGEX
+        .get_or_init(|| {
+            Regex::new(r"(?i)^(?P<number>[0-9]+(?:\.[0-9]+)?)\s*(?P<unit>[KMGTP]?)[Bo]$").unwrap()
+        })
+        .captures(value)?;
+    let number: f64 = captures["number"].parse().ok()?;
+    let exponent = match captures["unit"].to_ascii_uppercase().as_str() {
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        _ => 0,
+    };
+    Some(number * 1024_f64.powi(exponent))
+}
+
+/// Compares two values as numbers if both are numbers, else as case insensitive texts.
+/// Texts can only be equal or different.
+fn compare(operator: Operator, left: &str, right: &str) -> bool {
+    match (parse_number(left), parse_number(right)) {
+        (Some(left), Some(right)) => left
+            .partial_cmp(&right)
+            .is_some_and(|ordering| operator.matches(ordering)),
+        _ => {
+            let is_equal = left.trim().eq_ignore_ascii_case(right.trim());
+            match operator {
+                Operator::Equal => is_equal,
+                Operator::NotEqual => !is_equal,
+                _ => false,
+            }
+        }
+    }
+}
+
+/// Tag found between curly braces which drives the conditionals
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    If(Condition),
+    Elif(Condition),
+    Else,
+    End,
+    Ternary(Condition, String, String),
+}
+
+/// Finds the first occurrence of `needle` which is not inside parentheses
+fn find_top_level(text: &str, needle: impl Fn(usize, char) -> bool) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 && needle(index, c) => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses the conditional tag starting at the beginning of the text, like `{if %width > 3000}`.
+///
+/// # Arguments
+///
+/// * `text` - The rest of the template, starting with `{`.
+///
+/// # Returns
+///
+/// The tag and its length in bytes, or `None` if the curly braces hold a regular placeholder.
+pub fn parse_tag(text: &str) -> Option<(Tag, usize)> {
+    let content_start = text.strip_prefix('{')?;
+    let end = find_top_level(content_start, |_, c| c == '}')?;
+    let content = &content_start[..end];
+    let trimmed = content.trim();
+    let length = end + 2;
+
+    if trimmed == "else" {
+        return Some((Tag::Else, length));
+    }
+    if trimmed == "end" {
+        return Some((Tag::End, length));
+    }
+    if let Some(condition) = trimmed.strip_prefix("if ") {
+        return Some((Tag::If(Condition::from_string(condition)), length));
+    }
+    if let Some(condition) = trimmed.strip_prefix("elif ") {
+        return Some((Tag::Elif(Condition::from_string(condition)), length));
+    }
+
+    if !trimmed.starts_with('%') {
+        return None;
+    }
+    let question_mark = find_top_level(content, |_, c| c == '?')?;
+    let branches = &content[question_mark + 1..];
+    // The colon separating the branches is preceded by a space, unlike the one of `%exif:Make`
+    let colon = find_top_level(branches, |index, c| {
+        c == ':' && branches[..index].ends_with(char::is_whitespace)
+    })?;
+
+    Some((
+        Tag::Ternary(
+            Condition::from_string(&content[..question_mark]),
+            branches[..colon].trim().to_string(),
+            branches[colon + 1..].trim().to_string(),
+        ),
+        length,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(entries: &[(&str, &str, bool)]) -> PlaceholderValues {
+        entries
+            .iter()
+            .map(|(text, value, is_fallback)| {
+                (
+                    text.to_string(),
+                    PlaceholderValue::new(value.to_string(), *is_fallback),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        assert_eq!(
+            parse_tag("{if %width >= 3000}/rest"),
+            Some((
+                Tag::If(Condition {
+                    left: Operand::Placeholder("%width".to_string()),
+                    comparison: Some((
+                        Operator::GreaterOrEqual,
+                        Operand::Literal("3000".to_string())
+                    )),
+                }),
+                19
+            ))
+        );
+        assert_eq!(parse_tag("{else}"), Some((Tag::Else, 6)));
+        assert_eq!(
+            parse_tag("{%exif:Make ? %city : No location}"),
+            Some((
+                Tag::Ternary(
+                    Condition::from_string("%exif:Make"),
+                    "%city".to_string(),
+                    "No location".to_string()
+                ),
+                34
+            ))
+        );
+        assert_eq!(parse_tag("{%year|Unknown}"), None);
+        assert_eq!(parse_tag("{%exif:Make}"), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let values = values(&[
+            ("%media_type", "Video", false),
+            ("%size", "2097152", false),
+            ("%city", "Unknown City", true),
+            ("%has_gps", "false", false),
+        ]);
+        assert!(Condition::from_string("%media_type == video").evaluate(&values));
+        assert!(Condition::from_string("%size > 1MB").evaluate(&values));
+        assert!(!Condition::from_string("%size <= 1MB").evaluate(&values));
+        assert!(!Condition::from_string("%city").evaluate(&values));
+        assert!(!Condition::from_string("%has_gps").evaluate(&values));
+        assert!(!Condition::from_string("%media_type > photo").evaluate(&values));
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("3000"), Some(3000.0));
+        assert_eq!(parse_number("10MB"), Some(10.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_number("5.3KB"), Some(5.3 * 1024.0));
+        assert_eq!(parse_number("Canon"), None);
+    }
+}
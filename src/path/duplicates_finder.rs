@@ -1,8 +1,124 @@
 use crate::errors::ClineupError;
-use sha2::{Digest, Sha256};
-use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
+use crate::path::hash_index::is_hash_index_file;
+use crate::path::hash_index::HashIndex;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use xxhash_rust::xxh3::Xxh3;
 
-/// Calculates the SHA256 hash of a given file by using a 1024 bytes buffer.
+/// Number of bytes read at the start and at the end of a file for its partial hash
+pub const PARTIAL_HASH_SIZE: u64 = 4096;
+
+/// Size of the buffer used to read the files
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Algorithm used to compute the hash of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha512,
+    /// Non-cryptographic, much faster, good enough to find duplicates
+    Xxh3,
+    Blake3,
+}
+
+impl HashAlgorithm {
+    pub fn from_string<S: Into<String>>(chain: S) -> Result<HashAlgorithm, ClineupError> {
+        let algorithm = chain.into();
+        match algorithm.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "xxh3" => Ok(HashAlgorithm::Xxh3),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => Err(ClineupError::InvalidHashAlgorithm(algorithm)),
+        }
+    }
+
+    /// Name of the algorithm, as given on the command line and kept in the hash index
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512 => "sha512",
+            HashAlgorithm::Xxh3 => "xxh3",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+}
+
+/// Running hash of a file, whatever the algorithm
+enum FileHasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Xxh3(Box<Xxh3>),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl FileHasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => FileHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Sha512 => FileHasher::Sha512(Sha512::new()),
+            HashAlgorithm::Xxh3 => FileHasher::Xxh3(Box::new(Xxh3::new())),
+            HashAlgorithm::Blake3 => FileHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            FileHasher::Sha256(hasher) => hasher.update(bytes),
+            FileHasher::Sha512(hasher) => hasher.update(bytes),
+            FileHasher::Xxh3(hasher) => hasher.update(bytes),
+            FileHasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            FileHasher::Sha256(hasher) => to_hex(&hasher.finalize()),
+            FileHasher::Sha512(hasher) => to_hex(&hasher.finalize()),
+            FileHasher::Xxh3(hasher) => format!("{:032x}", hasher.digest128()),
+            FileHasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hashes at most `length` bytes of the file, from its current position.
+fn update_with_file_content(
+    hasher: &mut FileHasher,
+    open_file: &File,
+    length: u64,
+) -> Result<(), ClineupError> {
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let mut reader = open_file.take(length);
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => break, // End of file
+            Ok(bytes_read) => hasher.update(&buffer[..bytes_read]),
+            Err(_) => {
+                return Err(ClineupError::HashError(
+                    "Something went wrong reading buffer of file".to_string(),
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Calculates the SHA256 hash of a given file.
 ///
 /// # Arguments
 ///
@@ -11,34 +127,303 @@ use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
 /// # Returns
 ///
 /// The SHA256 hash of the file as a hexadecimal string, or an error if the hash calculation fails.
-pub fn get_hash_of_file(mut open_file: &File) -> Result<String, ClineupError> {
-    let mut hasher = Sha256::new();
-    let mut buffer = [0u8; 1024];
-    loop {
-        match open_file.read(&mut buffer) {
-            Ok(0) => break, // End of file
-            Ok(bytes_read) => {
-                hasher.update(&buffer[..bytes_read]);
-            }
-            Err(_) => {
-                return Err(ClineupError::HashError(
-                    "Something went wrong reading buffer of file".to_string(),
-                ))
-            }
+pub fn get_hash_of_file(open_file: &File) -> Result<String, ClineupError> {
+    get_hash_of_file_with(open_file, HashAlgorithm::Sha256)
+}
+
+/// Calculates the hash of a given file with the given algorithm.
+///
+/// # Arguments
+///
+/// * `open_file` - The file to calculate the hash of.
+/// * `algorithm` - The hash algorithm to use.
+///
+/// # Returns
+///
+/// The hash of the file as a hexadecimal string, or an error if the hash calculation fails.
+pub fn get_hash_of_file_with(
+    open_file: &File,
+    algorithm: HashAlgorithm,
+) -> Result<String, ClineupError> {
+    let mut hasher = FileHasher::new(algorithm);
+    update_with_file_content(&mut hasher, open_file, u64::MAX)?;
+    Ok(hasher.finalize())
+}
+
+/// Calculates the hash of the first and last `PARTIAL_HASH_SIZE` bytes of a file,
+/// which tells most files of the same size apart without reading them entirely.
+///
+/// # Arguments
+///
+/// * `open_file` - The file to calculate the partial hash of.
+/// * `algorithm` - The hash algorithm to use.
+///
+/// # Returns
+///
+/// The partial hash of the file as a hexadecimal string, which is its full hash for small files.
+pub fn get_partial_hash_of_file_with(
+    mut open_file: &File,
+    algorithm: HashAlgorithm,
+) -> Result<String, ClineupError> {
+    let size = open_file.metadata()?.len();
+    let mut hasher = FileHasher::new(algorithm);
+    if size <= 2 * PARTIAL_HASH_SIZE {
+        update_with_file_content(&mut hasher, open_file, size)?;
+    } else {
+        update_with_file_content(&mut hasher, open_file, PARTIAL_HASH_SIZE)?;
+        open_file.seek(SeekFrom::End(-(PARTIAL_HASH_SIZE as i64)))?;
+        update_with_file_content(&mut hasher, open_file, PARTIAL_HASH_SIZE)?;
+    }
+    Ok(hasher.finalize())
+}
+
+/// File which may be a duplicate of the next files of the same size, hashed only when needed
+#[derive(Debug)]
+struct Candidate {
+    path: PathBuf,
+    /// Only the files of the library are written to the hash index
+    in_library: bool,
+    partial_hash: Option<String>,
+    hash: Option<String>,
+}
+
+impl Candidate {
+    fn new(path: PathBuf, in_library: bool) -> Self {
+        Candidate {
+            path,
+            in_library,
+            partial_hash: None,
+            hash: None,
         }
     }
-    let result = hasher.finalize();
-    Ok(format!("{:x}", result))
 }
 
+/// Finds the duplicates in stages : a file of a new size is not read at all, then the files of the same size
+/// are compared by partial hash, and only the ones with the same partial hash are fully hashed.
 pub struct DuplicatesFinder {
-    _duplicates: HashMap<u64, Vec<String>>,
+    /// Files of the library and of the run, by size
+    _candidates: HashMap<u64, Vec<Candidate>>,
+    _hash_algorithm: HashAlgorithm,
+    _hash_index: Option<Rc<RefCell<HashIndex>>>,
+    /// Full hash of the last checked file, to record it once organized
+    _last_hash: Option<(PathBuf, String)>,
+}
+
+/// Lists the files of a library recursively, with their size, skipping the empty files and the hash index.
+/// Their paths are canonical, to recognize a source which is part of the library.
+/// A library which does not exist yet, like a new destination, is empty.
+///
+/// # Arguments
+///
+/// * `library` - The folder of the library.
+///
+/// # Returns
+///
+/// The files and their size, or an error if a folder of the library can not be read.
+pub fn list_library_files(library: &Path) -> Result<Vec<(PathBuf, u64)>, ClineupError> {
+    list_files(library, true)
+}
+
+/// Lists the regular files of a folder recursively, with their size, like `list_library_files`,
+/// but skipping the symlinks, so that no file outside the folder is listed.
+/// Their paths are under the canonical folder, and are never resolved through a symlink.
+///
+/// # Arguments
+///
+/// * `folder` - The folder to list.
+///
+/// # Returns
+///
+/// The files and their size, or an error if a folder can not be read.
+pub fn list_regular_files(folder: &Path) -> Result<Vec<(PathBuf, u64)>, ClineupError> {
+    list_files(folder, false)
+}
+
+/// Lists the files of a folder recursively, following the symlinks to files or skipping them.
+fn list_files(library: &Path, follow_symlinks: bool) -> Result<Vec<(PathBuf, u64)>, ClineupError> {
+    let mut library_files = Vec::new();
+    if !library.is_dir() {
+        return Ok(library_files);
+    }
+
+    let root = if follow_symlinks {
+        library.to_path_buf()
+    } else {
+        library.canonicalize()?
+    };
+    let mut folders = vec![root];
+    while let Some(folder) = folders.pop() {
+        for entry in std::fs::read_dir(folder)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                folders.push(path);
+                continue;
+            }
+            if is_hash_index_file(&path) {
+                continue;
+            }
+            let (path, metadata) = if follow_symlinks {
+                // Symlinked files are followed, the library being possibly organized with symlinks
+                let Ok(metadata) = std::fs::metadata(&path) else {
+                    warn!("Can not index {:?} of the library", path);
+                    continue;
+                };
+                (path.canonicalize().unwrap_or(path), metadata)
+            } else {
+                // The metadata of a symlink itself, which is not a file
+                (path, entry.metadata()?)
+            };
+            if metadata.is_file() && metadata.len() > 0 {
+                library_files.push((path, metadata.len()));
+            }
+        }
+    }
+    library_files.sort();
+    Ok(library_files)
 }
 
 impl DuplicatesFinder {
     pub fn new() -> Self {
         DuplicatesFinder {
-            _duplicates: HashMap::new(),
+            _candidates: HashMap::new(),
+            _hash_algorithm: HashAlgorithm::Sha256,
+            _hash_index: None,
+            _last_hash: None,
+        }
+    }
+
+    /// Compares the files with another algorithm than SHA-256, like the much faster xxh3.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: HashAlgorithm) -> Self {
+        self._hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Uses the persistent index of the library to hash its files, which only hashes the modified files.
+    /// The index is shared with the one recording the organized files when the library is the destination,
+    /// and must use the same hash algorithm as the finder.
+    pub fn with_hash_index(mut self, hash_index: Rc<RefCell<HashIndex>>) -> Self {
+        self._hash_index = Some(hash_index);
+        self
+    }
+
+    /// Indexes the files of an existing library by size, recursively.
+    /// They are hashed only when a file of the same size is checked, since most sizes never match.
+    /// A library which does not exist yet, like a new destination, is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `library` - The folder of the library.
+    ///
+    /// # Returns
+    ///
+    /// The number of files indexed, or an error if a folder of the library can not be read.
+    pub fn index_library(&mut self, library: &Path) -> Result<usize, ClineupError> {
+        let library_files = list_library_files(library)?;
+        let indexed_files = library_files.len();
+        for (path, size) in library_files {
+            self._candidates
+                .entry(size)
+                .or_default()
+                .push(Candidate::new(path, true));
+        }
+        Ok(indexed_files)
+    }
+
+    fn get_partial_hash(&self, path: &Path) -> Result<String, ClineupError> {
+        get_partial_hash_of_file_with(&File::open(path)?, self._hash_algorithm)
+    }
+
+    /// Hashes a file through the index, which is only updated for the files of the library.
+    fn get_hash(&mut self, path: &Path, in_library: bool) -> Result<String, ClineupError> {
+        if let Some(hash_index) = &self._hash_index {
+            let mut hash_index = hash_index.borrow_mut();
+            if in_library {
+                return hash_index.get_hash(path);
+            }
+            if let Some(hash) = hash_index.get_indexed_hash(path)? {
+                return Ok(hash);
+            }
+        }
+        get_hash_of_file_with(&File::open(path)?, self._hash_algorithm)
+    }
+
+    /// Hashes a candidate at the given stage if it is not done yet.
+    /// A candidate which can not be read anymore is never a duplicate.
+    fn get_candidate_hash(&mut self, size: u64, index: usize, partial: bool) -> Option<String> {
+        let candidate = &self._candidates[&size][index];
+        let known_hash = if partial {
+            &candidate.partial_hash
+        } else {
+            &candidate.hash
+        };
+        if known_hash.is_some() {
+            return known_hash.clone();
+        }
+
+        let path = candidate.path.clone();
+        let in_library = candidate.in_library;
+        let hash = if partial {
+            self.get_partial_hash(&path)
+        } else {
+            self.get_hash(&path, in_library)
+        };
+        let hash = match hash {
+            Ok(hash) => hash,
+            Err(err) => {
+                warn!("Can not hash {:?}: {}", path, err);
+                return None;
+            }
+        };
+
+        let candidate = &mut self._candidates.get_mut(&size).unwrap()[index];
+        if partial {
+            candidate.partial_hash = Some(hash.clone());
+        } else {
+            candidate.hash = Some(hash.clone());
+        }
+        Some(hash)
+    }
+
+    /// Compares a file with a candidate of the same size, reading them as little as possible.
+    /// The hashes are kept, so that no file is hashed twice at the same stage.
+    fn is_same_content(
+        &mut self,
+        file: &mut Candidate,
+        size: u64,
+        index: usize,
+    ) -> Result<bool, ClineupError> {
+        if file.partial_hash.is_none() {
+            file.partial_hash = Some(self.get_partial_hash(&file.path)?);
+        }
+        if file.partial_hash != self.get_candidate_hash(size, index, true) {
+            return Ok(false);
+        }
+        // The partial hash of a small file is its full hash
+        if size <= 2 * PARTIAL_HASH_SIZE {
+            return Ok(true);
+        }
+
+        if file.hash.is_none() {
+            file.hash = Some(self.get_hash(&file.path, file.in_library)?);
+        }
+        Ok(file.hash == self.get_candidate_hash(size, index, false))
+    }
+
+    /// Takes the full hash of a file which was just checked, if it was computed,
+    /// so that its copy is recorded in the persistent index without being hashed again.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The organized file.
+    ///
+    /// # Returns
+    ///
+    /// The hash of the file with the algorithm of the finder, or `None` if it was not fully hashed.
+    pub fn take_hash_of(&mut self, source: &Path) -> Option<String> {
+        match self._last_hash.take() {
+            Some((path, hash)) if path == source => Some(hash),
+            _ => None,
         }
     }
 }
@@ -48,8 +433,8 @@ impl Default for DuplicatesFinder {
         Self::new()
     }
 }
-/// Checks if the given path is a duplicate file by detecting its
-/// precence in the hashmap.Otherwise store the hash of the file in the hashmap
+/// Checks if the given path is a duplicate of a file of the library or of a previous file.
+/// Otherwise the file becomes a candidate for the next files of the same size.
 ///
 /// # Arguments
 ///
@@ -61,34 +446,51 @@ impl Default for DuplicatesFinder {
 /// Returns an `Err` if there was an error while checking for duplicates.
 impl DuplicatesFinder {
     pub fn is_duplicate(&mut self, path: &PathBuf) -> Result<bool, ClineupError> {
+        Ok(self.find_duplicate(path)?.is_some())
+    }
+
+    /// Finds the file of the library or the previous file the given path is a duplicate of.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to check.
+    ///
+    /// # Returns
+    ///
+    /// The path of the first file with the same content, or `None` if the file is not a duplicate.
+    pub fn find_duplicate(&mut self, path: &PathBuf) -> Result<Option<PathBuf>, ClineupError> {
         let metadata = std::fs::metadata(path)?;
 
         if metadata.is_dir() {
-            return Ok(false);
+            return Ok(None);
         }
 
         if metadata.len() == 0 {
-            return Ok(false);
+            return Ok(None);
         }
 
-        let open_file = File::open(path)?;
-        let hash_of_file = get_hash_of_file(&open_file)?;
-
-        if let std::collections::hash_map::Entry::Vacant(e) = self._duplicates.entry(metadata.len())
-        {
-            e.insert(vec![hash_of_file]);
-            return Ok(false);
-        }
+        let size = metadata.len();
+        // A file is not a duplicate of itself, when the source is part of the library or when
+        // overlapping sources reach it twice, so the candidates are compared by canonical path
+        let canonical_path = path.canonicalize().unwrap_or(path.clone());
+        let mut file = Candidate::new(canonical_path.clone(), false);
 
-        if let Some(duplicates) = self._duplicates.get_mut(&metadata.len()) {
-            if !duplicates.contains(&hash_of_file) {
-                duplicates.push(hash_of_file);
-                return Ok(false);
+        let number_of_candidates = self._candidates.get(&size).map_or(0, Vec::len);
+        let mut original = None;
+        for index in 0..number_of_candidates {
+            if self._candidates[&size][index].path == canonical_path {
+                continue;
+            }
+            if self.is_same_content(&mut file, size, index)? {
+                original = Some(self._candidates[&size][index].path.clone());
+                break;
             }
-        } else {
-            return Ok(false);
         }
 
-        Ok(true)
+        self._last_hash = file.hash.clone().map(|hash| (path.clone(), hash));
+        if original.is_none() {
+            self._candidates.entry(size).or_default().push(file);
+        }
+        Ok(original)
     }
 }
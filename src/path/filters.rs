@@ -0,0 +1,221 @@
+use deunicode::deunicode;
+
+use crate::errors::ClineupError;
+
+/// Placeholder whose first argument is part of the placeholder itself, like `%exif:LensModel`
+const PLACEHOLDERS_WITH_ARGUMENT: [&str; 1] = ["%exif"];
+
+/// Transformation applied to the value of a placeholder, like `lower` in `{%camera_brand:lower}`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Lower,
+    Upper,
+    Trim,
+    Ascii,
+    Slug,
+    Replace(String, String),
+    Truncate(usize),
+    Pad(usize),
+}
+
+/// Splits a filter like `replace( ,_)` into its name and its arguments
+fn split_filter_arguments(filter: &str) -> Result<(&str, Option<&str>), ClineupError> {
+    match filter.find('(') {
+        Some(start) if filter.ends_with(')') => {
+            Ok((&filter[..start], Some(&filter[start + 1..filter.len() - 1])))
+        }
+        Some(_) => Err(ClineupError::InvalidFilter(filter.to_string())),
+        None => Ok((filter, None)),
+    }
+}
+
+impl Filter {
+    pub fn from_string(filter: &str) -> Result<Filter, ClineupError> {
+        let invalid = || ClineupError::InvalidFilter(filter.to_string());
+        let parse_number = |arguments: Option<&str>| -> Result<usize, ClineupError> {
+            arguments
+                .and_then(|number| number.trim().parse::<usize>().ok())
+                .ok_or_else(invalid)
+        };
+
+        let (name, arguments) = split_filter_arguments(filter)?;
+        match (name, arguments) {
+            ("lower", None) => Ok(Filter::Lower),
+            ("upper", None) => Ok(Filter::Upper),
+            ("trim", None) => Ok(Filter::Trim),
+            ("ascii", None) => Ok(Filter::Ascii),
+            ("slug", None) => Ok(Filter::Slug),
+            ("replace", Some(arguments)) => arguments
+                .split_once(',')
+                .map(|(from, to)| Filter::Replace(from.to_string(), to.to_string()))
+                .ok_or_else(invalid),
+            ("truncate", arguments) => parse_number(arguments).map(Filter::Truncate),
+            ("pad", arguments) => parse_number(arguments).map(Filter::Pad),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Filter::Lower => value.to_lowercase(),
+            Filter::Upper => value.to_uppercase(),
+            Filter::Trim => value.trim().to_string(),
+            Filter::Ascii => deunicode(value),
+            Filter::Slug => slugify(value),
+            Filter::Replace(from, to) => value.replace(from.as_str(), to),
+            Filter::Truncate(length) => value.chars().take(*length).collect(),
+            Filter::Pad(width) => format!("{:0>width$}", value, width = width),
+        }
+    }
+}
+
+/// Converts a value to lowercase ASCII words separated by dashes, like `sao-paulo`
+fn slugify(value: &str) -> String {
+    deunicode(value)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+/// Splits a placeholder on the colons which are not inside parentheses
+fn split_segments(placeholder_text: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in placeholder_text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ':' if depth == 0 => {
+                segments.push(&placeholder_text[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&placeholder_text[start..]);
+    segments
+}
+
+/// Separates a placeholder from its filters.
+///
+/// # Arguments
+///
+/// * `placeholder_text` - The placeholder, like `%camera_brand:lower:replace( ,_)`.
+///
+/// # Returns
+///
+/// The placeholder without the filters, like `%camera_brand`, and the raw filters, like `["lower", "replace( ,_)"]`.
+/// Texts not starting with `%` are fallback values and never have filters.
+pub fn split_placeholder_filters(placeholder_text: &str) -> (String, Vec<String>) {
+    if !placeholder_text.starts_with('%') {
+        return (placeholder_text.to_string(), vec![]);
+    }
+
+    let segments = split_segments(placeholder_text);
+    let argument_count = if PLACEHOLDERS_WITH_ARGUMENT.contains(&segments[0]) && segments.len() > 1
+    {
+        2
+    } else {
+        1
+    };
+
+    (
+        segments[..argument_count].join(":"),
+        segments[argument_count..]
+            .iter()
+            .map(|filter| filter.to_string())
+            .collect(),
+    )
+}
+
+/// Parses the filters of a placeholder.
+///
+/// # Returns
+///
+/// The filters in the order they must be applied, or an error if one of them is unknown.
+pub fn get_filters(placeholder_text: &str) -> Result<Vec<Filter>, ClineupError> {
+    let (_, filters) = split_placeholder_filters(placeholder_text);
+    filters
+        .iter()
+        .map(|filter| Filter::from_string(filter))
+        .collect()
+}
+
+pub fn apply_filters(value: String, filters: &[Filter]) -> String {
+    filters
+        .iter()
+        .fold(value, |value, filter| filter.apply(&value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_placeholder_filters() {
+        assert_eq!(
+            split_placeholder_filters("%camera_brand:lower:replace( ,_)"),
+            (
+                "%camera_brand".to_string(),
+                vec!["lower".to_string(), "replace( ,_)".to_string()]
+            )
+        );
+        assert_eq!(
+            split_placeholder_filters("%exif:LensModel:slug"),
+            ("%exif:LensModel".to_string(), vec!["slug".to_string()])
+        );
+        assert_eq!(
+            split_placeholder_filters("%camera_model:replace(:,-)"),
+            (
+                "%camera_model".to_string(),
+                vec!["replace(:,-)".to_string()]
+            )
+        );
+        assert_eq!(
+            split_placeholder_filters("Unknown: city"),
+            ("Unknown: city".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn test_from_string() {
+        assert_eq!(Filter::from_string("lower").unwrap(), Filter::Lower);
+        assert_eq!(
+            Filter::from_string("replace( ,_)").unwrap(),
+            Filter::Replace(" ".to_string(), "_".to_string())
+        );
+        assert_eq!(
+            Filter::from_string("truncate(20)").unwrap(),
+            Filter::Truncate(20)
+        );
+        assert!(Filter::from_string("pad").is_err());
+        assert!(Filter::from_string("replace(abc)").is_err());
+        assert!(Filter::from_string("unknown").is_err());
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(
+            Filter::Lower.apply("NIKON CORPORATION"),
+            "nikon corporation"
+        );
+        assert_eq!(Filter::Ascii.apply("Zürich"), "Zurich");
+        assert_eq!(Filter::Slug.apply("São Paulo, Brasil"), "sao-paulo-brasil");
+        assert_eq!(Filter::Truncate(3).apply("Genève"), "Gen");
+        assert_eq!(Filter::Pad(2).apply("3"), "03");
+        assert_eq!(Filter::Pad(2).apply("12"), "12");
+        assert_eq!(
+            apply_filters(
+                "Canon EOS R5".to_string(),
+                &[
+                    Filter::Upper,
+                    Filter::Replace(" ".to_string(), "_".to_string())
+                ]
+            ),
+            "CANON_EOS_R5"
+        );
+    }
+}
@@ -1,22 +1,109 @@
 use exif::Error as _ExifError;
 use log::debug;
-use log::info;
 use log::warn;
 
+use crate::bursts::Bursts;
+use crate::clock_correction::find_clock_delta;
+use crate::clock_correction::ClockCorrection;
 use crate::errors::ClineupError;
 
+use crate::exif_extractor::format_aperture;
+use crate::exif_extractor::format_focal_length;
+use crate::exif_extractor::format_shutter_speed;
 use crate::exif_extractor::ExifExtractor;
 use crate::gps::base::GpsResolutionProvider;
 use crate::gps::location::LocationInfo;
 
+use crate::media_type::get_media_type;
+use crate::media_type::get_megapixels;
+use crate::media_type::get_orientation_class;
+use crate::media_type::get_resolution_class;
+use crate::media_type::MediaType;
+use crate::path::duplicates_finder::get_hash_of_file_with;
+use crate::path::duplicates_finder::HashAlgorithm;
+use crate::path::filters::apply_filters;
+use crate::path::filters::get_filters;
+use crate::path::parser::parse_template;
+use crate::path::sanitizer::Sanitizer;
+use crate::path::template::PlaceholderValue;
+use crate::path::template::PlaceholderValues;
+use crate::path::template::Template;
 use crate::placeholders::Placeholder;
+use crate::quicktime::get_video_dimensions;
+use crate::timezone::get_location_timezone;
+use crate::timezone::CaptureDate;
+use crate::timezone::TimezoneTarget;
+use crate::utils::format_size_human;
 use crate::utils::is_there_a_exif_placeholder;
 use crate::utils::is_there_a_location_placeholder;
 use crate::utils::is_there_a_metadata_placeholder;
-use chrono::prelude::{DateTime, Local};
+use chrono_tz::Tz;
 use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Stands for the counter in the rendered path until every other placeholder is resolved
+pub const COUNTER_MARKER: &str = "\u{1}counter\u{1}";
+
+/// Sequences of the `%counter` placeholder per destination folder.
+/// The clones share the same sequences, so that the formatters of several rules
+/// writing to the same folder never give the same number twice.
+#[derive(Debug, Clone, Default)]
+pub struct Counters {
+    destination: Option<PathBuf>,
+    sequences: Rc<RefCell<HashMap<String, u64>>>,
+}
+
+impl Counters {
+    /// Creates counters skipping the numbers whose file already exists in the destination,
+    /// so that a second run goes on with the sequence instead of colliding with the first one.
+    pub fn new(destination: &Path) -> Self {
+        Counters {
+            destination: Some(destination.to_path_buf()),
+            sequences: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    /// Replaces the counter marker of the rendered path by the next free number of its folder.
+    ///
+    /// # Arguments
+    ///
+    /// * `formatted_path` - The rendered path, relative to the destination.
+    /// * `counter_width` - The minimum number of digits, padded with zeros.
+    /// * `sanitizer` - Makes the path valid for the destination file system.
+    ///
+    /// # Returns
+    ///
+    /// The sanitized path with the counter.
+    fn replace(&self, formatted_path: &str, counter_width: usize, sanitizer: &Sanitizer) -> String {
+        if !formatted_path.contains(COUNTER_MARKER) {
+            return sanitizer.sanitize_path(formatted_path);
+        }
+        let folder = Path::new(formatted_path)
+            .parent()
+            .map(|parent| parent.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut sequences = self.sequences.borrow_mut();
+        let counter = sequences.entry(folder).or_insert(0);
+        loop {
+            *counter += 1;
+            let counter_value = format!("{:0width$}", counter, width = counter_width);
+            let path =
+                sanitizer.sanitize_path(&formatted_path.replace(COUNTER_MARKER, &counter_value));
+            match &self.destination {
+                Some(destination) if destination.join(&path).exists() => {
+                    debug!("Skip the counter of the existing file {:?}", path);
+                }
+                _ => return path,
+            }
+        }
+    }
+}
 
 pub fn get_fallback_name(which: &str) -> String {
     format!("Unknown {}", which)
@@ -57,68 +144,165 @@ fn round_float_to_nth_decimal_place(num: f32, decimal_places: u32) -> f32 {
     (num * multiplier).round() / multiplier
 }
 
-pub struct PathFormatter<'a, 'b> {
-    path_to_format: &'a String,
-    placeholders: &'b IndexMap<String, IndexMap<String, Placeholder>>,
-    reverse_geocoding: Option<Box<dyn GpsResolutionProvider>>,
-    gps_positions: IndexMap<StringLatLon, LocationInfo>,
+/// Reverse geocoding provider along with the locations it already resolved.
+/// The clones share both, so that the formatters of every rule respect the time gap
+/// between two requests of the provider and never resolve the same position twice.
+#[derive(Clone)]
+pub struct ReverseGeocoder {
+    provider: Rc<dyn GpsResolutionProvider>,
+    locations: Rc<RefCell<IndexMap<StringLatLon, LocationInfo>>>,
+}
+
+impl ReverseGeocoder {
+    pub fn new(provider: Box<dyn GpsResolutionProvider>) -> Self {
+        ReverseGeocoder {
+            provider: Rc::from(provider),
+            locations: Rc::new(RefCell::new(IndexMap::new())),
+        }
+    }
+
+    /// Resolves the location of a position, the provider being only asked for the new positions.
+    fn get_location(&self, lat: f32, lon: f32) -> Result<LocationInfo, ClineupError> {
+        let string_lat_lon = StringLatLon(lat.to_string(), lon.to_string());
+        if let Some(location) = self.locations.borrow().get(&string_lat_lon) {
+            debug!("Get already computed location {:?}", string_lat_lon);
+            return Ok(location.clone());
+        }
+
+        let location = self.provider.get_location(lat, lon)?;
+        debug!("Store location {:?}", location);
+        self.locations
+            .borrow_mut()
+            .insert(string_lat_lon, location.clone());
+        Ok(location)
+    }
+}
+
+pub struct PathFormatter<'a> {
+    template: Template,
+    placeholders: &'a IndexMap<String, IndexMap<String, Placeholder>>,
+    reverse_geocoding: Option<ReverseGeocoder>,
     optimize_gps: bool,
+    timezone: Option<TimezoneTarget>,
+    clock_corrections: Vec<ClockCorrection>,
+    hash_algorithm: HashAlgorithm,
+    hash_length: Option<usize>,
+    counter_width: usize,
+    counters: Counters,
+    bursts: Bursts,
+    sanitizer: Sanitizer,
 }
 
-impl<'a, 'b> PathFormatter<'a, 'b> {
+impl<'a> PathFormatter<'a> {
+    /// Creates the formatter of a template, which is parsed once instead of for every file.
+    ///
+    /// # Returns
+    ///
+    /// The formatter, or an error if the template has a syntax error.
     pub fn new(
-        path_to_format: &'a String,
-        placeholders: &'b IndexMap<String, IndexMap<String, Placeholder>>,
-        reverse_geocoding: Option<Box<dyn GpsResolutionProvider>>,
+        path_to_format: &str,
+        placeholders: &'a IndexMap<String, IndexMap<String, Placeholder>>,
+        reverse_geocoding: Option<ReverseGeocoder>,
         optimize_gps: bool,
-    ) -> Self {
-        let gps_positions = IndexMap::new();
-        PathFormatter {
-            path_to_format,
+    ) -> Result<Self, ClineupError> {
+        Ok(PathFormatter {
+            template: parse_template(path_to_format)?,
             placeholders,
             reverse_geocoding,
-            gps_positions,
             optimize_gps,
+            timezone: None,
+            clock_corrections: Vec::new(),
+            hash_algorithm: HashAlgorithm::Sha256,
+            hash_length: None,
+            counter_width: 4,
+            counters: Counters::default(),
+            bursts: Bursts::default(),
+            sanitizer: Sanitizer::default(),
+        })
+    }
+
+    /// Sets the timezone in which the date placeholders are expressed.
+    /// Without it, EXIF dates are kept as recorded and file dates use the local timezone.
+    pub fn with_timezone(mut self, timezone: Option<TimezoneTarget>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Sets the corrections applied to the EXIF dates of the matching cameras.
+    pub fn with_clock_corrections(mut self, clock_corrections: Vec<ClockCorrection>) -> Self {
+        self.clock_corrections = clock_corrections;
+        self
+    }
+
+    /// Sets the algorithm of the `%hash` placeholder and the number of characters kept.
+    /// The full hash is kept if `hash_length` is `None`.
+    pub fn with_hash(mut self, hash_algorithm: HashAlgorithm, hash_length: Option<usize>) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self.hash_length = hash_length;
+        self
+    }
+
+    /// Sets the minimum number of digits of the `%counter` placeholder, which is padded with zeros.
+    pub fn with_counter_width(mut self, counter_width: usize) -> Self {
+        self.counter_width = counter_width;
+        self
+    }
+
+    /// Sets the sequences of the `%counter` placeholder, shared with the formatters of the other rules.
+    pub fn with_counters(mut self, counters: Counters) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    /// Sets the bursts detected among the files, which give the `%burst_id` placeholder.
+    pub fn with_bursts(mut self, bursts: Bursts) -> Self {
+        self.bursts = bursts;
+        self
+    }
+
+    /// Sets how the placeholder values and the formatted path are made valid for the destination file system.
+    pub fn with_sanitizer(mut self, sanitizer: Sanitizer) -> Self {
+        self.sanitizer = sanitizer;
+        self
+    }
+
+    fn get_hash(&self, path: &PathBuf) -> Result<String, ClineupError> {
+        let open_file = File::open(path)?;
+        let hash = get_hash_of_file_with(&open_file, self.hash_algorithm)?;
+        Ok(match self.hash_length {
+            Some(length) => hash.chars().take(length).collect(),
+            None => hash,
+        })
+    }
+
+    fn to_target_timezone(&self, date: CaptureDate, location_timezone: Option<Tz>) -> CaptureDate {
+        match &self.timezone {
+            Some(timezone) => date.to_timezone(timezone, location_timezone),
+            None => date,
         }
     }
 
     fn get_location_info(
-        &mut self,
+        &self,
         exif_extractor: &ExifExtractor,
     ) -> Result<LocationInfo, ClineupError> {
         let lat = exif_extractor.get_latitude()?;
         let lon = exif_extractor.get_longitude()?;
 
-        if !self.optimize_gps {
-            return self
-                .reverse_geocoding
-                .as_ref()
-                .unwrap()
-                .get_location(lat, lon);
-        }
-
-        let rounded_lat = round_float_to_nth_decimal_place(lat, 1);
-        let rounded_lon = round_float_to_nth_decimal_place(lon, 1);
-        let string_lat_lon = StringLatLon(rounded_lat.to_string(), rounded_lon.to_string());
-
-        if self.gps_positions.contains_key(&string_lat_lon) {
-            debug!("Get already computed location {:?}", string_lat_lon);
-            return Ok(self.gps_positions.get(&string_lat_lon).unwrap().clone());
-        }
+        // The rounded positions are less accurate, about 1 kilometer, but resolved much less often
+        let (lat, lon) = if self.optimize_gps {
+            (
+                round_float_to_nth_decimal_place(lat, 1),
+                round_float_to_nth_decimal_place(lon, 1),
+            )
+        } else {
+            (lat, lon)
+        };
 
-        let location = self
-            .reverse_geocoding
+        self.reverse_geocoding
             .as_ref()
             .unwrap()
-            .get_location(rounded_lat, rounded_lon);
-        match location {
-            Ok(v) => {
-                debug!("Store location {:?}", v);
-                self.gps_positions.insert(string_lat_lon, v.clone());
-                Ok(v)
-            }
-            Err(_) => Err(ClineupError::LatOrLonMissing),
-        }
+            .get_location(lat, lon)
     }
 
     fn get_file_metadata(&self, path: &PathBuf) -> Result<std::fs::Metadata, ClineupError> {
@@ -130,10 +314,37 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
     }
 }
 
-impl<'a, 'b> PathFormatter<'a, 'b> {
+impl<'a> PathFormatter<'a> {
+    /// Formats the destination of a file, relative to the destination folder.
     pub fn get_formatted_path(&mut self, path: &PathBuf) -> Result<PathBuf, ClineupError> {
-        let mut formatted_path = self.path_to_format.clone();
+        self.get_formatted_path_with_values(path)
+            .map(|(formatted_path, _)| formatted_path)
+    }
 
+    /// Formats the destination of a file, along with the values of the placeholders it was built from.
+    pub fn get_formatted_path_with_values(
+        &mut self,
+        path: &PathBuf,
+    ) -> Result<(PathBuf, PlaceholderValues), ClineupError> {
+        let values = self.get_placeholder_values(path)?;
+
+        // The counters are replaced once every other placeholder is resolved, since the sequence
+        // is specific to the destination folder. The counters of the branches which were not kept
+        // are not rendered, so not incremented
+        let formatted_path = self.counters.replace(
+            &self.template.render(&values),
+            self.counter_width,
+            &self.sanitizer,
+        );
+        Ok((Path::new(&formatted_path).to_path_buf(), values))
+    }
+
+    /// Computes the value of every placeholder chain of the template for a file.
+    /// The counters are left as a marker, since they depend on the rendered path.
+    pub fn get_placeholder_values(
+        &mut self,
+        path: &PathBuf,
+    ) -> Result<PlaceholderValues, ClineupError> {
         let file_metadata = if is_there_a_metadata_placeholder(self.placeholders) {
             Some(self.get_file_metadata(path))
         } else {
@@ -162,43 +373,81 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
             None
         };
 
+        let location_timezone = match (&self.timezone, &exif_extractor) {
+            (Some(TimezoneTarget::Gps), Some(Ok(v))) => get_location_timezone(v).ok(),
+            _ => None,
+        };
+
+        let capture_date = exif_extractor.as_ref().map(|result| match result {
+            Ok(v) => v
+                .get_corrected_capture_date(find_clock_delta(&self.clock_corrections, v))
+                .map(|date| self.to_target_timezone(date, location_timezone)),
+            Err(_) => Err(ClineupError::ExifError {
+                source: _ExifError::NotFound("No exif data found"),
+                file: path.to_string_lossy().to_string(),
+            }),
+        });
+
+        // A video has no EXIF, its dimensions are read from the header of its video track
+        let dimensions = exif_extractor.as_ref().map(|result| match result {
+            Ok(v) => v
+                .get_displayed_dimensions()
+                .or_else(|err| match get_media_type(path) {
+                    MediaType::Video => get_video_dimensions(path),
+                    _ => Err(err),
+                }),
+            Err(_) if get_media_type(path) == MediaType::Video => get_video_dimensions(path),
+            Err(_) => Err(ClineupError::ExifError {
+                source: _ExifError::NotFound("No exif data found"),
+                file: path.to_string_lossy().to_string(),
+            }),
+        });
+
+        let mut values = PlaceholderValues::new();
+
         for (full_text, placeholders) in self.placeholders {
             let mut result = String::new();
-            let mut is_fallback = false;
+            let mut result_is_fallback = false;
+            let mut chain_index = 0;
 
-            for (placeholder_text, placeholder) in placeholders {
+            for (index, (placeholder_text, placeholder)) in placeholders.iter().enumerate() {
                 debug!("Compute placeholder {:?}", full_text);
+                // Each placeholder of the chain gets its chance, whatever happened to the previous ones
+                let mut is_fallback = false;
                 let current_result = match placeholder {
                     Placeholder::Year => {
                         handle_placeholder!(
-                            exif_extractor,
+                            capture_date,
                             "Year",
                             "Year",
-                            |v: &ExifExtractor| {
-                                v.get_exif_date().map(|date| date.format("%Y").to_string())
-                            },
+                            |v: &CaptureDate| Ok::<String, ClineupError>(v.format("%Y")),
                             is_fallback
                         )
                     }
                     Placeholder::Month => {
                         handle_placeholder!(
-                            exif_extractor,
+                            capture_date,
                             "Month",
                             "Month",
-                            |v: &ExifExtractor| {
-                                v.get_exif_date().map(|date| date.format("%m").to_string())
-                            },
+                            |v: &CaptureDate| Ok::<String, ClineupError>(v.format("%m")),
                             is_fallback
                         )
                     }
                     Placeholder::Day => {
                         handle_placeholder!(
-                            exif_extractor,
+                            capture_date,
                             "Day",
                             "Day",
-                            |v: &ExifExtractor| {
-                                v.get_exif_date().map(|date| date.format("%d").to_string())
-                            },
+                            |v: &CaptureDate| Ok::<String, ClineupError>(v.format("%d")),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Timezone => {
+                        handle_placeholder!(
+                            capture_date,
+                            "Timezone",
+                            "Timezone",
+                            |v: &CaptureDate| v.timezone(),
                             is_fallback
                         )
                     }
@@ -242,13 +491,187 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                             is_fallback
                         )
                     }
+                    Placeholder::LensModel => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Lens Model",
+                            "Lens Model",
+                            |v: &ExifExtractor| v.get_lens_model(),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::FocalLength => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Focal Length",
+                            "Focal Length",
+                            |v: &ExifExtractor| { v.get_focal_length().map(format_focal_length) },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::FocalLength35mm => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Focal Length 35mm",
+                            "Focal Length 35mm",
+                            |v: &ExifExtractor| {
+                                v.get_focal_length_35mm()
+                                    .map(|focal_length| format!("{}mm", focal_length))
+                            },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Aperture => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Aperture",
+                            "Aperture",
+                            |v: &ExifExtractor| { v.get_aperture().map(format_aperture) },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::ShutterSpeed => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Shutter Speed",
+                            "Shutter Speed",
+                            |v: &ExifExtractor| v.get_exposure_time().map(format_shutter_speed),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Iso => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "ISO",
+                            "ISO",
+                            |v: &ExifExtractor| v.get_iso().map(|iso| iso.to_string()),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Flash => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Flash",
+                            "Flash",
+                            |v: &ExifExtractor| {
+                                v.get_flash_fired().map(|fired| {
+                                    if fired {
+                                        "Flash".to_string()
+                                    } else {
+                                        "No Flash".to_string()
+                                    }
+                                })
+                            },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::ExifOrientation => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Orientation",
+                            "Orientation",
+                            |v: &ExifExtractor| {
+                                v.get_orientation()
+                                    .map(|orientation| orientation.to_string())
+                            },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Software => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Software",
+                            "Software",
+                            |v: &ExifExtractor| v.get_software(),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Artist => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Artist",
+                            "Artist",
+                            |v: &ExifExtractor| v.get_artist(),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Copyright => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Copyright",
+                            "Copyright",
+                            |v: &ExifExtractor| v.get_copyright(),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::SerialNumber => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            "Serial Number",
+                            "Serial Number",
+                            |v: &ExifExtractor| v.get_serial_number(),
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Exif(tag_name) => {
+                        handle_placeholder!(
+                            exif_extractor,
+                            placeholder_text,
+                            "EXIF Tag",
+                            |v: &ExifExtractor| {
+                                v.find_tag(tag_name).and_then(|tag| v.get_string_value(tag))
+                            },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Orientation => {
+                        handle_placeholder!(
+                            dimensions,
+                            "Orientation",
+                            "Orientation",
+                            |(width, height): &(f32, f32)| {
+                                Ok::<String, ClineupError>(
+                                    get_orientation_class(*width, *height).to_string(),
+                                )
+                            },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::Megapixels => {
+                        handle_placeholder!(
+                            dimensions,
+                            "Megapixels",
+                            "Megapixels",
+                            |(width, height): &(f32, f32)| {
+                                Ok::<String, ClineupError>(
+                                    get_megapixels(*width, *height).to_string(),
+                                )
+                            },
+                            is_fallback
+                        )
+                    }
+                    Placeholder::ResolutionClass => {
+                        handle_placeholder!(
+                            dimensions,
+                            "Resolution Class",
+                            "Resolution Class",
+                            |(width, height): &(f32, f32)| {
+                                Ok::<String, ClineupError>(
+                                    get_resolution_class(*width, *height).to_string(),
+                                )
+                            },
+                            is_fallback
+                        )
+                    }
                     Placeholder::CTimeYear => handle_placeholder!(
                         file_metadata.as_ref(),
                         "CTimeYear",
                         "Creation Time Year",
                         |v: &std::fs::Metadata| {
-                            v.created()
-                                .map(|date| DateTime::<Local>::from(date).format("%Y").to_string())
+                            v.created().map(|date| {
+                                self.to_target_timezone(CaptureDate::from(date), location_timezone)
+                                    .format("%Y")
+                            })
                         },
                         is_fallback
                     ),
@@ -257,9 +680,10 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                         "CTimeMonth",
                         "Creation Time Month",
                         |v: &std::fs::Metadata| {
-                            v.clone()
-                                .created()
-                                .map(|date| DateTime::<Local>::from(date).format("%m").to_string())
+                            v.created().map(|date| {
+                                self.to_target_timezone(CaptureDate::from(date), location_timezone)
+                                    .format("%m")
+                            })
                         },
                         is_fallback
                     ),
@@ -268,9 +692,10 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                         "CTimeDay",
                         "Creation Time Day",
                         |v: &std::fs::Metadata| {
-                            v.clone()
-                                .created()
-                                .map(|date| DateTime::<Local>::from(date).format("%d").to_string())
+                            v.created().map(|date| {
+                                self.to_target_timezone(CaptureDate::from(date), location_timezone)
+                                    .format("%d")
+                            })
                         },
                         is_fallback
                     ),
@@ -279,8 +704,10 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                         "MTimeYear",
                         "Modification Time Year",
                         |v: &std::fs::Metadata| {
-                            v.modified()
-                                .map(|date| DateTime::<Local>::from(date).format("%Y").to_string())
+                            v.modified().map(|date| {
+                                self.to_target_timezone(CaptureDate::from(date), location_timezone)
+                                    .format("%Y")
+                            })
                         },
                         is_fallback
                     ),
@@ -289,8 +716,10 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                         "MTimeMonth",
                         "Modification Time Month",
                         |v: &std::fs::Metadata| {
-                            v.modified()
-                                .map(|date| DateTime::<Local>::from(date).format("%m").to_string())
+                            v.modified().map(|date| {
+                                self.to_target_timezone(CaptureDate::from(date), location_timezone)
+                                    .format("%m")
+                            })
                         },
                         is_fallback
                     ),
@@ -299,8 +728,10 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                         "MTimeDay",
                         "Modification Time Day",
                         |v: &std::fs::Metadata| {
-                            v.modified()
-                                .map(|date| DateTime::<Local>::from(date).format("%d").to_string())
+                            v.modified().map(|date| {
+                                self.to_target_timezone(CaptureDate::from(date), location_timezone)
+                                    .format("%d")
+                            })
                         },
                         is_fallback
                     ),
@@ -359,6 +790,64 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                         },
                         is_fallback
                     ),
+                    Placeholder::MediaType => get_media_type(path).as_str().to_string(),
+                    Placeholder::HasGps => match &exif_extractor {
+                        Some(Ok(v)) => v.get_latitude().is_ok().to_string(),
+                        _ => false.to_string(),
+                    },
+                    Placeholder::Hash => self.get_hash(path).unwrap_or_else(|err| {
+                        warn!("{}", err);
+                        is_fallback = true;
+                        get_fallback_name("Hash")
+                    }),
+                    Placeholder::Size => handle_placeholder!(
+                        file_metadata.as_ref(),
+                        "Size",
+                        "Size",
+                        |v: &std::fs::Metadata| Ok::<String, ClineupError>(v.len().to_string()),
+                        is_fallback
+                    ),
+                    Placeholder::SizeHuman => handle_placeholder!(
+                        file_metadata.as_ref(),
+                        "SizeHuman",
+                        "Size",
+                        |v: &std::fs::Metadata| Ok::<String, ClineupError>(format_size_human(
+                            v.len()
+                        )),
+                        is_fallback
+                    ),
+                    Placeholder::Extension => path.extension().map_or_else(
+                        || {
+                            is_fallback = true;
+                            get_fallback_name("Extension")
+                        },
+                        |extension| extension.to_string_lossy().to_string(),
+                    ),
+                    Placeholder::ExtLower => path.extension().map_or_else(
+                        || {
+                            is_fallback = true;
+                            get_fallback_name("Extension")
+                        },
+                        |extension| extension.to_string_lossy().to_lowercase(),
+                    ),
+                    Placeholder::OriginalStem => path.file_stem().map_or_else(
+                        || {
+                            is_fallback = true;
+                            get_fallback_name("Original Stem")
+                        },
+                        |file_stem| file_stem.to_string_lossy().to_string(),
+                    ),
+                    Placeholder::Counter => {
+                        // The counter is replaced at the end
+                        COUNTER_MARKER.to_string()
+                    }
+                    Placeholder::BurstId => self.bursts.get_burst_id(path).map_or_else(
+                        || {
+                            is_fallback = true;
+                            get_fallback_name("Burst")
+                        },
+                        |burst_id| burst_id.clone(),
+                    ),
                     Placeholder::OriginalFilename => path.file_name().map_or_else(
                         || {
                             is_fallback = true;
@@ -379,13 +868,26 @@ impl<'a, 'b> PathFormatter<'a, 'b> {
                     Placeholder::Unknown => placeholder_text.clone(),
                 };
 
-                result = current_result.clone();
+                result = match placeholder {
+                    Placeholder::Counter | Placeholder::Fallback | Placeholder::Unknown => {
+                        current_result
+                    }
+                    _ => self.sanitizer.sanitize_value(&apply_filters(
+                        current_result,
+                        &get_filters(placeholder_text)?,
+                    )),
+                };
+                result_is_fallback = is_fallback;
+                chain_index = index;
                 if !is_fallback {
                     break;
                 }
             }
-            formatted_path = formatted_path.replace(full_text, result.as_str());
+            values.insert(
+                full_text.clone(),
+                PlaceholderValue::new(result, result_is_fallback).with_chain_index(chain_index),
+            );
         }
-        Ok(Path::new(&formatted_path).to_path_buf())
+        Ok(values)
     }
 }
@@ -0,0 +1,588 @@
+use clap::ArgMatches;
+use log::{info, warn};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashSet;
+use std::fs::File;
+use std::fs::Metadata;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::UNIX_EPOCH;
+
+#[cfg(target_family = "unix")]
+use std::os::unix::fs::MetadataExt;
+
+use crate::errors::ClineupError;
+use crate::path::duplicates_finder::get_hash_of_file_with;
+use crate::path::duplicates_finder::list_library_files;
+use crate::path::duplicates_finder::HashAlgorithm;
+use crate::utils::print_error;
+
+/// Name of the index in the library, its journal files starting with the same name
+pub const INDEX_FILE_NAME: &str = ".clineup-index.sqlite";
+
+/// Number of files written to the index before they are committed together
+const FILES_PER_TRANSACTION: usize = 1000;
+
+/// Whether the file is the hash index or one of its journal files, which are never organized.
+pub fn is_hash_index_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with(INDEX_FILE_NAME))
+}
+
+/// Files are indexed by canonical path, as listed by `list_library_files`
+fn get_index_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.to_path_buf())
+}
+
+/// What identifies a version of a file, without reading it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileStamp {
+    size: i64,
+    mtime: i64,
+    inode: i64,
+}
+
+impl FileStamp {
+    fn from_metadata(metadata: &Metadata) -> FileStamp {
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_nanos() as i64);
+        FileStamp {
+            size: metadata.len() as i64,
+            mtime,
+            inode: get_inode(metadata),
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn get_inode(metadata: &Metadata) -> i64 {
+    metadata.ino() as i64
+}
+
+#[cfg(target_family = "windows")]
+fn get_inode(_metadata: &Metadata) -> i64 {
+    0
+}
+
+/// Result of `clineup index verify`
+#[derive(Debug, Default, PartialEq)]
+pub struct Verification {
+    pub verified: usize,
+    /// Unchanged size and modification time, but a different content
+    pub corrupted: Vec<PathBuf>,
+    /// Modified since they were indexed
+    pub modified: Vec<PathBuf>,
+    pub missing: Vec<PathBuf>,
+    pub not_indexed: Vec<PathBuf>,
+}
+
+impl Verification {
+    pub fn report(&self) -> String {
+        let mut report = format!("{} file(s) verified\n", self.verified);
+        for (title, paths) in [
+            ("Corrupted", &self.corrupted),
+            ("Modified", &self.modified),
+            ("Missing", &self.missing),
+            ("Not indexed", &self.not_indexed),
+        ] {
+            report.push_str(&format!("{}: {}\n", title, paths.len()));
+            for path in paths {
+                report.push_str(&format!("    {}\n", path.display()));
+            }
+        }
+        report
+    }
+}
+
+/// Hashes of the files of a library, kept between runs so that only the modified files are hashed.
+/// A file may be indexed with several algorithms, each run reading and writing the hashes of its own.
+pub struct HashIndex {
+    connection: Connection,
+    algorithm: HashAlgorithm,
+    /// Files written since the last commit, which are committed together
+    pending_files: usize,
+}
+
+impl HashIndex {
+    /// Opens the index of a library, creating it if needed.
+    /// The SHA-256 hashes of an index created by a previous version are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `library` - The folder of the library the index describes.
+    ///
+    /// # Returns
+    ///
+    /// The index, or an error if it can not be opened.
+    pub fn open(library: &Path) -> Result<HashIndex, ClineupError> {
+        std::fs::create_dir_all(library)?;
+        let connection = Connection::open(library.join(INDEX_FILE_NAME))?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS hashes (
+                path TEXT NOT NULL,
+                algorithm TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                mtime INTEGER NOT NULL,
+                inode INTEGER NOT NULL,
+                hash TEXT NOT NULL,
+                PRIMARY KEY (path, algorithm)
+            )",
+        )?;
+        let has_previous_version = connection
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'files'",
+                [],
+                |_| Ok(()),
+            )
+            .optional()?
+            .is_some();
+        if has_previous_version {
+            connection.execute_batch(
+                "BEGIN;
+                INSERT OR IGNORE INTO hashes (path, algorithm, size, mtime, inode, hash)
+                    SELECT path, 'sha256', size, mtime, inode, sha256 FROM files;
+                DROP TABLE files;
+                COMMIT;",
+            )?;
+        }
+        Ok(HashIndex {
+            connection,
+            algorithm: HashAlgorithm::Sha256,
+            pending_files: 0,
+        })
+    }
+
+    /// Reads and writes the hashes of another algorithm than SHA-256, like the one used to find the duplicates.
+    pub fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    fn get_entry(&self, path: &Path) -> Result<Option<(FileStamp, String)>, ClineupError> {
+        let path = get_index_key(path);
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT size, mtime, inode, hash FROM hashes WHERE path = ?1 AND algorithm = ?2",
+                params![path.to_string_lossy(), self.algorithm.name()],
+                |row| {
+                    Ok((
+                        FileStamp {
+                            size: row.get(0)?,
+                            mtime: row.get(1)?,
+                            inode: row.get(2)?,
+                        },
+                        row.get(3)?,
+                    ))
+                },
+            )
+            .optional()?)
+    }
+
+    /// Writes a file in the pending transaction, which is committed every `FILES_PER_TRANSACTION` files.
+    fn set_entry(&mut self, path: &Path, stamp: FileStamp, hash: &str) -> Result<(), ClineupError> {
+        let path = get_index_key(path);
+        if self.pending_files == 0 {
+            self.connection.execute_batch("BEGIN")?;
+        }
+        self.connection.execute(
+            "INSERT OR REPLACE INTO hashes (path, algorithm, size, mtime, inode, hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                path.to_string_lossy(),
+                self.algorithm.name(),
+                stamp.size,
+                stamp.mtime,
+                stamp.inode,
+                hash
+            ],
+        )?;
+        self.pending_files += 1;
+        if self.pending_files >= FILES_PER_TRANSACTION {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commits the files written since the last commit. It is also done when the index is dropped.
+    pub fn commit(&mut self) -> Result<(), ClineupError> {
+        if self.pending_files > 0 {
+            self.pending_files = 0;
+            self.connection.execute_batch("COMMIT")?;
+        }
+        Ok(())
+    }
+
+    fn hash_file(path: &Path, algorithm: HashAlgorithm) -> Result<String, ClineupError> {
+        get_hash_of_file_with(&File::open(path)?, algorithm)
+    }
+
+    /// Gets the indexed hash of a file without updating the index, if it is still valid.
+    /// Used for the files outside the library, which must not be indexed.
+    pub fn get_indexed_hash(&self, path: &Path) -> Result<Option<String>, ClineupError> {
+        let stamp = FileStamp::from_metadata(&std::fs::metadata(path)?);
+        Ok(self
+            .get_entry(path)?
+            .filter(|(indexed_stamp, _)| *indexed_stamp == stamp)
+            .map(|(_, hash)| hash))
+    }
+
+    /// Gets the hash of a file, which is only computed when its size, modification time or inode changed.
+    pub fn get_hash(&mut self, path: &Path) -> Result<String, ClineupError> {
+        if let Some(hash) = self.get_indexed_hash(path)? {
+            return Ok(hash);
+        }
+
+        let stamp = FileStamp::from_metadata(&std::fs::metadata(path)?);
+        let hash = Self::hash_file(path, self.algorithm)?;
+        self.set_entry(path, stamp, &hash)?;
+        Ok(hash)
+    }
+
+    /// Records the hash of a file which is already known, like the one of the source of a copy.
+    pub fn insert(&mut self, path: &Path, hash: &str) -> Result<(), ClineupError> {
+        let stamp = FileStamp::from_metadata(&std::fs::metadata(path)?);
+        self.set_entry(path, stamp, hash)
+    }
+
+    /// Records a file written to the library, hashing it only when its hash is not known yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file written to the library.
+    /// * `known_hash` - The hash of its source with the algorithm of the index, if it was computed.
+    pub fn record(&mut self, path: &Path, known_hash: Option<String>) -> Result<(), ClineupError> {
+        match known_hash {
+            Some(hash) => self.insert(path, &hash),
+            None => self.get_hash(path).map(|_| ()),
+        }
+    }
+
+    /// Hashes every file of the library again, forgetting the files which are gone and the other algorithms.
+    ///
+    /// # Returns
+    ///
+    /// The number of files indexed.
+    pub fn rebuild(&mut self, library: &Path) -> Result<usize, ClineupError> {
+        self.commit()?;
+        let library_files = list_library_files(library)?;
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM hashes", [])?;
+        for (path, _) in &library_files {
+            let stamp = FileStamp::from_metadata(&std::fs::metadata(path)?);
+            let hash = Self::hash_file(path, self.algorithm)?;
+            transaction.execute(
+                "INSERT INTO hashes (path, algorithm, size, mtime, inode, hash) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    path.to_string_lossy(),
+                    self.algorithm.name(),
+                    stamp.size,
+                    stamp.mtime,
+                    stamp.inode,
+                    hash
+                ],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(library_files.len())
+    }
+
+    /// Hashes every indexed file again with its algorithm to detect bit rot, without updating the index.
+    /// A file which can not be read anymore is reported as missing.
+    pub fn verify(&mut self, library: &Path) -> Result<Verification, ClineupError> {
+        self.commit()?;
+        let mut verification = Verification::default();
+
+        let mut statement = self.connection.prepare(
+            "SELECT path, algorithm, size, mtime, inode, hash FROM hashes ORDER BY path, algorithm",
+        )?;
+        let entries = statement
+            .query_map([], |row| {
+                Ok((
+                    PathBuf::from(row.get::<_, String>(0)?),
+                    row.get::<_, String>(1)?,
+                    FileStamp {
+                        size: row.get(2)?,
+                        mtime: row.get(3)?,
+                        inode: row.get(4)?,
+                    },
+                    row.get::<_, String>(5)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (path, algorithm, indexed_stamp, indexed_hash) in &entries {
+            let Ok(metadata) = std::fs::metadata(path) else {
+                verification.missing.push(path.clone());
+                continue;
+            };
+            if FileStamp::from_metadata(&metadata) != *indexed_stamp {
+                verification.modified.push(path.clone());
+                continue;
+            }
+            let hash = HashAlgorithm::from_string(algorithm)
+                .and_then(|algorithm| Self::hash_file(path, algorithm));
+            match hash {
+                Ok(hash) if hash == *indexed_hash => verification.verified += 1,
+                Ok(_) => verification.corrupted.push(path.clone()),
+                Err(err) => {
+                    warn!("Can not hash {:?}: {}", path, err);
+                    verification.missing.push(path.clone());
+                }
+            }
+        }
+
+        let indexed_paths: HashSet<&PathBuf> = entries.iter().map(|(path, _, _, _)| path).collect();
+        for (path, _) in list_library_files(library)? {
+            if !indexed_paths.contains(&path) {
+                verification.not_indexed.push(path);
+            }
+        }
+        Ok(verification)
+    }
+}
+
+impl Drop for HashIndex {
+    fn drop(&mut self) {
+        if let Err(err) = self.commit() {
+            warn!("Can not write the hash index: {}", err);
+        }
+    }
+}
+
+/// Runs `clineup index rebuild` or `clineup index verify`, then exits.
+/// The verification exits with an error when a file is corrupted.
+pub fn run_index_command(matches: &ArgMatches) -> ! {
+    let (command, command_matches) = matches.subcommand();
+    let command_matches = command_matches.unwrap();
+    let library = Path::new(command_matches.value_of("destination").unwrap());
+    let mut hash_index = HashIndex::open(library).unwrap_or_else(print_error);
+
+    match command {
+        "rebuild" => {
+            let algorithm =
+                HashAlgorithm::from_string(command_matches.value_of("hash-algorithm").unwrap())
+                    .unwrap_or_else(print_error);
+            hash_index = hash_index.with_hash_algorithm(algorithm);
+            let indexed_files = hash_index.rebuild(library).unwrap_or_else(print_error);
+            println!("{} file(s) indexed", indexed_files);
+            exit(0)
+        }
+        "verify" => {
+            let verification = hash_index.verify(library).unwrap_or_else(print_error);
+            print!("{}", verification.report());
+            info!("Index {:?} verified", library.join(INDEX_FILE_NAME));
+            exit(if verification.corrupted.is_empty() {
+                0
+            } else {
+                1
+            })
+        }
+        _ => unreachable!("clap requires an index subcommand"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    /// Changes the content of a file like bit rot does, keeping its size and modification time
+    fn corrupt(path: &Path) {
+        let modified = std::fs::metadata(path).unwrap().modified().unwrap();
+        let mut content = std::fs::read(path).unwrap();
+        content[0] ^= 0xff;
+        std::fs::write(path, content).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_hash() {
+        let library = TempDir::new("library").unwrap();
+        let photo = library.path().join("photo.jpg");
+        std::fs::write(&photo, "photo").unwrap();
+
+        let mut hash_index = HashIndex::open(library.path()).unwrap();
+        let hash = hash_index.get_hash(&photo).unwrap();
+        assert_eq!(
+            hash,
+            get_hash_of_file_with(&File::open(&photo).unwrap(), HashAlgorithm::Sha256).unwrap()
+        );
+
+        // Not hashed again while its size and modification time are unchanged
+        corrupt(&photo);
+        assert_eq!(hash_index.get_hash(&photo).unwrap(), hash);
+
+        std::fs::write(&photo, "another photo").unwrap();
+        assert_ne!(hash_index.get_hash(&photo).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_get_hash_by_algorithm() {
+        let library = TempDir::new("library").unwrap();
+        let photo = library.path().join("photo.jpg");
+        std::fs::write(&photo, "photo").unwrap();
+
+        let sha256 = HashIndex::open(library.path())
+            .unwrap()
+            .get_hash(&photo)
+            .unwrap();
+        let mut hash_index = HashIndex::open(library.path())
+            .unwrap()
+            .with_hash_algorithm(HashAlgorithm::Xxh3);
+        assert_eq!(hash_index.get_indexed_hash(&photo).unwrap(), None);
+        let xxh3 = hash_index.get_hash(&photo).unwrap();
+        assert_eq!(
+            xxh3,
+            get_hash_of_file_with(&File::open(&photo).unwrap(), HashAlgorithm::Xxh3).unwrap()
+        );
+        drop(hash_index);
+
+        // Each algorithm keeps its own hash of the file
+        let hash_index = HashIndex::open(library.path()).unwrap();
+        assert_eq!(hash_index.get_indexed_hash(&photo).unwrap(), Some(sha256));
+    }
+
+    #[test]
+    fn test_open_previous_version() {
+        let library = TempDir::new("library").unwrap();
+        let photo = library.path().join("photo.jpg");
+        std::fs::write(&photo, "photo").unwrap();
+        let stamp = FileStamp::from_metadata(&std::fs::metadata(&photo).unwrap());
+        let connection = Connection::open(library.path().join(INDEX_FILE_NAME)).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE files (
+                    path TEXT PRIMARY KEY,
+                    size INTEGER NOT NULL,
+                    mtime INTEGER NOT NULL,
+                    inode INTEGER NOT NULL,
+                    sha256 TEXT NOT NULL
+                )",
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO files VALUES (?1, ?2, ?3, ?4, 'indexed')",
+                params![
+                    get_index_key(&photo).to_string_lossy(),
+                    stamp.size,
+                    stamp.mtime,
+                    stamp.inode
+                ],
+            )
+            .unwrap();
+        drop(connection);
+
+        let hash_index = HashIndex::open(library.path()).unwrap();
+        assert_eq!(
+            hash_index.get_indexed_hash(&photo).unwrap(),
+            Some("indexed".to_string())
+        );
+    }
+
+    #[test]
+    fn test_record_in_one_transaction() {
+        let library = TempDir::new("library").unwrap();
+        let mut hash_index = HashIndex::open(library.path()).unwrap();
+        for index in 0..FILES_PER_TRANSACTION + 1 {
+            let photo = library.path().join(format!("{}.jpg", index));
+            std::fs::write(&photo, index.to_string()).unwrap();
+            hash_index.record(&photo, None).unwrap();
+        }
+        // The first files are committed together, the last one when the index is dropped
+        assert_eq!(hash_index.pending_files, 1);
+        drop(hash_index);
+
+        let verification = HashIndex::open(library.path())
+            .unwrap()
+            .verify(library.path())
+            .unwrap();
+        assert_eq!(verification.verified, FILES_PER_TRANSACTION + 1);
+        assert!(verification.not_indexed.is_empty());
+    }
+
+    #[test]
+    fn test_verify_unreadable_files() {
+        let library = TempDir::new("library").unwrap();
+        for name in ["deleted.jpg", "locked.jpg", "ok.jpg"] {
+            std::fs::write(library.path().join(name), name).unwrap();
+        }
+        let mut hash_index = HashIndex::open(library.path()).unwrap();
+        assert_eq!(hash_index.rebuild(library.path()).unwrap(), 3);
+
+        let locked = library.path().join("locked.jpg").canonicalize().unwrap();
+        std::fs::remove_file(library.path().join("deleted.jpg")).unwrap();
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        }
+        // The superuser still reads a file without permissions
+        let is_locked = File::open(&locked).is_err();
+
+        // The verification goes on after a file which can not be read
+        let verification = hash_index.verify(library.path()).unwrap();
+        let deleted = library.path().canonicalize().unwrap().join("deleted.jpg");
+        if is_locked {
+            assert_eq!(verification.missing, vec![deleted, locked]);
+            assert_eq!(verification.verified, 1);
+        } else {
+            assert_eq!(verification.missing, vec![deleted]);
+            assert_eq!(verification.verified, 2);
+        }
+        assert!(verification.corrupted.is_empty());
+    }
+
+    #[test]
+    fn test_rebuild_and_verify() {
+        let library = TempDir::new("library").unwrap();
+        std::fs::create_dir_all(library.path().join("2023")).unwrap();
+        for name in [
+            "2023/corrupted.jpg",
+            "2023/modified.jpg",
+            "missing.jpg",
+            "ok.jpg",
+        ] {
+            std::fs::write(library.path().join(name), name).unwrap();
+        }
+
+        let mut hash_index = HashIndex::open(library.path()).unwrap();
+        assert_eq!(hash_index.rebuild(library.path()).unwrap(), 4);
+        assert_eq!(hash_index.verify(library.path()).unwrap().verified, 4);
+
+        corrupt(&library.path().join("2023/corrupted.jpg"));
+        std::fs::write(library.path().join("2023/modified.jpg"), "modified").unwrap();
+        std::fs::remove_file(library.path().join("missing.jpg")).unwrap();
+        std::fs::write(library.path().join("new.jpg"), "new").unwrap();
+
+        let canonical = |name: &str| library.path().join(name).canonicalize().unwrap();
+        let missing = library.path().canonicalize().unwrap().join("missing.jpg");
+        assert_eq!(
+            hash_index.verify(library.path()).unwrap(),
+            Verification {
+                verified: 1,
+                corrupted: vec![canonical("2023/corrupted.jpg")],
+                modified: vec![canonical("2023/modified.jpg")],
+                missing: vec![missing],
+                not_indexed: vec![canonical("new.jpg")],
+            }
+        );
+
+        assert_eq!(hash_index.rebuild(library.path()).unwrap(), 4);
+        assert!(hash_index
+            .verify(library.path())
+            .unwrap()
+            .corrupted
+            .is_empty());
+    }
+}
@@ -1,11 +1,23 @@
 use crate::cli::Config;
 use crate::errors::ClineupError;
-use glob::glob;
+use crate::path::companions::get_companion_finder;
+use crate::path::companions::CompanionFinder;
+use crate::path::hash_index::is_hash_index_file;
+use ignore::overrides::Override;
+use ignore::overrides::OverrideBuilder;
+use ignore::DirEntry;
+use ignore::WalkBuilder;
 use log::debug;
 
 use log::warn;
 
+use std::collections::HashSet;
+#[cfg(target_family = "unix")]
+use std::ffi::OsStr;
 use std::fs;
+use std::io::Read;
+#[cfg(target_family = "unix")]
+use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -87,46 +99,274 @@ pub fn is_allowed_size(
     Ok(true)
 }
 
-/// Generates a glob pattern based on the given source path and recursion flag.
-/// If recursion is enabled, append "**/*" to the source path.
-/// Otherwise, append "*".
+/// Name of the files listing the paths to skip, with the gitignore syntax
+pub const IGNORE_FILE_NAME: &str = ".clineupignore";
+
+/// Builds the glob patterns excluded from the walk, with the gitignore syntax.
+///
+/// # Arguments
+///
+/// * `source` - The source directory, the patterns being relative to it.
+/// * `exclude_globs` - The patterns, like `*.tmp` or `Thumbnails/`.
+///
+/// # Returns
+///
+/// The patterns, or an error if one of them is invalid.
+pub fn get_exclude_globs(source: &str, exclude_globs: &[String]) -> Result<Override, ClineupError> {
+    let mut builder = OverrideBuilder::new(source);
+    for exclude_glob in exclude_globs {
+        // A negated override excludes what it matches
+        builder.add(&format!("!{}", exclude_glob))?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Parses a list of files, like the output of `find` or `find -print0`.
+/// The paths are separated by NUL characters if there is one, by new lines otherwise.
+/// The names are bytes, since they are not always valid UTF-8.
+///
+/// # Arguments
+///
+/// * `content` - The list of files.
+///
+/// # Returns
+///
+/// The files, without the empty lines.
+pub fn parse_file_list<C: AsRef<[u8]> + ?Sized>(content: &C) -> Vec<PathBuf> {
+    let content = content.as_ref();
+    let separator = if content.contains(&b'\0') {
+        b'\0'
+    } else {
+        b'\n'
+    };
+    content
+        .split(|byte| *byte == separator)
+        .map(|path| path.strip_suffix(b"\r").unwrap_or(path))
+        .filter(|path| !path.is_empty())
+        .map(get_path_from_bytes)
+        .collect()
+}
+
+/// Keeps the bytes of a listed name as they are, which is a valid path on Unix even if it is not UTF-8.
+#[cfg(target_family = "unix")]
+fn get_path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+/// The paths are UTF-16 on Windows, so that the invalid UTF-8 sequences of a listed name are replaced.
+#[cfg(target_family = "windows")]
+fn get_path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Reads the list of files to organize from a file, or from the standard input for `-`.
 ///
 /// # Arguments
 ///
-/// * `source` - The source path to generate the glob pattern for.
-/// * `recursive` - A flag indicating whether the pattern should be recursive or not.
+/// * `files_from` - The path to the list of files, or `-`.
 ///
 /// # Returns
 ///
-/// The generated glob pattern as a `String`.
-fn get_glob_pattern(source: &str, recursive: &bool) -> PathBuf {
-    let mut source_path = Path::new(source).to_path_buf();
-    if *recursive {
-        source_path = source_path.join("**/*");
+/// The files, or an error if the list can't be read.
+pub fn read_file_list(files_from: &str) -> Result<Vec<PathBuf>, ClineupError> {
+    let mut content = Vec::new();
+    if files_from == "-" {
+        std::io::stdin().read_to_end(&mut content)?;
     } else {
-        source_path = source_path.join("*");
+        fs::File::open(files_from)?.read_to_end(&mut content)?;
+    }
+    Ok(parse_file_list(&content))
+}
+
+type Entries = Box<dyn Iterator<Item = Result<DirEntry, ignore::Error>>>;
+
+/// Builds the walk of a source directory.
+/// The directories matching the excluded patterns or a `.clineupignore` file are pruned
+/// before descending into them, and the entries are sorted by name.
+/// A source file is walked on its own.
+///
+/// # Arguments
+///
+/// * `source` - The source directory or file.
+/// * `exclude_globs` - The excluded patterns, relative to the source.
+/// * `config` - The configuration with the walk options.
+///
+/// # Returns
+///
+/// The walk, yielding the directories as well as the files.
+fn get_walk(source: &str, exclude_globs: &Override, config: &Config) -> Entries {
+    // Without recursion, only the files of the source directory are walked
+    let max_depth = if config.recursive {
+        config.max_depth
+    } else {
+        Some(1)
+    };
+
+    Box::new(
+        WalkBuilder::new(source)
+            .standard_filters(false)
+            .hidden(config.skip_hidden)
+            .follow_links(config.follow_symlinks)
+            .same_file_system(config.one_file_system)
+            .max_depth(max_depth)
+            .add_custom_ignore_filename(IGNORE_FILE_NAME)
+            .overrides(exclude_globs.clone())
+            .sort_by_file_name(|left, right| left.cmp(right))
+            .build(),
+    )
+}
+
+/// Builds the walk of the listed files, in the order of the list.
+/// The listed directories are not walked through.
+///
+/// # Arguments
+///
+/// * `files` - The listed files.
+///
+/// # Returns
+///
+/// The walk of the files, or `None` if there is no file.
+fn get_listed_files_walk(files: &[PathBuf]) -> Option<Entries> {
+    let (first, others) = files.split_first()?;
+    let mut builder = WalkBuilder::new(first);
+    for file in others {
+        builder.add(file);
+    }
+    Some(Box::new(
+        builder.standard_filters(false).max_depth(Some(0)).build(),
+    ))
+}
+
+/// Checks if the file matches the regexes, extensions and sizes of the configuration.
+///
+/// # Arguments
+///
+/// * `entry` - The path to the file.
+/// * `config` - The configuration with the filters.
+///
+/// # Returns
+///
+/// `true` if the file is selected, `false` otherwise.
+pub fn is_selected(entry: &PathBuf, config: &Config) -> bool {
+    if let Some(include_regex) = &config.include_regex {
+        if !include_regex.is_match(&entry.to_string_lossy()) {
+            return false;
+        }
     }
 
-    source_path
+    if let Some(exclude) = &config.exclude_regex {
+        if exclude.is_match(&entry.to_string_lossy()) {
+            return false;
+        }
+    }
+
+    if !is_allowed_extension(entry, &config.extensions, &config.exclude_extensions) {
+        return false;
+    }
+
+    let _is_allowed_size = is_allowed_size(entry, &config.size_lower, &config.size_greater);
+
+    match _is_allowed_size {
+        Ok(allowed) => allowed,
+        Err(err) => {
+            warn!("Unable to check file size: {:?}", err);
+            false
+        }
+    }
+}
+
+/// Checks if the file is excluded by the exclude regex or the excluded extensions.
+/// The companions, which follow their primary file, are only filtered by the exclusions.
+///
+/// # Arguments
+///
+/// * `entry` - The path to the file.
+/// * `config` - The configuration with the filters.
+///
+/// # Returns
+///
+/// `true` if the file is excluded, `false` otherwise.
+pub fn is_excluded(entry: &Path, config: &Config) -> bool {
+    if config
+        .exclude_globs
+        .iter()
+        .any(|exclude_globs| exclude_globs.matched(entry, false).is_ignore())
+    {
+        return true;
+    }
+
+    if let Some(exclude) = &config.exclude_regex {
+        if exclude.is_match(&entry.to_string_lossy()) {
+            return true;
+        }
+    }
+
+    let extension = entry
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match (extension, &config.exclude_extensions) {
+        (Some(extension), Some(exclude_extensions)) => exclude_extensions.contains(&extension),
+        _ => false,
+    }
 }
 
 pub struct FileIterator<'a> {
-    entries: glob::Paths,
+    entries: Entries,
     config: &'a Config,
+    companion_finder: Option<CompanionFinder>,
+    explicit_files: HashSet<PathBuf>,
+    seen_files: HashSet<PathBuf>,
 }
 
 impl<'a> FileIterator<'a> {
     pub fn new(config: &'a Config) -> Self {
-        let source_path = get_glob_pattern(&config.source, &config.recursive);
+        // The sources are walked one after the other, then the listed files
+        let walks: Vec<Entries> = config
+            .sources
+            .iter()
+            .zip(&config.exclude_globs)
+            .map(|(source, exclude_globs)| get_walk(source, exclude_globs, config))
+            .chain(get_listed_files_walk(&config.listed_files))
+            .collect();
+        let entries = Box::new(walks.into_iter().flatten());
 
-        let entries = glob(&source_path.to_string_lossy()).unwrap_or_else(|_| {
-            panic!(
-                "Failed to iterate through source pattern {}",
-                source_path.display()
-            )
-        });
+        // The companions are organized along with their primary file, not on their own
+        let companion_finder = get_companion_finder(config);
 
-        FileIterator { entries, config }
+        let explicit_files = config
+            .sources
+            .iter()
+            .map(PathBuf::from)
+            .filter(|source| source.is_file())
+            .chain(config.listed_files.iter().cloned())
+            .collect();
+
+        FileIterator {
+            entries,
+            config,
+            companion_finder,
+            explicit_files,
+            seen_files: HashSet::new(),
+        }
+    }
+
+    /// Whether the file follows a primary file which is organized.
+    /// A file given on its own only follows a primary file given as well.
+    fn is_companion(&mut self, entry: &PathBuf, is_explicit: bool) -> bool {
+        let primary = self
+            .companion_finder
+            .as_mut()
+            .and_then(|companion_finder| companion_finder.get_primary(entry));
+        match primary {
+            Some(primary)
+                if is_selected(&primary, self.config)
+                    && (!is_explicit || self.explicit_files.contains(&primary)) =>
+            {
+                debug!("{:?} follows {:?}", entry, primary);
+                true
+            }
+            _ => false,
+        }
     }
 }
 
@@ -134,53 +374,25 @@ impl<'a> Iterator for FileIterator<'a> {
     type Item = PathBuf;
 
     fn next(&mut self) -> Option<Self::Item> {
-        for entry in self.entries.by_ref() {
+        while let Some(entry) = self.entries.next() {
             match entry {
                 Err(err) => {
-                    warn!("Unable to get entry: {:?}", err);
+                    warn!("Unable to get entry: {}", err);
                     continue;
                 }
                 Ok(entry) => {
-                    // Check if the entry is a file
-                    if entry.is_file() {
-                        if let Some(include_regex) = &self.config.include_regex {
-                            if !include_regex.is_match(&entry.to_string_lossy()) {
-                                continue;
-                            }
-                        }
-
-                        if let Some(exclude) = &self.config.exclude_regex {
-                            if exclude.is_match(&entry.to_string_lossy()) {
-                                continue;
-                            }
-                        }
-
-                        if !is_allowed_extension(
-                            &entry,
-                            &self.config.extensions,
-                            &self.config.exclude_extensions,
-                        ) {
-                            continue;
-                        }
-
-                        let _is_allowed_size = is_allowed_size(
-                            &entry,
-                            &self.config.size_lower,
-                            &self.config.size_greater,
-                        );
-
-                        match _is_allowed_size {
-                            Ok(allowed) => {
-                                if !allowed {
-                                    continue;
-                                }
-                            }
-                            Err(err) => {
-                                warn!("Unable to check file size: {:?}", err);
-                                continue;
-                            }
-                        }
-
+                    // The sources and the listed files are walked at depth 0
+                    let is_explicit = entry.depth() == 0;
+                    let entry = entry.into_path();
+                    // Check if the entry is a file, the hash index of a library and the ignore files being never organized
+                    if entry.is_file()
+                        && !is_hash_index_file(&entry)
+                        && entry.file_name() != Some(IGNORE_FILE_NAME.as_ref())
+                        && is_selected(&entry, self.config)
+                        && !self.is_companion(&entry, is_explicit)
+                        // A file found in several sources is organized once, whatever the path it is found by
+                        && self.seen_files.insert(entry.canonicalize().unwrap_or(entry.clone()))
+                    {
                         return Some(entry);
                     }
                 }
@@ -190,3 +402,53 @@ impl<'a> Iterator for FileIterator<'a> {
         None
     }
 }
+
+/// Selects the files to organize among the filtered ones.
+/// The files being sorted by path, `--offset` resumes a run stopped by `--limit`.
+///
+/// # Arguments
+///
+/// * `files` - The filtered files, sorted by path.
+/// * `offset` - The number of files skipped.
+/// * `sample` - The size of the random sample, if any.
+/// * `seed` - The seed of the random sample, a random one being used if `None`.
+/// * `limit` - The maximum number of files selected, if any.
+///
+/// # Returns
+///
+/// The selected files, in the order of `files`.
+pub fn select_files<'a, I>(
+    files: I,
+    offset: usize,
+    sample: Option<usize>,
+    seed: Option<u64>,
+    limit: Option<usize>,
+) -> Box<dyn Iterator<Item = PathBuf> + 'a>
+where
+    I: Iterator<Item = PathBuf> + 'a,
+{
+    let files = files.skip(offset);
+
+    let files: Box<dyn Iterator<Item = PathBuf> + 'a> = match sample {
+        Some(sample) => {
+            let files: Vec<PathBuf> = files.collect();
+            let mut rng = match seed {
+                Some(seed) => fastrand::Rng::with_seed(seed),
+                None => fastrand::Rng::new(),
+            };
+            let mut indices: Vec<usize> = (0..files.len()).collect();
+            rng.shuffle(&mut indices);
+            indices.truncate(sample);
+            indices.sort_unstable();
+            debug!("Sample {} file(s) out of {}", indices.len(), files.len());
+
+            Box::new(indices.into_iter().map(move |index| files[index].clone()))
+        }
+        None => Box::new(files),
+    };
+
+    match limit {
+        Some(limit) => Box::new(files.take(limit)),
+        None => files,
+    }
+}
@@ -1,4 +1,11 @@
+pub mod companions;
+pub mod conditional;
 pub mod duplicates_finder;
+pub mod filters;
 pub mod formatter;
+pub mod hash_index;
 pub mod iterator;
 pub mod parser;
+pub mod perceptual_hash;
+pub mod sanitizer;
+pub mod template;
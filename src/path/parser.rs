@@ -1,115 +1,412 @@
+use crate::errors::ClineupError;
+use crate::path::conditional::parse_tag;
+use crate::path::conditional::Condition;
+use crate::path::conditional::Tag;
+use crate::path::filters::get_filters;
+use crate::path::filters::split_placeholder_filters;
+use crate::path::template::Node;
+use crate::path::template::Template;
 use crate::placeholders::Placeholder;
+use crate::placeholders::PLACEHOLDER_NAMES;
 use indexmap::IndexMap;
+use strsim::levenshtein;
 
 fn is_word_char(c: char) -> bool {
     c.is_alphanumeric() || c == '_'
 }
 
+/// Name of a placeholder written without braces, from the word following its `%`.
+/// Since `_` is part of the names, a known name followed by `_` and more text, like `day_`
+/// in `%day_%counter`, ends before the `_`, the longest known name being kept.
+fn get_placeholder_name(word: &str) -> &str {
+    let is_known = |name: &str| {
+        PLACEHOLDER_NAMES
+            .iter()
+            .any(|known_name| known_name[1..] == *name)
+    };
+    if is_known(word) {
+        return word;
+    }
+    word.match_indices('_')
+        .rev()
+        .map(|(index, _)| &word[..index])
+        .find(|name| is_known(name))
+        .unwrap_or(word)
+}
+
+/// Error at a given column of the template, counted in characters
 #[derive(Debug, PartialEq)]
-struct ParsedPlaceholder {
-    full_placeholder: String,
-    placeholders: Vec<String>,
-}
-
-fn parse_percent_placeholder(iter: &mut std::iter::Peekable<std::str::Chars>) -> ParsedPlaceholder {
-    let mut full_placeholder = String::from("%");
-    let mut current_placeholder = String::from("%");
-    let mut placeholders = Vec::new();
-
-    iter.next();
-    while let Some(&next_char) = iter.peek() {
-        if is_word_char(next_char) {
-            current_placeholder.push(next_char);
-            full_placeholder.push(next_char);
-            iter.next();
-        } else if next_char == '%' {
-            placeholders.push(current_placeholder.clone());
-            current_placeholder.clear();
-            iter.next();
-            break;
-        } else {
-            break;
+struct SyntaxError {
+    column: usize,
+    message: String,
+}
+
+impl SyntaxError {
+    fn new<S: Into<String>>(column: usize, message: S) -> Self {
+        SyntaxError {
+            column,
+            message: message.into(),
         }
     }
-    placeholders.push(current_placeholder.clone());
-    ParsedPlaceholder {
-        full_placeholder,
-        placeholders,
+
+    /// Builds the error shown to the user, with a caret under the bad column
+    fn into_error(self, template: &str) -> ClineupError {
+        ClineupError::InvalidTemplate(format!(
+            "{} at column {}\n{}\n{}^",
+            self.message,
+            self.column + 1,
+            template,
+            " ".repeat(self.column)
+        ))
+    }
+}
+
+enum Token {
+    Node(Node),
+    Tag(Tag, usize),
+}
+
+fn get_tag_name(tag: &Tag) -> &'static str {
+    match tag {
+        Tag::If(_) => "{if ...}",
+        Tag::Elif(_) => "{elif ...}",
+        Tag::Else => "{else}",
+        Tag::End => "{end}",
+        Tag::Ternary(_, _, _) => "ternary",
+    }
+}
+
+/// Checks that a placeholder of a chain is a name optionally followed by arguments,
+/// like `%camera_model` or `%exif:Make:lower`.
+fn check_placeholder_syntax(element: &str, column: usize) -> Result<(), SyntaxError> {
+    let name_length = element[1..]
+        .chars()
+        .take_while(|c| is_word_char(*c))
+        .count();
+    if name_length == 0 {
+        return Err(SyntaxError::new(
+            column,
+            "Missing placeholder name after %, use %% for a literal %",
+        ));
+    }
+    match element[1..].chars().nth(name_length) {
+        None | Some(':') => Ok(()),
+        Some(c) => Err(SyntaxError::new(
+            column + 1 + name_length,
+            format!("Unexpected character '{}' in placeholder", c),
+        )),
     }
 }
 
-fn parse_curly_placeholder(iter: &mut std::iter::Peekable<std::str::Chars>) -> ParsedPlaceholder {
-    let mut full_placeholder = String::from("{");
-    let mut current_placeholder = String::from("{");
-    let mut placeholders = Vec::new();
-    let mut is_in_fallback = false;
+/// Parses a placeholder between curly braces, like `{%city|%country|Unknown}`.
+///
+/// # Arguments
+///
+/// * `chars` - The rest of the template, starting with `{`.
+/// * `column` - The column of the `{` in the template.
+///
+/// # Returns
+///
+/// The placeholder node and its length in characters.
+fn parse_curly_placeholder(chars: &[char], column: usize) -> Result<(Node, usize), SyntaxError> {
+    let mut chain = vec![String::new()];
+    let mut starts = vec![1];
+    // The arguments of the filters, like in `replace(|,-)`, may contain any character
+    let mut depth = 0;
+    let mut index = 1;
 
-    iter.next();
-    while let Some(&next_char) = iter.peek() {
-        if next_char == '}' {
-            if is_in_fallback {
-                placeholders.push(current_placeholder.clone());
+    loop {
+        let c = *chars
+            .get(index)
+            .ok_or_else(|| SyntaxError::new(column, "Unclosed {, use {{ for a literal {"))?;
+        match c {
+            '}' if depth == 0 => break,
+            '|' if depth == 0 => {
+                chain.push(String::new());
+                starts.push(index + 1);
+                index += 1;
+                continue;
             }
-            full_placeholder.push(next_char);
-            iter.next();
-            break;
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ => {}
         }
-        if next_char == '%' {
-            is_in_fallback = false;
-            let placeholder = parse_percent_placeholder(iter);
-            full_placeholder.push_str(&placeholder.full_placeholder);
-            placeholders.extend(placeholder.placeholders);
-            current_placeholder.clear();
-            continue;
+        chain.last_mut().unwrap().push(c);
+        index += 1;
+    }
+
+    if chain.len() == 1 && chain[0].is_empty() {
+        return Err(SyntaxError::new(column, "Empty placeholder {}"));
+    }
+    for (element, start) in chain.iter().zip(starts) {
+        if element.starts_with('%') {
+            check_placeholder_syntax(element, column + start)?;
+        }
+    }
+
+    let full_text = chars[..=index].iter().collect();
+    Ok((
+        Node::Placeholder {
+            full_text,
+            chain,
+            column,
+        },
+        index + 1,
+    ))
+}
+
+/// Turns a ternary into a conditional, its branches being templates on their own
+fn parse_ternary(
+    condition: Condition,
+    then: &str,
+    otherwise: &str,
+    tag_text: &str,
+    column: usize,
+) -> Result<Node, SyntaxError> {
+    let column_of = |position: Option<usize>| {
+        column + position.map_or(0, |position| tag_text[..position].chars().count())
+    };
+    let then_nodes = parse_nodes(then, column_of(tag_text.find(then)))?;
+    let otherwise_nodes = parse_nodes(otherwise, column_of(tag_text.rfind(otherwise)))?;
+
+    Ok(Node::Conditional {
+        branches: vec![(condition, then_nodes)],
+        otherwise: otherwise_nodes,
+        column,
+    })
+}
+
+fn tokenize(template: &str, offset: usize) -> Result<Vec<Token>, SyntaxError> {
+    let chars: Vec<char> = template.chars().collect();
+    let mut tokens = Vec::new();
+    let mut text = String::new();
+    let mut index = 0;
+
+    let flush = |text: &mut String, tokens: &mut Vec<Token>| {
+        if !text.is_empty() {
+            tokens.push(Token::Node(Node::Text(std::mem::take(text))));
         }
-        if next_char == '|' {
-            is_in_fallback = true;
-            current_placeholder.clear();
-            full_placeholder.push(next_char);
-            iter.next();
-            continue;
-        } else {
-            current_placeholder.push(next_char);
-            full_placeholder.push(next_char);
-            iter.next();
+    };
+
+    while index < chars.len() {
+        let column = offset + index;
+        match (chars[index], chars.get(index + 1)) {
+            ('%', Some('%')) | ('{', Some('{')) | ('}', Some('}')) => {
+                text.push(chars[index]);
+                index += 2;
+            }
+            ('%', _) => {
+                let word: String = chars[index + 1..]
+                    .iter()
+                    .take_while(|c| is_word_char(**c))
+                    .collect();
+                let name = get_placeholder_name(&word);
+                if name.is_empty() {
+                    return Err(SyntaxError::new(
+                        column,
+                        "Missing placeholder name after %, use %% for a literal %",
+                    ));
+                }
+                flush(&mut text, &mut tokens);
+                let full_text = format!("%{}", name);
+                index += full_text.chars().count();
+                tokens.push(Token::Node(Node::Placeholder {
+                    chain: vec![full_text.clone()],
+                    full_text,
+                    column,
+                }));
+            }
+            ('{', _) => {
+                flush(&mut text, &mut tokens);
+                let rest: String = chars[index..].iter().collect();
+                if let Some((tag, length)) = parse_tag(&rest) {
+                    let tag_text = &rest[..length];
+                    index += tag_text.chars().count();
+                    tokens.push(match tag {
+                        Tag::Ternary(condition, then, otherwise) => Token::Node(parse_ternary(
+                            condition, &then, &otherwise, tag_text, column,
+                        )?),
+                        tag => Token::Tag(tag, column),
+                    });
+                } else {
+                    let (node, length) = parse_curly_placeholder(&chars[index..], column)?;
+                    index += length;
+                    tokens.push(Token::Node(node));
+                }
+            }
+            ('}', _) => {
+                return Err(SyntaxError::new(
+                    column,
+                    "Unexpected }, use }} for a literal }",
+                ))
+            }
+            (c, _) => {
+                text.push(c);
+                index += 1;
+            }
         }
     }
+    flush(&mut text, &mut tokens);
+    Ok(tokens)
+}
 
-    ParsedPlaceholder {
-        full_placeholder,
-        placeholders,
+/// Nodes of a block and the tag closing it with its column, `None` at the end of the template
+type Block = (Vec<Node>, Option<(Tag, usize)>);
+
+/// Builds the nodes until a tag closing the current block
+fn build_nodes(tokens: &mut std::vec::IntoIter<Token>) -> Result<Block, SyntaxError> {
+    let mut nodes = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Node(node) => nodes.push(node),
+            Token::Tag(Tag::If(condition), column) => {
+                nodes.push(build_conditional(tokens, condition, column)?)
+            }
+            Token::Tag(tag, column) => return Ok((nodes, Some((tag, column)))),
+        }
     }
+    Ok((nodes, None))
 }
 
-pub fn parse_placeholders(path_to_format: &str) -> IndexMap<String, Vec<String>> {
-    let mut placeholder_map: IndexMap<String, Vec<String>> = IndexMap::new();
-    let mut iter = path_to_format.chars().peekable();
+fn build_conditional(
+    tokens: &mut std::vec::IntoIter<Token>,
+    condition: Condition,
+    column: usize,
+) -> Result<Node, SyntaxError> {
+    let mut branches = Vec::new();
+    let mut condition = condition;
 
-    while let Some(&c) = iter.peek() {
-        match c {
-            '%' => {
-                let placeholder = parse_percent_placeholder(&mut iter);
-                placeholder_map
-                    .entry(placeholder.full_placeholder.clone())
-                    .or_insert(vec![])
-                    .extend(placeholder.placeholders);
+    loop {
+        let (nodes, closing_tag) = build_nodes(tokens)?;
+        branches.push((condition, nodes));
+        match closing_tag {
+            Some((Tag::Elif(next_condition), _)) => condition = next_condition,
+            Some((Tag::Else, _)) => {
+                let (otherwise, closing_tag) = build_nodes(tokens)?;
+                return match closing_tag {
+                    Some((Tag::End, _)) => Ok(Node::Conditional {
+                        branches,
+                        otherwise,
+                        column,
+                    }),
+                    Some((tag, tag_column)) => Err(SyntaxError::new(
+                        tag_column,
+                        format!("Unexpected {} after {{else}}", get_tag_name(&tag)),
+                    )),
+                    None => Err(SyntaxError::new(column, "Missing {end} for this {if ...}")),
+                };
+            }
+            Some((Tag::End, _)) => {
+                return Ok(Node::Conditional {
+                    branches,
+                    otherwise: vec![],
+                    column,
+                })
             }
+            _ => return Err(SyntaxError::new(column, "Missing {end} for this {if ...}")),
+        }
+    }
+}
+
+fn parse_nodes(template: &str, offset: usize) -> Result<Vec<Node>, SyntaxError> {
+    let mut tokens = tokenize(template, offset)?.into_iter();
+    match build_nodes(&mut tokens)? {
+        (nodes, None) => Ok(nodes),
+        (_, Some((tag, column))) => Err(SyntaxError::new(
+            column,
+            format!("Unexpected {} without {{if ...}}", get_tag_name(&tag)),
+        )),
+    }
+}
+
+/// Parses a template into its syntax tree.
+///
+/// `%%`, `{{` and `}}` are the escapes of a literal `%`, `{` and `}`.
+///
+/// # Arguments
+///
+/// * `path_to_format` - The template, like `%year/{%city|Unknown}/{%original_filename}`.
+///
+/// # Returns
+///
+/// The `Template`, or an error pointing at the column of the first syntax error.
+pub fn parse_template(path_to_format: &str) -> Result<Template, ClineupError> {
+    parse_nodes(path_to_format, 0)
+        .map(Template::new)
+        .map_err(|error| error.into_error(path_to_format))
+}
+
+/// Returns the known placeholder closest to an unknown one, if it is close enough to be a typo
+fn get_suggestion(name: &str) -> Option<&'static str> {
+    PLACEHOLDER_NAMES
+        .iter()
+        .map(|candidate| (levenshtein(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2.max(name.len() / 3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
 
-            '{' => {
-                let placeholder = parse_curly_placeholder(&mut iter);
-                placeholder_map
-                    .entry(placeholder.full_placeholder.clone())
-                    .or_insert(vec![])
-                    .extend(placeholder.placeholders);
+fn check_placeholder(placeholder_text: &str, column: usize) -> Result<(), SyntaxError> {
+    if !placeholder_text.starts_with('%') {
+        return Ok(());
+    }
+    if Placeholder::from_string(placeholder_text) == Placeholder::Unknown {
+        let (name, _) = split_placeholder_filters(placeholder_text);
+        let message = match get_suggestion(&name) {
+            Some(suggestion) => {
+                format!("Unknown placeholder {}, did you mean {}?", name, suggestion)
             }
+            None => format!("Unknown placeholder {}", name),
+        };
+        return Err(SyntaxError::new(column, message));
+    }
+    get_filters(placeholder_text)
+        .map(|_| ())
+        .map_err(|error| SyntaxError::new(column, error.to_string()))
+}
 
-            _ => {
-                iter.next(); // Skip the character if it is not '%' or '{'
+fn check_nodes(nodes: &[Node]) -> Result<(), SyntaxError> {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Placeholder { chain, column, .. } => {
+                for placeholder_text in chain {
+                    check_placeholder(placeholder_text, *column)?;
+                }
+            }
+            Node::Conditional {
+                branches,
+                otherwise,
+                column,
+            } => {
+                for (condition, nodes) in branches {
+                    for placeholder_text in condition.placeholders() {
+                        check_placeholder(placeholder_text, *column)?;
+                    }
+                    check_nodes(nodes)?;
+                }
+                check_nodes(otherwise)?;
             }
         }
     }
+    Ok(())
+}
+
+/// Checks that every placeholder of the template is known and that its filters exist.
+pub fn check_placeholders(path_to_format: &str, template: &Template) -> Result<(), ClineupError> {
+    check_nodes(template.nodes()).map_err(|error| error.into_error(path_to_format))
+}
 
-    placeholder_map
+/// Lists the placeholder chains of a template.
+///
+/// # Returns
+///
+/// The placeholder chains, or an error pointing at the column of the first syntax error.
+pub fn parse_placeholders(
+    path_to_format: &str,
+) -> Result<IndexMap<String, Vec<String>>, ClineupError> {
+    parse_template(path_to_format).map(|template| template.placeholders())
 }
 
 /// Maps placeholders to enums.
@@ -141,93 +438,144 @@ pub fn map_placeholders_to_enums(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::path::template::PlaceholderValue;
+    use crate::path::template::PlaceholderValues;
 
-    #[test]
-    fn test_parse_percent_placeholder_single() {
-        let input = "%placeholder";
-        let mut iter = input.chars().peekable();
-        let result = parse_percent_placeholder(&mut iter);
+    fn expected(entries: &[(&str, &[&str])]) -> IndexMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(full, chain)| {
+                (
+                    full.to_string(),
+                    chain.iter().map(|text| text.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
 
-        let expected = ParsedPlaceholder {
-            full_placeholder: "%placeholder".to_string(),
-            placeholders: vec!["%placeholder".to_string()],
-        };
+    fn values(entries: &[(&str, &str)]) -> PlaceholderValues {
+        entries
+            .iter()
+            .map(|(full, value)| {
+                (
+                    full.to_string(),
+                    PlaceholderValue::new(value.to_string(), value.starts_with("Unknown")),
+                )
+            })
+            .collect()
+    }
+
+    fn error_message(template: &str) -> String {
+        match parse_template(template).and_then(|parsed| check_placeholders(template, &parsed)) {
+            Err(ClineupError::InvalidTemplate(message)) => message,
+            other => panic!("Expected a template error, got {:?}", other),
+        }
+    }
 
-        assert_eq!(result.placeholders, expected.placeholders);
-        assert_eq!(result.full_placeholder, expected.full_placeholder);
+    #[test]
+    fn test_parse_percent_placeholder_single() {
+        assert_eq!(
+            parse_placeholders("%placeholder").unwrap(),
+            expected(&[("%placeholder", &["%placeholder"])])
+        );
+    }
+    #[test]
+    fn test_parse_percent_placeholders_without_separator() {
+        assert_eq!(
+            parse_placeholders("%year%month").unwrap(),
+            expected(&[("%year", &["%year"]), ("%month", &["%month"])])
+        );
+        // A known name followed by `_` ends before it
+        assert_eq!(
+            parse_placeholders("%day_%counter.%ext_lower").unwrap(),
+            expected(&[
+                ("%day", &["%day"]),
+                ("%counter", &["%counter"]),
+                ("%ext_lower", &["%ext_lower"])
+            ])
+        );
+        assert_eq!(
+            parse_placeholders("%camera_model_%iso_").unwrap(),
+            expected(&[("%camera_model", &["%camera_model"]), ("%iso", &["%iso"])])
+        );
     }
     #[test]
     fn test_parse_curly_placeholder_single() {
-        let input = "{%placeholder}";
-        let mut iter = input.chars().peekable();
-        let result = parse_curly_placeholder(&mut iter);
-
-        let expected = ParsedPlaceholder {
-            full_placeholder: "{%placeholder}".to_string(),
-            placeholders: vec!["%placeholder".to_string()],
-        };
-
-        assert_eq!(result.placeholders, expected.placeholders);
-        assert_eq!(result.full_placeholder, expected.full_placeholder);
+        assert_eq!(
+            parse_placeholders("{%placeholder}").unwrap(),
+            expected(&[("{%placeholder}", &["%placeholder"])])
+        );
     }
 
     #[test]
     fn test_parse_curly_placeholder_multiple() {
-        let input = "{%placeholder|%second_placeholder}";
-        let mut iter = input.chars().peekable();
-        let result = parse_curly_placeholder(&mut iter);
-
-        let expected = ParsedPlaceholder {
-            full_placeholder: "{%placeholder|%second_placeholder}".to_string(),
-            placeholders: vec![
-                "%placeholder".to_string(),
-                "%second_placeholder".to_string(),
-            ],
-        };
-
-        assert_eq!(result.placeholders, expected.placeholders);
-        assert_eq!(result.full_placeholder, expected.full_placeholder);
+        assert_eq!(
+            parse_placeholders("{%placeholder|%second_placeholder}").unwrap(),
+            expected(&[(
+                "{%placeholder|%second_placeholder}",
+                &["%placeholder", "%second_placeholder"]
+            )])
+        );
     }
     #[test]
     fn test_parse_curly_placeholder_multiple_fallback() {
-        let input = "{%placeholder|%second_placeholder|fallback}";
-        let mut iter = input.chars().peekable();
-        let result = parse_curly_placeholder(&mut iter);
-
-        let expected = ParsedPlaceholder {
-            full_placeholder: "{%placeholder|%second_placeholder|fallback}".to_string(),
-            placeholders: vec![
-                "%placeholder".to_string(),
-                "%second_placeholder".to_string(),
-                "fallback".to_string(),
-            ],
-        };
-
-        assert_eq!(result.placeholders, expected.placeholders);
-        assert_eq!(result.full_placeholder, expected.full_placeholder);
+        assert_eq!(
+            parse_placeholders("{%placeholder|%second_placeholder|fallback}").unwrap(),
+            expected(&[(
+                "{%placeholder|%second_placeholder|fallback}",
+                &["%placeholder", "%second_placeholder", "fallback"]
+            )])
+        );
     }
     #[test]
     fn test_parse_curly_placeholder_multiple_last_fallback_empty() {
-        let input = "{%placeholder|%second_placeholder|}";
-        let mut iter = input.chars().peekable();
-        let result = parse_curly_placeholder(&mut iter);
-
-        let expected = ParsedPlaceholder {
-            full_placeholder: "{%placeholder|%second_placeholder|}".to_string(),
-            placeholders: vec![
-                "%placeholder".to_string(),
-                "%second_placeholder".to_string(),
-                "".to_string(),
-            ],
-        };
+        assert_eq!(
+            parse_placeholders("{%placeholder|%second_placeholder|}").unwrap(),
+            expected(&[(
+                "{%placeholder|%second_placeholder|}",
+                &["%placeholder", "%second_placeholder", ""]
+            )])
+        );
+    }
+    #[test]
+    fn test_parse_curly_placeholder_with_argument() {
+        assert_eq!(
+            parse_placeholders("{%exif:LensModel|%camera_model|Unknown}").unwrap(),
+            expected(&[(
+                "{%exif:LensModel|%camera_model|Unknown}",
+                &["%exif:LensModel", "%camera_model", "Unknown"]
+            )])
+        );
+    }
+    #[test]
+    fn test_parse_curly_placeholder_with_filters() {
+        assert_eq!(
+            parse_placeholders("{%camera_model:replace(|,-):lower|%camera_brand:upper}").unwrap(),
+            expected(&[(
+                "{%camera_model:replace(|,-):lower|%camera_brand:upper}",
+                &["%camera_model:replace(|,-):lower", "%camera_brand:upper"]
+            )])
+        );
+    }
+    #[test]
+    fn test_parse_placeholders_conditionals() {
+        let result = parse_placeholders(
+            "{if %media_type == video}Videos{else}{%year}{end}/{%has_gps ? %city : No location}",
+        )
+        .unwrap();
+
+        let mut expected = IndexMap::new();
+        expected.insert("%media_type".to_string(), vec!["%media_type".to_string()]);
+        expected.insert("{%year}".to_string(), vec!["%year".to_string()]);
+        expected.insert("%has_gps".to_string(), vec!["%has_gps".to_string()]);
+        expected.insert("%city".to_string(), vec!["%city".to_string()]);
 
-        assert_eq!(result.placeholders, expected.placeholders);
-        assert_eq!(result.full_placeholder, expected.full_placeholder);
+        assert_eq!(result, expected);
     }
     #[test]
     fn test_parse_parse_placeholders_full_path() {
         let input = "/home/myuser/photos/%year/{%city|%camera_brand|To sort}";
-        let result = parse_placeholders(input);
+        let result = parse_placeholders(input).unwrap();
 
         let mut expected = IndexMap::new();
         expected.insert("%year".to_string(), vec!["%year".to_string()]);
@@ -242,4 +590,113 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+    #[test]
+    fn test_parse_placeholders_syntax_error() {
+        assert!(parse_placeholders("%year/{%city|Unknown").is_err());
+        assert!(parse_placeholders("{if %has_gps}GPS").is_err());
+    }
+    #[test]
+    fn test_render_escapes() {
+        let template = parse_template("100%%/{{%year}}/{%city|Unknown}").unwrap();
+        assert_eq!(
+            template.placeholders(),
+            expected(&[
+                ("%year", &["%year"]),
+                ("{%city|Unknown}", &["%city", "Unknown"])
+            ])
+        );
+        let values = values(&[("%year", "2023"), ("{%city|Unknown}", "Paris")]);
+        assert_eq!(template.render(&values), "100%/{2023}/Paris");
+    }
+    #[test]
+    fn test_render_conditionals() {
+        let template = parse_template(
+            "{if %media_type == video}Videos{elif %width > 3000}Large{else}Photos{end}/{%has_gps ? %city : No city}",
+        )
+        .unwrap();
+
+        let video = values(&[
+            ("%media_type", "video"),
+            ("%width", "640"),
+            ("%has_gps", "true"),
+            ("%city", "Paris"),
+        ]);
+        assert_eq!(template.render(&video), "Videos/Paris");
+
+        let large = values(&[
+            ("%media_type", "photo"),
+            ("%width", "4000"),
+            ("%has_gps", "false"),
+        ]);
+        assert_eq!(template.render(&large), "Large/No city");
+
+        let unknown = values(&[("%media_type", "photo"), ("%width", "Unknown Width")]);
+        assert_eq!(template.render(&unknown), "Photos/No city");
+    }
+    #[test]
+    fn test_render_nested_conditionals() {
+        let template =
+            parse_template("{if %has_gps}{if %size > 1MB}Big{end}{%city ? %city : No city}{end}")
+                .unwrap();
+
+        let with_city = values(&[
+            ("%has_gps", "true"),
+            ("%size", "2097152"),
+            ("%city", "Paris"),
+        ]);
+        assert_eq!(template.render(&with_city), "BigParis");
+
+        let without_city = values(&[
+            ("%has_gps", "true"),
+            ("%size", "10"),
+            ("%city", "Unknown City"),
+        ]);
+        assert_eq!(template.render(&without_city), "No city");
+
+        let without_gps = values(&[("%has_gps", "false")]);
+        assert_eq!(template.render(&without_gps), "");
+    }
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(
+            error_message("%year/{%month"),
+            "Unclosed {, use {{ for a literal { at column 7\n%year/{%month\n      ^"
+        );
+        assert_eq!(
+            error_message("%year}"),
+            "Unexpected }, use }} for a literal } at column 6\n%year}\n     ^"
+        );
+        assert_eq!(
+            error_message("100%/%year"),
+            "Missing placeholder name after %, use %% for a literal % at column 4\n100%/%year\n   ^"
+        );
+        assert_eq!(
+            error_message("{%year text}"),
+            "Unexpected character ' ' in placeholder at column 7\n{%year text}\n      ^"
+        );
+        assert!(error_message("{if %width > 10}a{else}b").starts_with("Missing {end}"));
+        assert!(error_message("a{end}").starts_with("Unexpected {end} without {if ...}"));
+        assert!(
+            error_message("{if %width > 10}a{else}b{elif %height}c{end}")
+                .starts_with("Unexpected {elif ...} after {else}")
+        );
+    }
+    #[test]
+    fn test_unknown_placeholders() {
+        assert_eq!(
+            error_message("%year/%mnth"),
+            "Unknown placeholder %mnth, did you mean %month? at column 7\n%year/%mnth\n      ^"
+        );
+        assert!(error_message("{%camera_brand:lower|%camera_modle}")
+            .starts_with("Unknown placeholder %camera_modle, did you mean %camera_model?"));
+        assert!(error_message("{if %mediatype == video}a{end}")
+            .starts_with("Unknown placeholder %mediatype, did you mean %media_type?"));
+        assert!(
+            error_message("%something_else").starts_with("Unknown placeholder %something_else at")
+        );
+        assert!(error_message("{%year:lowercase}").starts_with("Invalid filter: lowercase"));
+
+        let valid = "{%exif:Make:lower|Unknown}/{%has_gps ? %city : No location}";
+        assert!(check_placeholders(valid, &parse_template(valid).unwrap()).is_ok());
+    }
 }
@@ -0,0 +1,132 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+use std::path::Path;
+
+use crate::errors::ClineupError;
+use crate::exif_extractor::ExifExtractor;
+
+/// Default maximum number of different bits between the hashes of near-duplicates, out of 64
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+
+/// Calculates the difference hash of an image : each bit tells whether a pixel of a 9x8 grayscale
+/// thumbnail is brighter than its right neighbour. Resizing or re-encoding the image barely changes it.
+/// The image is first rotated as its EXIF orientation tells, so that a rotated copy has the same hash.
+///
+/// # Arguments
+///
+/// * `path` - The image, in a format which can be decoded.
+///
+/// # Returns
+///
+/// The 64 bits hash, or an error if the image can not be decoded.
+pub fn get_dhash(path: &Path) -> Result<u64, ClineupError> {
+    let orientation = ExifExtractor::new(&path.to_path_buf())
+        .and_then(|extractor| extractor.get_orientation())
+        .unwrap_or(1);
+    let thumbnail = apply_orientation(image::open(path)?, orientation)
+        .resize_exact(9, 8, FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let is_brighter = thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | is_brighter as u64;
+        }
+    }
+    Ok(hash)
+}
+
+/// Rotates or flips an image as its EXIF orientation tells, from 1 to 8, so that it is upright.
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Number of different bits between two hashes, the lower the more similar the images.
+pub fn hamming_distance(left: u64, right: u64) -> u32 {
+    (left ^ right).count_ones()
+}
+
+/// Number of pixels of an image, from its EXIF dimensions or else from its header.
+pub fn get_resolution(path: &Path) -> u64 {
+    let exif_dimensions = ExifExtractor::new(&path.to_path_buf()).and_then(|extractor| {
+        Ok((
+            extractor.get_width()? as u64,
+            extractor.get_height()? as u64,
+        ))
+    });
+    match exif_dimensions {
+        Ok((width, height)) => width * height,
+        Err(_) => {
+            image::image_dimensions(path).map_or(0, |(width, height)| width as u64 * height as u64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::experimental::Writer;
+    use exif::{Field, In, Tag, Value};
+    use tempdir::TempDir;
+
+    /// Saves a JPEG image with an EXIF orientation, in an APP1 segment right after its start marker
+    fn save_with_orientation(image: &DynamicImage, path: &Path, orientation: u16) {
+        let field = Field {
+            tag: Tag::Orientation,
+            ifd_num: In::PRIMARY,
+            value: Value::Short(vec![orientation]),
+        };
+        let mut writer = Writer::new();
+        writer.push_field(&field);
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let tiff = tiff.into_inner();
+
+        let mut jpeg = std::io::Cursor::new(Vec::new());
+        image
+            .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90))
+            .unwrap();
+        let jpeg = jpeg.into_inner();
+        let mut content = jpeg[..2].to_vec();
+        content.extend_from_slice(&[0xff, 0xe1]);
+        content.extend_from_slice(&(8 + tiff.len() as u16).to_be_bytes());
+        content.extend_from_slice(b"Exif\0\0");
+        content.extend_from_slice(&tiff);
+        content.extend_from_slice(&jpeg[2..]);
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_get_dhash_orientation() {
+        let tmp_dir = TempDir::new("orientation").unwrap();
+        let folder = tmp_dir.path();
+        // Blocks of pseudo-random brightness, so that the image does not look the same sideways
+        let photo = DynamicImage::ImageLuma8(image::GrayImage::from_fn(64, 48, |x, y| {
+            image::Luma([((x / 8 * 7 + y / 8 * 13).wrapping_mul(2654435761) >> 24) as u8])
+        }));
+        photo.save(folder.join("original.png")).unwrap();
+        // Stored sideways, as a camera held in portrait does, with the orientation to display it upright
+        save_with_orientation(&photo.rotate270(), &folder.join("rotated.jpg"), 6);
+        photo.rotate270().save(folder.join("sideways.png")).unwrap();
+
+        let original = get_dhash(&folder.join("original.png")).unwrap();
+        assert!(
+            hamming_distance(original, get_dhash(&folder.join("rotated.jpg")).unwrap())
+                <= DEFAULT_MAX_DISTANCE
+        );
+        assert!(
+            hamming_distance(original, get_dhash(&folder.join("sideways.png")).unwrap())
+                > DEFAULT_MAX_DISTANCE
+        );
+    }
+}
@@ -0,0 +1,319 @@
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization as _;
+
+use crate::errors::ClineupError;
+
+const MAX_COMPONENT_LENGTH: usize = 255;
+const WINDOWS_RESERVED_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+const WINDOWS_RESERVED_NAMES: [&str; 26] = [
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4",
+    "COM5", "COM6", "COM7", "COM8", "COM9", "LPT0", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6",
+    "LPT7", "LPT8", "LPT9",
+];
+
+/// File system the destination paths must be valid on
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SanitizeProfile {
+    Posix,
+    Windows,
+    Exfat,
+}
+
+impl SanitizeProfile {
+    pub fn from_string(profile: &str) -> Result<SanitizeProfile, ClineupError> {
+        match profile.to_ascii_lowercase().as_str() {
+            "posix" => Ok(SanitizeProfile::Posix),
+            "windows" => Ok(SanitizeProfile::Windows),
+            "exfat" => Ok(SanitizeProfile::Exfat),
+            _ => Err(ClineupError::InvalidSanitizeProfile(profile.to_string())),
+        }
+    }
+
+    fn is_reserved_char(&self, c: char) -> bool {
+        match self {
+            SanitizeProfile::Posix => c == '/' || c == '\0',
+            SanitizeProfile::Windows | SanitizeProfile::Exfat => {
+                c.is_ascii_control() || WINDOWS_RESERVED_CHARS.contains(&c)
+            }
+        }
+    }
+
+    fn is_separator(&self, c: char) -> bool {
+        match self {
+            SanitizeProfile::Posix => c == '/',
+            SanitizeProfile::Windows | SanitizeProfile::Exfat => c == '/' || c == '\\',
+        }
+    }
+
+    /// Length of a name as counted by the file system : bytes on POSIX, UTF-16 code units otherwise
+    fn length(&self, name: &str) -> usize {
+        match self {
+            SanitizeProfile::Posix => name.len(),
+            SanitizeProfile::Windows | SanitizeProfile::Exfat => name.encode_utf16().count(),
+        }
+    }
+
+    fn max_path_length(&self) -> usize {
+        match self {
+            SanitizeProfile::Posix => 4096,
+            SanitizeProfile::Windows => 260,
+            SanitizeProfile::Exfat => 32767,
+        }
+    }
+}
+
+/// Unicode normalization form of the destination paths
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnicodeNormalization {
+    Nfc,
+    Nfd,
+}
+
+impl UnicodeNormalization {
+    pub fn from_string(normalization: &str) -> Result<Option<UnicodeNormalization>, ClineupError> {
+        match normalization.to_ascii_lowercase().as_str() {
+            "nfc" => Ok(Some(UnicodeNormalization::Nfc)),
+            "nfd" => Ok(Some(UnicodeNormalization::Nfd)),
+            "none" => Ok(None),
+            _ => Err(ClineupError::InvalidUnicodeNormalization(
+                normalization.to_string(),
+            )),
+        }
+    }
+
+    fn normalize(&self, value: &str) -> String {
+        match self {
+            UnicodeNormalization::Nfc => value.nfc().collect(),
+            UnicodeNormalization::Nfd => value.nfd().collect(),
+        }
+    }
+}
+
+/// Makes the destination paths valid for a given file system
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sanitizer {
+    profile: SanitizeProfile,
+    normalization: Option<UnicodeNormalization>,
+    replacement: String,
+}
+
+/// The file names are kept as they are written, the normalization renaming them being opt-in
+impl Default for Sanitizer {
+    fn default() -> Self {
+        Sanitizer::new(SanitizeProfile::Posix, None)
+    }
+}
+
+impl Sanitizer {
+    pub fn new(profile: SanitizeProfile, normalization: Option<UnicodeNormalization>) -> Self {
+        Sanitizer {
+            profile,
+            normalization,
+            replacement: "_".to_string(),
+        }
+    }
+
+    /// Sets the string replacing the reserved characters. They are stripped if it is empty.
+    pub fn with_replacement(mut self, replacement: String) -> Self {
+        self.replacement = replacement;
+        self
+    }
+
+    fn replace_reserved_chars(&self, value: &str) -> String {
+        value
+            .chars()
+            .map(|c| {
+                if self.profile.is_reserved_char(c) {
+                    self.replacement.clone()
+                } else {
+                    c.to_string()
+                }
+            })
+            .collect()
+    }
+
+    /// Sanitizes the value of a placeholder before it is put in the path, so that
+    /// a value like `AC/DC` or `..` never creates or leaves a folder.
+    pub fn sanitize_value(&self, value: &str) -> String {
+        let value = self.replace_reserved_chars(value);
+        if !value.is_empty() && value.chars().all(|c| c == '.') {
+            return value.replace('.', "_");
+        }
+        value
+    }
+
+    fn sanitize_component(&self, component: &str, is_file_name: bool) -> String {
+        if component.is_empty() || component == "." || component == ".." {
+            return component.to_string();
+        }
+
+        let mut component = match self.normalization {
+            Some(normalization) => normalization.normalize(component),
+            None => component.to_string(),
+        };
+        component = self.replace_reserved_chars(&component);
+
+        if self.profile != SanitizeProfile::Posix {
+            component = self.fix_windows_name(component);
+        }
+
+        if self.profile.length(&component) > MAX_COMPONENT_LENGTH {
+            component = self.truncate_component(&component, is_file_name);
+            if self.profile != SanitizeProfile::Posix {
+                component = self.fix_windows_name(component);
+            }
+        }
+        component
+    }
+
+    /// Removes the trailing dots and spaces which are silently dropped by Windows,
+    /// and renames the reserved device names like `CON` or `LPT1.jpg`.
+    fn fix_windows_name(&self, component: String) -> String {
+        let mut component = component.trim_end_matches(['.', ' ']).to_string();
+        if component.is_empty() {
+            component = "_".to_string();
+        }
+
+        let (stem, rest) = match component.find('.') {
+            Some(index) => component.split_at(index),
+            None => (component.as_str(), ""),
+        };
+        if WINDOWS_RESERVED_NAMES
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case(stem.trim_end()))
+        {
+            return format!("{}_{}", stem, rest);
+        }
+        component
+    }
+
+    /// Shortens a component to the maximum length, keeping the extension of the file name.
+    fn truncate_component(&self, component: &str, is_file_name: bool) -> String {
+        let extension = match component.rfind('.') {
+            Some(index) if is_file_name && index > 0 => &component[index..],
+            _ => "",
+        };
+        let stem = &component[..component.len() - extension.len()];
+        let max_stem_length = MAX_COMPONENT_LENGTH.saturating_sub(self.profile.length(extension));
+
+        let mut truncated = String::new();
+        let mut length = 0;
+        for c in stem.chars() {
+            length += self.profile.length(c.encode_utf8(&mut [0; 4]));
+            if length > max_stem_length {
+                break;
+            }
+            truncated.push(c);
+        }
+        truncated.push_str(extension);
+        truncated
+    }
+
+    /// Sanitizes a folder name which is added to an already sanitized path, like the subfolder of a burst.
+    pub fn sanitize_folder_name(&self, name: &str) -> String {
+        self.sanitize_component(&self.sanitize_value(name), false)
+    }
+
+    /// Sanitizes every component of a formatted path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path relative to the destination, like `2023/Paris/IMG_0001.jpg`.
+    ///
+    /// # Returns
+    ///
+    /// The path with reserved characters replaced, Windows specific names fixed,
+    /// components truncated to 255 characters and Unicode normalized.
+    pub fn sanitize_path(&self, path: &str) -> String {
+        let components: Vec<&str> = path.split(|c| self.profile.is_separator(c)).collect();
+        let last_index = components.len() - 1;
+        components
+            .iter()
+            .enumerate()
+            .map(|(index, component)| self.sanitize_component(component, index == last_index))
+            .collect::<Vec<String>>()
+            .join("/")
+    }
+
+    /// Checks that the full destination path does not exceed the limit of the file system.
+    pub fn check_path_length(&self, path: &Path) -> Result<(), ClineupError> {
+        let path = path.to_string_lossy();
+        if self.profile.length(&path) > self.profile.max_path_length() {
+            return Err(ClineupError::PathTooLong(path.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_value() {
+        let posix = Sanitizer::default();
+        assert_eq!(posix.sanitize_value("AC/DC"), "AC_DC");
+        assert_eq!(posix.sanitize_value("Paris: 10e?"), "Paris: 10e?");
+        assert_eq!(posix.sanitize_value(".."), "__");
+
+        let windows = Sanitizer::new(SanitizeProfile::Windows, None);
+        assert_eq!(windows.sanitize_value("Paris: 10e?"), "Paris_ 10e_");
+
+        let stripped = windows.with_replacement(String::new());
+        assert_eq!(stripped.sanitize_value("Paris: 10e?"), "Paris 10e");
+    }
+
+    #[test]
+    fn test_sanitize_path_windows() {
+        let windows = Sanitizer::new(SanitizeProfile::Windows, None);
+        assert_eq!(
+            windows.sanitize_path("2023/Saint-Malo. /con/LPT1.jpg"),
+            "2023/Saint-Malo/con_/LPT1_.jpg"
+        );
+        assert_eq!(windows.sanitize_path("a\\b/CONSOLE.jpg"), "a/b/CONSOLE.jpg");
+        assert_eq!(
+            windows.sanitize_path("com0/lpt0.jpg/CONIN$/conout$.txt"),
+            "com0_/lpt0_.jpg/CONIN$_/conout$_.txt"
+        );
+
+        let posix = Sanitizer::default();
+        assert_eq!(posix.sanitize_path("Saint-Malo. /CON"), "Saint-Malo. /CON");
+    }
+
+    #[test]
+    fn test_sanitize_path_length() {
+        let posix = Sanitizer::default();
+        let long_name = format!("{}.jpg", "é".repeat(200));
+        let sanitized = posix.sanitize_path(&format!("2023/{}", long_name));
+        let file_name = sanitized.strip_prefix("2023/").unwrap();
+        assert_eq!(file_name.len(), 254);
+        assert!(file_name.ends_with(".jpg"));
+
+        let exfat = Sanitizer::new(SanitizeProfile::Exfat, None);
+        assert_eq!(exfat.sanitize_path(&long_name), long_name);
+
+        let windows = Sanitizer::new(SanitizeProfile::Windows, None);
+        assert!(windows.check_path_length(Path::new(&long_name)).is_ok());
+        assert!(windows
+            .check_path_length(Path::new(&format!("a/{}/{}", long_name, long_name)))
+            .is_err());
+    }
+
+    #[test]
+    fn test_unicode_normalization() {
+        let decomposed = "Ze\u{301}nith";
+        let composed = "Z\u{e9}nith";
+
+        let nfc = Sanitizer::new(SanitizeProfile::Posix, Some(UnicodeNormalization::Nfc));
+        assert_eq!(nfc.sanitize_path(decomposed), composed);
+
+        let nfd = Sanitizer::new(SanitizeProfile::Posix, Some(UnicodeNormalization::Nfd));
+        assert_eq!(nfd.sanitize_path(composed), decomposed);
+
+        let none = Sanitizer::new(SanitizeProfile::Posix, None);
+        assert_eq!(none.sanitize_path(decomposed), decomposed);
+
+        // The names are not renamed unless a normalization is asked for
+        assert_eq!(Sanitizer::default().sanitize_path(decomposed), decomposed);
+    }
+}
@@ -0,0 +1,153 @@
+use indexmap::IndexMap;
+
+use crate::path::conditional::Condition;
+
+/// Value of a placeholder once computed
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaceholderValue {
+    text: String,
+    is_fallback: bool,
+    chain_index: usize,
+}
+
+impl PlaceholderValue {
+    pub fn new(text: String, is_fallback: bool) -> Self {
+        PlaceholderValue {
+            text,
+            is_fallback,
+            chain_index: 0,
+        }
+    }
+
+    /// Sets the index of the element of the chain which gave the value
+    pub fn with_chain_index(mut self, chain_index: usize) -> Self {
+        self.chain_index = chain_index;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether every placeholder of the chain failed and the value is a fallback name
+    pub fn is_fallback(&self) -> bool {
+        self.is_fallback
+    }
+
+    /// Index of the element of the chain which gave the value, `0` unless a fallback was used
+    pub fn chain_index(&self) -> usize {
+        self.chain_index
+    }
+}
+
+/// Values of the placeholders of a template, by the text of their chain like `{%city|Unknown}`
+pub type PlaceholderValues = IndexMap<String, PlaceholderValue>;
+
+/// Node of the syntax tree of a template
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// Literal text, escapes already resolved
+    Text(String),
+    /// A placeholder and its fallbacks, like `%year` or `{%city|%country|Unknown}`
+    Placeholder {
+        full_text: String,
+        chain: Vec<String>,
+        column: usize,
+    },
+    /// The first branch whose condition holds is kept, else the `otherwise` nodes
+    Conditional {
+        branches: Vec<(Condition, Vec<Node>)>,
+        otherwise: Vec<Node>,
+        column: usize,
+    },
+}
+
+/// Parsed template, see `path::parser::parse_template`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+fn collect_placeholders(nodes: &[Node], placeholders: &mut IndexMap<String, Vec<String>>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Placeholder {
+                full_text, chain, ..
+            } => {
+                placeholders
+                    .entry(full_text.clone())
+                    .or_insert_with(|| chain.clone());
+            }
+            Node::Conditional {
+                branches,
+                otherwise,
+                ..
+            } => {
+                for (condition, nodes) in branches {
+                    // The placeholders of the conditions are computed like any other one
+                    for text in condition.placeholders() {
+                        placeholders
+                            .entry(text.to_string())
+                            .or_insert_with(|| vec![text.to_string()]);
+                    }
+                    collect_placeholders(nodes, placeholders);
+                }
+                collect_placeholders(otherwise, placeholders);
+            }
+        }
+    }
+}
+
+fn render_nodes(nodes: &[Node], values: &PlaceholderValues, rendered: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => rendered.push_str(text),
+            Node::Placeholder { full_text, .. } => {
+                if let Some(value) = values.get(full_text) {
+                    rendered.push_str(value.text());
+                }
+            }
+            Node::Conditional {
+                branches,
+                otherwise,
+                ..
+            } => {
+                let kept = branches
+                    .iter()
+                    .find(|(condition, _)| condition.evaluate(values))
+                    .map_or(otherwise, |(_, nodes)| nodes);
+                render_nodes(kept, values, rendered);
+            }
+        }
+    }
+}
+
+impl Template {
+    pub fn new(nodes: Vec<Node>) -> Self {
+        Template { nodes }
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Lists the placeholder chains of the template, including the ones of the conditions.
+    ///
+    /// # Returns
+    ///
+    /// The chains by their full text, like `{%city|Unknown}` mapped to `["%city", "Unknown"]`.
+    pub fn placeholders(&self) -> IndexMap<String, Vec<String>> {
+        let mut placeholders = IndexMap::new();
+        collect_placeholders(&self.nodes, &mut placeholders);
+        placeholders
+    }
+
+    /// Builds the path from the values of the placeholders, keeping only the branches
+    /// of the conditionals whose condition holds.
+    pub fn render(&self, values: &PlaceholderValues) -> String {
+        let mut rendered = String::new();
+        render_nodes(&self.nodes, values, &mut rendered);
+        rendered
+    }
+}
@@ -1,7 +1,93 @@
+use crate::path::filters::split_placeholder_filters;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Prefix of the placeholder giving the raw value of any EXIF tag, like `%exif:LensModel`
+const EXIF_PREFIX: &str = "%exif:";
+
+/// The known placeholders by name, from which `Placeholder::from_string` and the suggestions are derived
+const PLACEHOLDERS: [(&str, Placeholder); 46] = [
+    ("%year", Placeholder::Year),
+    ("%month", Placeholder::Month),
+    ("%day", Placeholder::Day),
+    ("%ctimeyear", Placeholder::CTimeYear),
+    ("%ctimemonth", Placeholder::CTimeMonth),
+    ("%ctimeday", Placeholder::CTimeDay),
+    ("%mtimeyear", Placeholder::MTimeYear),
+    ("%mtimemonth", Placeholder::MTimeMonth),
+    ("%mtimeday", Placeholder::MTimeDay),
+    ("%timezone", Placeholder::Timezone),
+    ("%width", Placeholder::Width),
+    ("%height", Placeholder::Height),
+    ("%camera_model", Placeholder::CameraModel),
+    ("%camera_brand", Placeholder::CameraBrand),
+    ("%lens_model", Placeholder::LensModel),
+    ("%focal_length", Placeholder::FocalLength),
+    ("%focal_length_35mm", Placeholder::FocalLength35mm),
+    ("%aperture", Placeholder::Aperture),
+    ("%shutter_speed", Placeholder::ShutterSpeed),
+    ("%iso", Placeholder::Iso),
+    ("%flash", Placeholder::Flash),
+    ("%exif_orientation", Placeholder::ExifOrientation),
+    ("%software", Placeholder::Software),
+    ("%artist", Placeholder::Artist),
+    ("%copyright", Placeholder::Copyright),
+    ("%serial_number", Placeholder::SerialNumber),
+    ("%media_type", Placeholder::MediaType),
+    ("%has_gps", Placeholder::HasGps),
+    ("%orientation", Placeholder::Orientation),
+    ("%megapixels", Placeholder::Megapixels),
+    ("%resolution_class", Placeholder::ResolutionClass),
+    ("%country", Placeholder::Country),
+    ("%state", Placeholder::State),
+    ("%county", Placeholder::County),
+    ("%municipality", Placeholder::Municipality),
+    ("%city", Placeholder::City),
+    ("%original_folder", Placeholder::OriginalFolder),
+    ("%original_filename", Placeholder::OriginalFilename),
+    ("%original_stem", Placeholder::OriginalStem),
+    ("%extension", Placeholder::Extension),
+    ("%ext_lower", Placeholder::ExtLower),
+    ("%hash", Placeholder::Hash),
+    ("%size", Placeholder::Size),
+    ("%size_human", Placeholder::SizeHuman),
+    ("%counter", Placeholder::Counter),
+    ("%burst_id", Placeholder::BurstId),
+];
+
+/// Names of the known placeholders, used to suggest the closest one to a typo
+pub const PLACEHOLDER_NAMES: [&str; PLACEHOLDERS.len()] = {
+    let mut names = [""; PLACEHOLDERS.len()];
+    let mut index = 0;
+    while index < names.len() {
+        names[index] = PLACEHOLDERS[index].0;
+        index += 1;
+    }
+    names
+};
+
+/// Where the value of a placeholder comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataSource {
+    Exif,
+    Filesystem,
+    Geocoding,
+    Text,
+}
+
+impl fmt::Display for DataSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataSource::Exif => write!(f, "EXIF"),
+            DataSource::Filesystem => write!(f, "filesystem"),
+            DataSource::Geocoding => write!(f, "geocoding"),
+            DataSource::Text => write!(f, "text"),
+        }
+    }
+}
 
 // Configuration struct for the photo organizer
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub enum Placeholder {
     Year,
     Month,
@@ -12,10 +98,29 @@ pub enum Placeholder {
     MTimeYear,
     MTimeMonth,
     MTimeDay,
+    Timezone,
     Width,
     Height,
     CameraModel,
     CameraBrand,
+    LensModel,
+    FocalLength,
+    FocalLength35mm,
+    Aperture,
+    ShutterSpeed,
+    Iso,
+    Flash,
+    ExifOrientation,
+    Software,
+    Artist,
+    Copyright,
+    SerialNumber,
+    Exif(String),
+    MediaType,
+    HasGps,
+    Orientation,
+    Megapixels,
+    ResolutionClass,
     Country,
     State,
     County,
@@ -23,37 +128,31 @@ pub enum Placeholder {
     City,
     OriginalFolder,
     OriginalFilename,
+    OriginalStem,
+    Extension,
+    ExtLower,
+    Hash,
+    Size,
+    SizeHuman,
+    Counter,
+    BurstId,
     Unknown,
     Fallback,
 }
 
 impl Placeholder {
     pub fn from_string<S: Into<String>>(chain: S) -> Placeholder {
-        let format_string = chain.into();
-        let format_string_str = format_string.as_str();
-        match format_string_str {
-            "%year" => Placeholder::Year,
-            "%month" => Placeholder::Month,
-            "%day" => Placeholder::Day,
-            "%ctimeyear" => Placeholder::CTimeYear,
-            "%ctimemonth" => Placeholder::CTimeMonth,
-            "%ctimeday" => Placeholder::CTimeDay,
-            "%mtimeyear" => Placeholder::MTimeYear,
-            "%mtimemonth" => Placeholder::MTimeMonth,
-            "%mtimeday" => Placeholder::MTimeDay,
-            "%width" => Placeholder::Width,
-            "%height" => Placeholder::Height,
-            "%camera_model" => Placeholder::CameraModel,
-            "%camera_brand" => Placeholder::CameraBrand,
-            "%country" => Placeholder::Country,
-            "%state" => Placeholder::State,
-            "%county" => Placeholder::County,
-            "%municipality" => Placeholder::Municipality,
-            "%city" => Placeholder::City,
-            "%original_folder" => Placeholder::OriginalFolder,
-            "%original_filename" => Placeholder::OriginalFilename,
-            _ if format_string.starts_with('%') => Placeholder::Unknown,
-            _ => Placeholder::Fallback,
+        let (format_string, _) = split_placeholder_filters(&chain.into());
+        if let Some((_, placeholder)) = PLACEHOLDERS.iter().find(|(name, _)| *name == format_string)
+        {
+            placeholder.clone()
+        } else if format_string.len() > EXIF_PREFIX.len() && format_string.starts_with(EXIF_PREFIX)
+        {
+            Placeholder::Exif(format_string[EXIF_PREFIX.len()..].to_string())
+        } else if format_string.starts_with('%') {
+            Placeholder::Unknown
+        } else {
+            Placeholder::Fallback
         }
     }
 
@@ -62,10 +161,28 @@ impl Placeholder {
             Placeholder::Year
             | Placeholder::Month
             | Placeholder::Day
+            | Placeholder::Timezone
             | Placeholder::Width
             | Placeholder::Height
             | Placeholder::CameraModel
             | Placeholder::CameraBrand
+            | Placeholder::LensModel
+            | Placeholder::FocalLength
+            | Placeholder::FocalLength35mm
+            | Placeholder::Aperture
+            | Placeholder::ShutterSpeed
+            | Placeholder::Iso
+            | Placeholder::Flash
+            | Placeholder::ExifOrientation
+            | Placeholder::Software
+            | Placeholder::Artist
+            | Placeholder::Copyright
+            | Placeholder::SerialNumber
+            | Placeholder::Exif(_)
+            | Placeholder::HasGps
+            | Placeholder::Orientation
+            | Placeholder::Megapixels
+            | Placeholder::ResolutionClass
             | Placeholder::Country
             | Placeholder::State
             | Placeholder::County
@@ -81,7 +198,9 @@ impl Placeholder {
             | Placeholder::CTimeDay
             | Placeholder::MTimeYear
             | Placeholder::MTimeMonth
-            | Placeholder::MTimeDay => true,
+            | Placeholder::MTimeDay
+            | Placeholder::Size
+            | Placeholder::SizeHuman => true,
             _ => false,
         }
     }
@@ -95,17 +214,53 @@ impl Placeholder {
             _ => false,
         }
     }
+
+    /// The location placeholders need the GPS position from the EXIF data and a reverse geocoding provider
+    pub fn get_data_source(&self) -> DataSource {
+        match self {
+            Placeholder::Fallback | Placeholder::Unknown => DataSource::Text,
+            // The bursts are detected from the EXIF data before formatting the paths
+            Placeholder::BurstId => DataSource::Exif,
+            _ if self.is_location_related() => DataSource::Geocoding,
+            _ if self.is_exif_related() => DataSource::Exif,
+            _ => DataSource::Filesystem,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_placeholder_names() {
+        for name in PLACEHOLDER_NAMES {
+            assert_ne!(Placeholder::from_string(name), Placeholder::Unknown);
+        }
+        let mut names = PLACEHOLDER_NAMES.to_vec();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), PLACEHOLDER_NAMES.len());
+    }
+
+    #[test]
+    fn test_get_data_source() {
+        assert_eq!(Placeholder::Year.get_data_source(), DataSource::Exif);
+        assert_eq!(Placeholder::City.get_data_source(), DataSource::Geocoding);
+        assert_eq!(
+            Placeholder::MTimeYear.get_data_source(),
+            DataSource::Filesystem
+        );
+        assert_eq!(Placeholder::Hash.get_data_source(), DataSource::Filesystem);
+        assert_eq!(Placeholder::Fallback.get_data_source(), DataSource::Text);
+    }
+
     #[test]
     fn test_from_string() {
         assert_eq!(Placeholder::from_string("%year"), Placeholder::Year);
         assert_eq!(Placeholder::from_string("%month"), Placeholder::Month);
         assert_eq!(Placeholder::from_string("%day"), Placeholder::Day);
+        assert_eq!(Placeholder::from_string("%timezone"), Placeholder::Timezone);
         assert_eq!(Placeholder::from_string("%width"), Placeholder::Width);
         assert_eq!(Placeholder::from_string("%height"), Placeholder::Height);
         assert_eq!(
@@ -116,6 +271,53 @@ mod tests {
             Placeholder::from_string("%camera_brand"),
             Placeholder::CameraBrand
         );
+        assert_eq!(
+            Placeholder::from_string("%lens_model"),
+            Placeholder::LensModel
+        );
+        assert_eq!(
+            Placeholder::from_string("%focal_length_35mm"),
+            Placeholder::FocalLength35mm
+        );
+        assert_eq!(
+            Placeholder::from_string("%shutter_speed"),
+            Placeholder::ShutterSpeed
+        );
+        assert_eq!(Placeholder::from_string("%iso"), Placeholder::Iso);
+        assert_eq!(
+            Placeholder::from_string("%serial_number"),
+            Placeholder::SerialNumber
+        );
+        assert_eq!(
+            Placeholder::from_string("%exif:LensModel"),
+            Placeholder::Exif("LensModel".to_string())
+        );
+        assert_eq!(Placeholder::from_string("%exif:"), Placeholder::Unknown);
+        assert_eq!(
+            Placeholder::from_string("%exif:Make:lower"),
+            Placeholder::Exif("Make".to_string())
+        );
+        assert_eq!(
+            Placeholder::from_string("%camera_brand:lower"),
+            Placeholder::CameraBrand
+        );
+        assert_eq!(
+            Placeholder::from_string("%media_type"),
+            Placeholder::MediaType
+        );
+        assert_eq!(Placeholder::from_string("%has_gps"), Placeholder::HasGps);
+        assert_eq!(
+            Placeholder::from_string("%orientation"),
+            Placeholder::Orientation
+        );
+        assert_eq!(
+            Placeholder::from_string("%megapixels"),
+            Placeholder::Megapixels
+        );
+        assert_eq!(
+            Placeholder::from_string("%resolution_class"),
+            Placeholder::ResolutionClass
+        );
         assert_eq!(Placeholder::from_string("%country"), Placeholder::Country);
         assert_eq!(Placeholder::from_string("%state"), Placeholder::State);
         assert_eq!(Placeholder::from_string("%county"), Placeholder::County);
@@ -132,6 +334,21 @@ mod tests {
             Placeholder::from_string("%original_filename"),
             Placeholder::OriginalFilename
         );
+        assert_eq!(
+            Placeholder::from_string("%original_stem"),
+            Placeholder::OriginalStem
+        );
+        assert_eq!(
+            Placeholder::from_string("%ext_lower"),
+            Placeholder::ExtLower
+        );
+        assert_eq!(Placeholder::from_string("%hash"), Placeholder::Hash);
+        assert_eq!(
+            Placeholder::from_string("%size_human"),
+            Placeholder::SizeHuman
+        );
+        assert_eq!(Placeholder::from_string("%counter"), Placeholder::Counter);
+        assert_eq!(Placeholder::from_string("%burst_id"), Placeholder::BurstId);
         assert_eq!(
             Placeholder::from_string("%unknown_placeholder"),
             Placeholder::Unknown
@@ -0,0 +1,432 @@
+use clap::ArgMatches;
+use log::info;
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::errors::ClineupError;
+use crate::organizer::OrganizationMode;
+use crate::path::template::PlaceholderValues;
+use crate::utils::get_organization_strategy;
+use crate::utils::print_error;
+
+/// Separates the fallen back placeholders in the single column of a CSV plan
+const CSV_FALLBACKS_SEPARATOR: &str = ";";
+
+/// File format of a plan, given by its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlanFormat {
+    Json,
+    Csv,
+}
+
+impl PlanFormat {
+    pub fn from_path(path: &Path) -> Result<PlanFormat, ClineupError> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("json") => Ok(PlanFormat::Json),
+            Some("csv") => Ok(PlanFormat::Csv),
+            _ => Err(ClineupError::InvalidPlanFormat(
+                path.to_string_lossy().to_string(),
+            )),
+        }
+    }
+}
+
+/// Whether the destination of a file is already taken
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conflict {
+    None,
+    /// The destination exists before the organization
+    Exists,
+    /// The destination is planned for a previous file
+    Collision,
+}
+
+fn serialize_strategy<S: Serializer>(
+    strategy: &OrganizationMode,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(strategy.as_str())
+}
+
+fn deserialize_strategy<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<OrganizationMode, D::Error> {
+    let strategy = String::deserialize(deserializer)?;
+    OrganizationMode::from_string(&strategy).map_err(serde::de::Error::custom)
+}
+
+/// Planned organization of a single file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanEntry {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    #[serde(
+        serialize_with = "serialize_strategy",
+        deserialize_with = "deserialize_strategy"
+    )]
+    pub strategy: OrganizationMode,
+    /// Duplicates are kept in the plan but not organized
+    pub duplicate: bool,
+    pub conflict: Conflict,
+    /// Placeholders whose value is a fallback, like `{%city|Unknown}`
+    pub fallbacks: Vec<String>,
+}
+
+/// Row of a CSV plan, which has a single column for the fallbacks
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvPlanEntry {
+    source: PathBuf,
+    destination: PathBuf,
+    strategy: String,
+    duplicate: bool,
+    conflict: Conflict,
+    fallbacks: String,
+}
+
+impl From<&PlanEntry> for CsvPlanEntry {
+    fn from(entry: &PlanEntry) -> Self {
+        CsvPlanEntry {
+            source: entry.source.clone(),
+            destination: entry.destination.clone(),
+            strategy: entry.strategy.as_str().to_string(),
+            duplicate: entry.duplicate,
+            conflict: entry.conflict,
+            fallbacks: entry.fallbacks.join(CSV_FALLBACKS_SEPARATOR),
+        }
+    }
+}
+
+impl TryFrom<CsvPlanEntry> for PlanEntry {
+    type Error = ClineupError;
+
+    fn try_from(entry: CsvPlanEntry) -> Result<Self, Self::Error> {
+        Ok(PlanEntry {
+            source: entry.source,
+            destination: entry.destination,
+            strategy: OrganizationMode::from_string(&entry.strategy)?,
+            duplicate: entry.duplicate,
+            conflict: entry.conflict,
+            fallbacks: entry
+                .fallbacks
+                .split(CSV_FALLBACKS_SEPARATOR)
+                .filter(|fallback| !fallback.is_empty())
+                .map(|fallback| fallback.to_string())
+                .collect(),
+        })
+    }
+}
+
+/// Lists the placeholders whose value comes from a fallback, sorted as in the template.
+pub fn get_fallbacks(values: &PlaceholderValues) -> Vec<String> {
+    values
+        .iter()
+        .filter(|(_, value)| value.is_fallback() || value.chain_index() > 0)
+        .map(|(full_text, _)| full_text.clone())
+        .collect()
+}
+
+/// Complete mapping of the files to organize, written with `--plan-output`
+#[derive(Debug, Default)]
+pub struct Plan {
+    entries: Vec<PlanEntry>,
+    destinations: HashSet<PathBuf>,
+}
+
+impl Plan {
+    pub fn new() -> Self {
+        Plan::default()
+    }
+
+    pub fn entries(&self) -> &[PlanEntry] {
+        &self.entries
+    }
+
+    /// Adds a file to the plan, finding whether its destination is already taken.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The file to organize.
+    /// * `destination` - The full destination path of the file.
+    /// * `strategy` - The strategy of the rule the file matches.
+    /// * `duplicate` - Whether the file is a duplicate of a previous file.
+    /// * `fallbacks` - The placeholders whose value is a fallback.
+    pub fn add(
+        &mut self,
+        source: &Path,
+        destination: &Path,
+        strategy: OrganizationMode,
+        duplicate: bool,
+        fallbacks: Vec<String>,
+    ) {
+        let conflict = if destination.exists() {
+            Conflict::Exists
+        } else if !duplicate && !self.destinations.insert(destination.to_path_buf()) {
+            Conflict::Collision
+        } else {
+            Conflict::None
+        };
+
+        self.entries.push(PlanEntry {
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            strategy,
+            duplicate,
+            conflict,
+            fallbacks,
+        });
+    }
+
+    /// Writes the plan as JSON or CSV, depending on the extension of the path.
+    pub fn write(&self, path: &Path) -> Result<(), ClineupError> {
+        match PlanFormat::from_path(path)? {
+            PlanFormat::Json => {
+                serde_json::to_writer_pretty(File::create(path)?, &self.entries)?;
+            }
+            PlanFormat::Csv => {
+                let mut writer = csv::Writer::from_path(path)?;
+                for entry in &self.entries {
+                    writer.serialize(CsvPlanEntry::from(entry))?;
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads a plan written with `--plan-output`, possibly edited by hand.
+pub fn read_plan(path: &Path) -> Result<Vec<PlanEntry>, ClineupError> {
+    match PlanFormat::from_path(path)? {
+        PlanFormat::Json => Ok(serde_json::from_reader(File::open(path)?)?),
+        PlanFormat::Csv => csv::Reader::from_path(path)?
+            .deserialize::<CsvPlanEntry>()
+            .map(|entry| PlanEntry::try_from(entry?))
+            .collect(),
+    }
+}
+
+/// Outcome of the organization of a plan
+#[derive(Debug, Default, PartialEq)]
+pub struct ApplyReport {
+    /// Files organized
+    pub processed: usize,
+    /// Entries whose destination is taken, which were not resolved while reviewing the plan
+    pub conflicts: usize,
+    /// Entries whose source no longer exists
+    pub missing: usize,
+    /// Entries which could not be organized, like when the destination was created since the plan
+    pub failed: usize,
+}
+
+impl ApplyReport {
+    /// Describes the entries which were skipped or failed, if any
+    pub fn skipped_report(&self) -> Option<String> {
+        (self.conflicts + self.missing + self.failed > 0).then(|| {
+            format!(
+                "Skipped {} conflicting file(s), {} missing source(s) and {} failed file(s).",
+                self.conflicts, self.missing, self.failed
+            )
+        })
+    }
+}
+
+/// Organizes the files exactly as planned.
+/// The duplicates and the entries with a conflict are skipped, the conflict having to be
+/// set to `none` once the destination is fixed while reviewing the plan.
+///
+/// # Returns
+///
+/// The number of files processed, skipped and failed.
+pub fn apply_plan(entries: &[PlanEntry]) -> ApplyReport {
+    let mut report = ApplyReport::default();
+
+    for entry in entries {
+        if entry.duplicate {
+            info!("Skip duplicate {:?}", entry.source.display());
+            continue;
+        }
+        if entry.conflict != Conflict::None {
+            warn!(
+                "Skip {:?}, its destination {:?} conflicts: {:?}",
+                entry.source.display(),
+                entry.destination.display(),
+                entry.conflict
+            );
+            report.conflicts += 1;
+            continue;
+        }
+        if !entry.source.exists() {
+            warn!("Skip {:?}, which no longer exists", entry.source.display());
+            report.missing += 1;
+            continue;
+        }
+        // The strategy is always set, so that the organization strategy always exists
+        let is_organized = get_organization_strategy(Some(&entry.strategy))
+            .unwrap()
+            .organize(&entry.source, &entry.destination);
+        if is_organized {
+            report.processed += 1;
+        } else {
+            report.failed += 1;
+        }
+    }
+    report
+}
+
+/// Runs `clineup apply --plan <FILE>`, then exits.
+pub fn run_apply_command(matches: &ArgMatches) -> ! {
+    let plan_path = Path::new(matches.value_of("plan").unwrap());
+    let entries = read_plan(plan_path).unwrap_or_else(print_error);
+    let report = apply_plan(&entries);
+    println!("Done. {:?} file(s) processed.", report.processed);
+    if let Some(skipped_report) = report.skipped_report() {
+        println!("{}", skipped_report);
+    }
+    exit(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn plan(destination: &Path) -> Plan {
+        let mut plan = Plan::new();
+        plan.add(
+            Path::new("a/IMG_0001.jpg"),
+            &destination.join("2023/IMG_0001.jpg"),
+            OrganizationMode::Copy,
+            false,
+            vec![],
+        );
+        plan.add(
+            Path::new("b/IMG_0001.jpg"),
+            &destination.join("2023/IMG_0001.jpg"),
+            OrganizationMode::Move,
+            false,
+            vec!["{%city|Unknown}".to_string(), "%year".to_string()],
+        );
+        plan.add(
+            Path::new("b/IMG_0002.jpg"),
+            &destination.join("2023/IMG_0001.jpg"),
+            OrganizationMode::Symlinks,
+            true,
+            vec![],
+        );
+        plan.add(
+            Path::new("c/existing.jpg"),
+            destination,
+            OrganizationMode::Copy,
+            false,
+            vec![],
+        );
+        plan
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let tmp_dir = TempDir::new("plan").unwrap();
+        let plan = plan(tmp_dir.path());
+        let conflicts: Vec<Conflict> = plan.entries().iter().map(|e| e.conflict).collect();
+        assert_eq!(
+            conflicts,
+            vec![
+                Conflict::None,
+                Conflict::Collision,
+                Conflict::None,
+                Conflict::Exists
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_plan() {
+        let tmp_dir = TempDir::new("plan").unwrap();
+        let source = tmp_dir.path().join("source");
+        std::fs::create_dir_all(&source).unwrap();
+        for name in ["a.jpg", "b.jpg", "c.jpg"] {
+            std::fs::write(source.join(name), name).unwrap();
+        }
+        let destination = tmp_dir.path().join("destination");
+        std::fs::create_dir_all(&destination).unwrap();
+        std::fs::write(destination.join("taken.jpg"), "taken").unwrap();
+
+        let entry = |source: &Path, destination: PathBuf, conflict: Conflict| PlanEntry {
+            source: source.to_path_buf(),
+            destination,
+            strategy: OrganizationMode::Copy,
+            duplicate: false,
+            conflict,
+            fallbacks: vec![],
+        };
+        let entries = vec![
+            entry(
+                &source.join("a.jpg"),
+                destination.join("a.jpg"),
+                Conflict::None,
+            ),
+            entry(
+                &source.join("b.jpg"),
+                destination.join("a.jpg"),
+                Conflict::Collision,
+            ),
+            entry(
+                &source.join("missing.jpg"),
+                destination.join("missing.jpg"),
+                Conflict::None,
+            ),
+            // Taken since the plan was written
+            entry(
+                &source.join("c.jpg"),
+                destination.join("taken.jpg"),
+                Conflict::None,
+            ),
+        ];
+
+        assert_eq!(
+            apply_plan(&entries),
+            ApplyReport {
+                processed: 1,
+                conflicts: 1,
+                missing: 1,
+                failed: 1
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(destination.join("a.jpg")).unwrap(),
+            "a.jpg"
+        );
+        assert_eq!(
+            std::fs::read_to_string(destination.join("taken.jpg")).unwrap(),
+            "taken"
+        );
+    }
+
+    #[test]
+    fn test_write_and_read_plan() {
+        let tmp_dir = TempDir::new("plan").unwrap();
+        let plan = plan(tmp_dir.path());
+
+        for file_name in ["plan.json", "plan.csv"] {
+            let plan_path = tmp_dir.path().join(file_name);
+            plan.write(&plan_path).unwrap();
+            assert_eq!(read_plan(&plan_path).unwrap(), plan.entries());
+        }
+
+        let plan_path = tmp_dir.path().join("plan.csv");
+        let content = std::fs::read_to_string(plan_path).unwrap();
+        assert!(content.starts_with("source,destination,strategy,duplicate,conflict,fallbacks\n"));
+        assert!(content.contains(",move,false,collision,{%city|Unknown};%year\n"));
+
+        assert!(plan.write(&tmp_dir.path().join("plan.txt")).is_err());
+    }
+}
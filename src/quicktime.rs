@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+
+use crate::errors::ClineupError;
+
+/// Largest `moov` box read, the metadata of the videos being much smaller
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Box of an ISO base media file, like `moov`, with its content
+struct Atom<'a> {
+    kind: &'a [u8],
+    content: &'a [u8],
+}
+
+/// Splits the content of a box into its children, ignoring a truncated last one
+fn get_atoms(mut data: &[u8]) -> Vec<Atom<'_>> {
+    let mut atoms = Vec::new();
+    while data.len() >= 8 {
+        let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+        let (header_size, size) = match size {
+            0 => (8, data.len()),
+            1 if data.len() >= 16 => (
+                16,
+                u64::from_be_bytes(data[8..16].try_into().unwrap()) as usize,
+            ),
+            _ => (8, size),
+        };
+        if size < header_size || size > data.len() {
+            break;
+        }
+        atoms.push(Atom {
+            kind: &data[4..8],
+            content: &data[header_size..size],
+        });
+        data = &data[size..];
+    }
+    atoms
+}
+
+pub(crate) fn find_atom<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    get_atoms(data)
+        .into_iter()
+        .find(|atom| atom.kind == kind)
+        .map(|atom| atom.content)
+}
+
+/// Reads the `moov` box of a video, which holds the description of its tracks and its metadata.
+///
+/// # Arguments
+///
+/// * `path` - The video, in the mp4 or QuickTime format.
+///
+/// # Returns
+///
+/// The content of the `moov` box, or `None` if there is none or if it is too large.
+pub fn read_moov(path: &Path) -> Result<Option<Vec<u8>>, ClineupError> {
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    let mut position = 0;
+    let mut header = [0u8; 16];
+
+    while position + 8 <= file_size {
+        file.seek(SeekFrom::Start(position))?;
+        file.read_exact(&mut header[..8])?;
+        let (header_size, size) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            0 => (8, file_size - position),
+            1 => {
+                file.read_exact(&mut header[8..16])?;
+                (16, u64::from_be_bytes(header[8..16].try_into().unwrap()))
+            }
+            size => (8, size as u64),
+        };
+        if size < header_size {
+            break;
+        }
+        if &header[4..8] == b"moov" {
+            if size > MAX_MOOV_SIZE {
+                return Ok(None);
+            }
+            let mut moov = vec![0u8; (size - header_size) as usize];
+            file.read_exact(&mut moov)?;
+            return Ok(Some(moov));
+        }
+        position += size;
+    }
+    Ok(None)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Reads the displayed dimensions of a track from its `tkhd` box.
+/// A track rotated by a quarter turn, like a portrait video of a phone, has its dimensions swapped.
+///
+/// # Arguments
+///
+/// * `tkhd` - The content of the `tkhd` box.
+///
+/// # Returns
+///
+/// The width and the height, or `None` for a track without picture, like an audio one.
+fn parse_track_dimensions(tkhd: &[u8]) -> Option<(f32, f32)> {
+    // The dates and the duration are written on 64 bits from the version 1
+    let matrix_offset = match tkhd.first()? {
+        1 => 52,
+        _ => 40,
+    };
+    // Fixed-point numbers, 16.16 like the dimensions
+    let a = read_u32(tkhd, matrix_offset)? as i32;
+    let b = read_u32(tkhd, matrix_offset + 4)? as i32;
+    let width = read_u32(tkhd, matrix_offset + 36)? as f32 / 65536.0;
+    let height = read_u32(tkhd, matrix_offset + 40)? as f32 / 65536.0;
+    if width == 0.0 || height == 0.0 {
+        return None;
+    }
+    if a == 0 && b != 0 {
+        Some((height, width))
+    } else {
+        Some((width, height))
+    }
+}
+
+/// Reads the displayed dimensions of the first video track of a `moov` box.
+pub fn parse_video_dimensions(moov: &[u8]) -> Option<(f32, f32)> {
+    get_atoms(moov)
+        .into_iter()
+        .filter(|atom| atom.kind == b"trak")
+        .find_map(|trak| parse_track_dimensions(find_atom(trak.content, b"tkhd")?))
+}
+
+/// Reads the displayed dimensions of a video from the header of its first video track.
+pub fn get_video_dimensions(path: &Path) -> Result<(f32, f32), ClineupError> {
+    read_moov(path)?
+        .and_then(|moov| parse_video_dimensions(&moov))
+        .ok_or_else(|| ClineupError::MissingVideoTrack(path.to_string_lossy().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(kind: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut atom = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        atom.extend_from_slice(kind);
+        atom.extend_from_slice(content);
+        atom
+    }
+
+    /// Header of a track of version 0, with the transformation matrix given by its first two terms
+    fn tkhd(width: u32, height: u32, a: i32, b: i32) -> Vec<u8> {
+        let mut tkhd = vec![0u8; 40];
+        for value in [a << 16, b << 16, 0, -b << 16, a << 16, 0, 0, 0, 1 << 30] {
+            tkhd.extend_from_slice(&value.to_be_bytes());
+        }
+        tkhd.extend_from_slice(&(width << 16).to_be_bytes());
+        tkhd.extend_from_slice(&(height << 16).to_be_bytes());
+        atom(b"tkhd", &tkhd)
+    }
+
+    #[test]
+    fn test_parse_video_dimensions() {
+        let moov = |tracks: &[Vec<u8>]| {
+            [
+                atom(b"mvhd", &[0; 100]),
+                tracks
+                    .iter()
+                    .flat_map(|tkhd| atom(b"trak", tkhd))
+                    .collect::<Vec<u8>>(),
+            ]
+            .concat()
+        };
+
+        // The audio track has no picture
+        assert_eq!(
+            parse_video_dimensions(&moov(&[tkhd(0, 0, 1, 0), tkhd(1920, 1080, 1, 0)])),
+            Some((1920.0, 1080.0))
+        );
+        // A portrait video of a phone is a landscape track turned by a quarter
+        assert_eq!(
+            parse_video_dimensions(&moov(&[tkhd(1920, 1080, 0, 1)])),
+            Some((1080.0, 1920.0))
+        );
+        assert_eq!(parse_video_dimensions(&moov(&[tkhd(0, 0, 1, 0)])), None);
+        assert_eq!(parse_video_dimensions(&[]), None);
+    }
+}
@@ -0,0 +1,231 @@
+use log::debug;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cli::convert_size_to_bytes;
+use crate::errors::ClineupError;
+use crate::exif_extractor::ExifExtractor;
+use crate::media_type::get_media_type;
+use crate::media_type::MediaType;
+use crate::organizer::OrganizationMode;
+use crate::path::iterator::is_allowed_extension;
+use crate::path::iterator::is_allowed_size;
+
+/// Raw rule as written in the configuration file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    name: Option<String>,
+    extensions: Option<Vec<String>>,
+    regex: Option<String>,
+    size_greater: Option<String>,
+    size_lower: Option<String>,
+    has_exif: Option<bool>,
+    camera_model: Option<String>,
+    media_types: Option<Vec<MediaType>>,
+    folder_format: Option<String>,
+    filename_format: Option<String>,
+    strategy: Option<String>,
+}
+
+/// Routes the files matching every condition to their own formats and strategy.
+/// A rule without any condition matches every file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawRule")]
+pub struct Rule {
+    pub name: Option<String>,
+    extensions: Option<Vec<String>>,
+    regex: Option<Regex>,
+    size_greater: Option<u64>,
+    size_lower: Option<u64>,
+    has_exif: Option<bool>,
+    camera_model: Option<String>,
+    media_types: Option<Vec<MediaType>>,
+    pub folder_format: Option<String>,
+    pub filename_format: Option<String>,
+    pub strategy: Option<OrganizationMode>,
+}
+
+impl TryFrom<RawRule> for Rule {
+    type Error = ClineupError;
+
+    fn try_from(raw: RawRule) -> Result<Self, Self::Error> {
+        if raw.folder_format.is_none() && raw.filename_format.is_none() {
+            return Err(ClineupError::InvalidRule(format!(
+                "{} must set folder_format or filename_format",
+                raw.name.as_deref().unwrap_or("rule")
+            )));
+        }
+
+        Ok(Rule {
+            name: raw.name,
+            extensions: raw.extensions.map(|extensions| {
+                extensions
+                    .iter()
+                    .map(|e| e.replace('.', "").to_ascii_lowercase())
+                    .collect()
+            }),
+            regex: raw.regex.as_deref().map(Regex::new).transpose()?,
+            size_greater: raw
+                .size_greater
+                .as_deref()
+                .map(convert_size_to_bytes)
+                .transpose()?,
+            size_lower: raw
+                .size_lower
+                .as_deref()
+                .map(convert_size_to_bytes)
+                .transpose()?,
+            has_exif: raw.has_exif,
+            camera_model: raw.camera_model,
+            media_types: raw.media_types,
+            folder_format: raw.folder_format,
+            filename_format: raw.filename_format,
+            strategy: raw
+                .strategy
+                .as_deref()
+                .map(OrganizationMode::from_string)
+                .transpose()?,
+        })
+    }
+}
+
+impl Rule {
+    /// Checks whether the file matches every condition of the rule.
+    /// The cheapest conditions are checked first, the EXIF data being read last.
+    pub fn matches(&self, path: &Path) -> bool {
+        let entry = PathBuf::from(path);
+
+        if self.extensions.is_some() && !is_allowed_extension(&entry, &self.extensions, &None) {
+            return false;
+        }
+
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&entry.to_string_lossy()) {
+                return false;
+            }
+        }
+
+        if (self.size_greater.is_some() || self.size_lower.is_some())
+            && !is_allowed_size(&entry, &self.size_lower, &self.size_greater).unwrap_or(false)
+        {
+            return false;
+        }
+
+        if let Some(media_types) = &self.media_types {
+            if !media_types.contains(&get_media_type(path)) {
+                return false;
+            }
+        }
+
+        if self.has_exif.is_none() && self.camera_model.is_none() {
+            return true;
+        }
+
+        let exif_extractor = ExifExtractor::new(&entry);
+        if let Some(has_exif) = self.has_exif {
+            if exif_extractor.is_ok() != has_exif {
+                return false;
+            }
+        }
+
+        if let Some(camera_model) = &self.camera_model {
+            let is_same_model = exif_extractor
+                .and_then(|exif_extractor| exif_extractor.get_camera_model())
+                .is_ok_and(|model| model.trim().eq_ignore_ascii_case(camera_model.trim()));
+            if !is_same_model {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Finds the first rule matching the file.
+///
+/// # Arguments
+///
+/// * `rules` - The rules, in the order of the configuration file.
+/// * `path` - The path to the file.
+///
+/// # Returns
+///
+/// The index of the matching rule, or `None` if no rule matches.
+pub fn find_rule(rules: &[Rule], path: &Path) -> Option<usize> {
+    let index = rules.iter().position(|rule| rule.matches(path));
+    if let Some(index) = index {
+        debug!(
+            "File {:?} matches rule {}",
+            path,
+            rules[index].name.as_deref().unwrap_or(&index.to_string())
+        );
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(content: &str) -> Vec<Rule> {
+        #[derive(Deserialize)]
+        struct Rules {
+            rules: Vec<Rule>,
+        }
+        toml::from_str::<Rules>(content).unwrap().rules
+    }
+
+    fn data(file: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data")
+            .join(file)
+    }
+
+    #[test]
+    fn test_find_rule() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            name = "raw"
+            extensions = ["cr2", ".NEF"]
+            folder_format = "raw/%year"
+
+            [[rules]]
+            name = "without exif"
+            has_exif = false
+            media_types = ["photo"]
+            folder_format = "screens/%mtimeyear"
+            strategy = "move"
+
+            [[rules]]
+            camera_model = "RustTest"
+            size_greater = "1KB"
+            folder_format = "%camera_model/%year"
+
+            [[rules]]
+            folder_format = "%year/%month"
+            "#,
+        );
+
+        assert_eq!(find_rule(&rules, Path::new("IMG_0001.nef")), Some(0));
+        assert_eq!(find_rule(&rules, &data("Paris.png")), Some(1));
+        assert_eq!(find_rule(&rules, &data("Paris-20230304.jpg")), Some(2));
+        assert_eq!(find_rule(&rules, &data("empty.txt")), Some(3));
+        assert!(matches!(rules[1].strategy, Some(OrganizationMode::Move)));
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        let without_format = toml::from_str::<Rule>(r#"extensions = ["jpg"]"#);
+        assert!(without_format.is_err());
+
+        let invalid_strategy = toml::from_str::<Rule>(
+            r#"folder_format = "%year"
+            strategy = "hardlink""#,
+        );
+        assert!(invalid_strategy.is_err());
+    }
+}
@@ -0,0 +1,423 @@
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::errors::ClineupError;
+use crate::path::template::PlaceholderValues;
+use crate::placeholders::Placeholder;
+use crate::utils::format_size_human;
+
+/// How the summary of a dry run is printed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SummaryMode {
+    /// The planned destinations, then the statistics
+    Stats,
+    /// A tree of the destination folders instead of the planned destinations
+    Tree,
+}
+
+impl SummaryMode {
+    pub fn from_string(mode: &str) -> Result<SummaryMode, ClineupError> {
+        match mode {
+            "stats" => Ok(SummaryMode::Stats),
+            "tree" => Ok(SummaryMode::Tree),
+            _ => Err(ClineupError::InvalidSummaryMode(mode.to_string())),
+        }
+    }
+}
+
+/// Lists the names which come from a fallback, like `Unknown Year` or the fallback text of a chain.
+/// A placeholder of the chain used as a fallback, like `%country` in `{%city|%country}`, gives a real name.
+pub fn get_fallback_names(
+    values: &PlaceholderValues,
+    placeholders: &IndexMap<String, IndexMap<String, Placeholder>>,
+) -> Vec<String> {
+    values
+        .iter()
+        .filter(|(full_text, value)| {
+            value.is_fallback()
+                || placeholders
+                    .get(*full_text)
+                    .and_then(|chain| chain.get_index(value.chain_index()))
+                    .is_some_and(|(_, placeholder)| *placeholder == Placeholder::Fallback)
+        })
+        .map(|(_, value)| value.text().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Planned file, kept to build the tree of the destination
+#[derive(Debug)]
+struct SummaryFile {
+    destination: PathBuf,
+    size: u64,
+    fallback_names: Vec<String>,
+}
+
+/// Folder of the tree of the destination, its counts including the subfolders
+#[derive(Debug, Default)]
+struct FolderNode {
+    files: usize,
+    size: u64,
+    is_fallback: bool,
+    children: BTreeMap<String, FolderNode>,
+}
+
+impl FolderNode {
+    fn add(&mut self, size: u64) {
+        self.files += 1;
+        self.size += size;
+    }
+
+    fn direct_files(&self) -> usize {
+        self.files
+            - self
+                .children
+                .values()
+                .map(|child| child.files)
+                .sum::<usize>()
+    }
+
+    fn describe(&self) -> String {
+        format!(
+            "{}({} file(s), {})",
+            if self.is_fallback { "[fallback] " } else { "" },
+            self.files,
+            format_size_human(self.size)
+        )
+    }
+
+    /// Prints the subfolders, the chains of folders without files being collapsed like `2023/05/Paris`.
+    fn render(&self, prefix: &str, report: &mut String) {
+        let last_index = self.children.len().saturating_sub(1);
+        for (index, (name, child)) in self.children.iter().enumerate() {
+            let mut name = name.clone();
+            let mut child = child;
+            let mut is_fallback = child.is_fallback;
+            while child.children.len() == 1 && child.direct_files() == 0 {
+                let (grandchild_name, grandchild) = child.children.iter().next().unwrap();
+                name = format!("{}/{}", name, grandchild_name);
+                is_fallback |= grandchild.is_fallback;
+                child = grandchild;
+            }
+
+            let (branch, indent) = if index == last_index {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            report.push_str(&format!(
+                "{}{}{} {}{}\n",
+                prefix,
+                branch,
+                name,
+                if is_fallback && !child.is_fallback {
+                    "[fallback] "
+                } else {
+                    ""
+                },
+                child.describe()
+            ));
+            child.render(&format!("{}{}", prefix, indent), report);
+        }
+    }
+}
+
+/// Statistics of a dry run, printed once every file is processed
+#[derive(Debug, Default)]
+pub struct Summary {
+    files: Vec<SummaryFile>,
+    fallbacks: IndexMap<String, usize>,
+    files_with_fallback: usize,
+    duplicates: usize,
+    dropped_burst_frames: usize,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Summary::default()
+    }
+
+    /// Counts a file planned to `destination`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The file to organize, whose size is counted.
+    /// * `destination` - The full destination path of the file.
+    /// * `fallbacks` - The placeholders which fell back.
+    /// * `fallback_names` - The names which come from a fallback, to highlight their folders.
+    pub fn add_file(
+        &mut self,
+        source: &Path,
+        destination: &Path,
+        fallbacks: &[String],
+        fallback_names: Vec<String>,
+    ) {
+        if !fallbacks.is_empty() {
+            self.files_with_fallback += 1;
+        }
+        for fallback in fallbacks {
+            *self.fallbacks.entry(fallback.clone()).or_insert(0) += 1;
+        }
+
+        self.files.push(SummaryFile {
+            destination: destination.to_path_buf(),
+            size: std::fs::metadata(source).map_or(0, |metadata| metadata.len()),
+            fallback_names,
+        });
+    }
+
+    pub fn add_duplicate(&mut self) {
+        self.duplicates += 1;
+    }
+
+    /// Counts a frame of a burst which is not kept, with `--bursts first` or `--bursts sharpest`
+    pub fn add_dropped_burst_frame(&mut self) {
+        self.dropped_burst_frames += 1;
+    }
+
+    fn get_files_per_folder(&self) -> BTreeMap<&Path, usize> {
+        let mut files_per_folder = BTreeMap::new();
+        for file in &self.files {
+            let folder = file.destination.parent().unwrap_or(Path::new(""));
+            *files_per_folder.entry(folder).or_insert(0) += 1;
+        }
+        files_per_folder
+    }
+
+    fn get_tree(&self, destination: &Path) -> (FolderNode, usize) {
+        let mut root = FolderNode::default();
+        let mut files_in_fallback_folders = 0;
+
+        for file in &self.files {
+            let relative_path = file
+                .destination
+                .strip_prefix(destination)
+                .unwrap_or(&file.destination);
+            let folders = relative_path.parent().unwrap_or(Path::new(""));
+
+            root.add(file.size);
+            let mut node = &mut root;
+            let mut is_in_fallback_folder = false;
+            for folder in folders.iter() {
+                let folder = folder.to_string_lossy().to_string();
+                // A whole folder name, a short fallback like `-` being part of many names
+                let is_fallback = file.fallback_names.contains(&folder);
+                node = node.children.entry(folder).or_default();
+                node.add(file.size);
+                node.is_fallback |= is_fallback;
+                is_in_fallback_folder |= is_fallback;
+            }
+            if is_in_fallback_folder {
+                files_in_fallback_folders += 1;
+            }
+        }
+        (root, files_in_fallback_folders)
+    }
+
+    fn push_fallbacks_and_duplicates(&self, report: &mut String) {
+        report.push_str(&format!(
+            "Files with a fallback: {}\n",
+            self.files_with_fallback
+        ));
+        let mut fallbacks: Vec<(&String, &usize)> = self.fallbacks.iter().collect();
+        fallbacks.sort_by(|(_, left), (_, right)| right.cmp(left));
+        for (placeholder, count) in fallbacks {
+            report.push_str(&format!("    {}: {}\n", placeholder, count));
+        }
+
+        report.push_str(&format!("Duplicates: {}\n", self.duplicates));
+        if self.dropped_burst_frames > 0 {
+            report.push_str(&format!(
+                "Dropped burst frames: {}\n",
+                self.dropped_burst_frames
+            ));
+        }
+    }
+
+    /// Builds the report, with the folders sorted by path and the fallbacks by count.
+    pub fn report(&self) -> String {
+        let mut report = format!("Summary of {} file(s)\n", self.files.len());
+
+        let files_per_folder = self.get_files_per_folder();
+        report.push_str(&format!(
+            "Files per destination folder ({}):\n",
+            files_per_folder.len()
+        ));
+        for (folder, count) in files_per_folder {
+            report.push_str(&format!("    {}: {}\n", folder.display(), count));
+        }
+
+        self.push_fallbacks_and_duplicates(&mut report);
+        report
+    }
+
+    /// Builds the tree of the destination folders, with their number of files and total size.
+    /// The folders named after a fallback, like `Unknown Year`, are highlighted.
+    pub fn tree_report(&self, destination: &Path) -> String {
+        let (root, files_in_fallback_folders) = self.get_tree(destination);
+
+        let mut report = format!("{} {}\n", destination.display(), root.describe());
+        root.render("", &mut report);
+
+        report.push_str(&format!(
+            "Files in fallback folders: {}\n",
+            files_in_fallback_folders
+        ));
+        self.push_fallbacks_and_duplicates(&mut report);
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::parser::map_placeholders_to_enums;
+    use crate::path::parser::parse_placeholders;
+    use crate::path::template::PlaceholderValue;
+
+    fn data(file: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data")
+            .join(file)
+    }
+
+    fn summary() -> Summary {
+        let mut summary = Summary::new();
+        summary.add_file(
+            &data("Paris-20230304.jpg"),
+            Path::new("/photos/2023/03/Paris/IMG_0001.jpg"),
+            &[],
+            vec![],
+        );
+        summary.add_file(
+            &data("London-20230102.jpg"),
+            Path::new("/photos/2023/01/London/IMG_0002.jpg"),
+            &[],
+            vec![],
+        );
+        summary.add_file(
+            &data("empty.txt"),
+            Path::new("/photos/Unknown Year/notes.txt"),
+            &["{%year|Unknown}".to_string(), "%camera_model".to_string()],
+            vec![
+                "Unknown Year".to_string(),
+                "Unknown Camera Model".to_string(),
+            ],
+        );
+        summary.add_file(
+            &data("5384_bytes.png"),
+            Path::new("/photos/Unknown Year/IMG_0003.png"),
+            &["{%year|Unknown}".to_string()],
+            vec!["Unknown Year".to_string()],
+        );
+        summary.add_duplicate();
+        summary
+    }
+
+    #[test]
+    fn test_report() {
+        assert_eq!(
+            summary().report(),
+            "Summary of 4 file(s)\n\
+            Files per destination folder (3):\n    /photos/2023/01/London: 1\n    /photos/2023/03/Paris: 1\n    /photos/Unknown Year: 2\n\
+            Files with a fallback: 2\n    {%year|Unknown}: 2\n    %camera_model: 1\n\
+            Duplicates: 1\n"
+        );
+    }
+
+    #[test]
+    fn test_tree_report() {
+        let report = summary().tree_report(Path::new("/photos"));
+        let size = |files: &[&str]| {
+            format_size_human(
+                files
+                    .iter()
+                    .map(|file| std::fs::metadata(data(file)).unwrap().len())
+                    .sum(),
+            )
+        };
+        assert_eq!(
+            report,
+            format!(
+                "/photos (4 file(s), {})\n\
+                ├── 2023 (2 file(s), {})\n\
+                │   ├── 01/London (1 file(s), {})\n\
+                │   └── 03/Paris (1 file(s), {})\n\
+                └── Unknown Year [fallback] (2 file(s), {})\n\
+                Files in fallback folders: 2\n\
+                Files with a fallback: 2\n    {{%year|Unknown}}: 2\n    %camera_model: 1\n\
+                Duplicates: 1\n",
+                size(&[
+                    "Paris-20230304.jpg",
+                    "London-20230102.jpg",
+                    "empty.txt",
+                    "5384_bytes.png"
+                ]),
+                size(&["Paris-20230304.jpg", "London-20230102.jpg"]),
+                size(&["London-20230102.jpg"]),
+                size(&["Paris-20230304.jpg"]),
+                size(&["empty.txt", "5384_bytes.png"]),
+            )
+        );
+    }
+
+    #[test]
+    fn test_tree_report_short_fallback() {
+        let mut summary = Summary::new();
+        summary.add_file(
+            &data("Paris-20230304.jpg"),
+            Path::new("/photos/2023-03/X/IMG_0001.jpg"),
+            &["{%city|X}".to_string()],
+            vec!["X".to_string()],
+        );
+        summary.add_file(
+            &data("London-20230102.jpg"),
+            Path::new("/photos/2023-01/-/Xmas/IMG_0002.jpg"),
+            &["{%city|-}".to_string()],
+            vec!["-".to_string()],
+        );
+        let report = summary.tree_report(Path::new("/photos"));
+
+        assert!(report.contains("2023-03/X [fallback] (1 file(s), "));
+        assert!(report.contains("2023-01/-/Xmas [fallback] (1 file(s), "));
+        assert!(!report.contains("2023-03 [fallback]"));
+        assert!(report.contains("Files in fallback folders: 2\n"));
+
+        let mut summary = Summary::new();
+        summary.add_file(
+            &data("Paris-20230304.jpg"),
+            Path::new("/photos/2023-03/Xmas/IMG_0001.jpg"),
+            &["{%camera_model|-}".to_string()],
+            vec!["-".to_string(), "X".to_string()],
+        );
+        let report = summary.tree_report(Path::new("/photos"));
+        assert!(!report.contains("[fallback]"));
+        assert!(report.contains("Files in fallback folders: 0\n"));
+    }
+
+    #[test]
+    fn test_get_fallback_names() {
+        let placeholders = map_placeholders_to_enums(
+            &parse_placeholders("%year/{%city|%country}/{%camera_model|No model}").unwrap(),
+        );
+        let mut values = PlaceholderValues::new();
+        values.insert(
+            "%year".to_string(),
+            PlaceholderValue::new("Unknown Year".to_string(), true),
+        );
+        values.insert(
+            "{%city|%country}".to_string(),
+            PlaceholderValue::new("France".to_string(), false).with_chain_index(1),
+        );
+        values.insert(
+            "{%camera_model|No model}".to_string(),
+            PlaceholderValue::new("No model".to_string(), false).with_chain_index(1),
+        );
+        assert_eq!(
+            get_fallback_names(&values, &placeholders),
+            vec!["Unknown Year", "No model"]
+        );
+    }
+}
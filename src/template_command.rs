@@ -0,0 +1,277 @@
+use clap::ArgMatches;
+use indexmap::IndexMap;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::cli::get_geocoding_enum;
+use crate::errors::ClineupError;
+use crate::gps::gpsenum::GpsResolutionProviderImpl;
+use crate::path::formatter::PathFormatter;
+use crate::path::formatter::ReverseGeocoder;
+use crate::path::formatter::COUNTER_MARKER;
+use crate::path::parser::check_placeholders;
+use crate::path::parser::map_placeholders_to_enums;
+use crate::path::parser::parse_template;
+use crate::placeholders::DataSource;
+use crate::placeholders::Placeholder;
+use crate::utils::get_reverse_geocoding_provider;
+use crate::utils::is_there_a_location_placeholder;
+use crate::utils::print_error;
+
+/// Checks that a reverse geocoding provider is set when the location placeholders are used.
+pub fn check_reverse_geocoding(
+    placeholders: &IndexMap<String, IndexMap<String, Placeholder>>,
+    reverse_geocoding: Option<&GpsResolutionProviderImpl>,
+) -> Result<(), ClineupError> {
+    if is_there_a_location_placeholder(placeholders) && reverse_geocoding.is_none() {
+        return Err(ClineupError::MissingReverseGeocoding);
+    }
+    Ok(())
+}
+
+/// Lists what is likely to give unexpected paths in a valid template.
+///
+/// # Arguments
+///
+/// * `placeholders` - The placeholders of the template.
+/// * `reverse_geocoding` - The reverse geocoding provider, if any.
+///
+/// # Returns
+///
+/// The warnings, empty if the template looks fine.
+pub fn get_template_warnings(
+    placeholders: &IndexMap<String, IndexMap<String, Placeholder>>,
+    reverse_geocoding: Option<&GpsResolutionProviderImpl>,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if let Err(err) = check_reverse_geocoding(placeholders, reverse_geocoding) {
+        warnings.push(err.to_string());
+    }
+
+    for (full_text, chain) in placeholders {
+        // A fallback text always has a value, so the rest of the chain is never used
+        let fallback_position = chain
+            .values()
+            .position(|placeholder| *placeholder == Placeholder::Fallback);
+        if let Some(position) = fallback_position.filter(|position| position + 1 < chain.len()) {
+            let (fallback_text, _) = chain.get_index(position).unwrap();
+            for (placeholder_text, _) in chain.iter().skip(position + 1) {
+                warnings.push(format!(
+                    "{} is never used in {} since it comes after the fallback text '{}'",
+                    placeholder_text, full_text, fallback_text
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+fn get_template_placeholders(
+    format: &str,
+) -> Result<IndexMap<String, IndexMap<String, Placeholder>>, ClineupError> {
+    let template = parse_template(format)?;
+    check_placeholders(format, &template)?;
+    Ok(map_placeholders_to_enums(&template.placeholders()))
+}
+
+/// Describes the placeholders of a template and where their values come from.
+///
+/// # Arguments
+///
+/// * `format` - The template to check.
+/// * `reverse_geocoding` - The reverse geocoding provider, if any.
+///
+/// # Returns
+///
+/// The report to print, or the syntax error of the template.
+pub fn describe_template(
+    format: &str,
+    reverse_geocoding: Option<&GpsResolutionProviderImpl>,
+) -> Result<String, ClineupError> {
+    let placeholders = get_template_placeholders(format)?;
+    let mut report = format!("Template {} is valid\n", format);
+
+    if placeholders.is_empty() {
+        report.push_str("No placeholder found\n");
+    }
+    for (full_text, chain) in &placeholders {
+        if chain.len() == 1 {
+            let (_, placeholder) = chain.first().unwrap();
+            report.push_str(&format!(
+                "{}: {}\n",
+                full_text,
+                placeholder.get_data_source()
+            ));
+            continue;
+        }
+        report.push_str(&format!("{}:\n", full_text));
+        for (placeholder_text, placeholder) in chain {
+            match placeholder.get_data_source() {
+                DataSource::Text => {
+                    report.push_str(&format!("    '{}': fallback text\n", placeholder_text))
+                }
+                data_source => {
+                    report.push_str(&format!("    {}: {}\n", placeholder_text, data_source))
+                }
+            }
+        }
+    }
+
+    for warning in get_template_warnings(&placeholders, reverse_geocoding) {
+        report.push_str(&format!("Warning: {}\n", warning));
+    }
+    Ok(report)
+}
+
+/// Resolves the destination of sample files, with the value of each placeholder.
+///
+/// # Arguments
+///
+/// * `format` - The template to preview, relative to the destination folder.
+/// * `files` - The sample files.
+/// * `reverse_geocoding` - The reverse geocoding provider, if any.
+/// * `nominatim_email` - The email required by Nominatim.
+///
+/// # Returns
+///
+/// The report to print, or the error preventing any file from being resolved.
+pub fn preview_template(
+    format: &str,
+    files: &[PathBuf],
+    reverse_geocoding: Option<&GpsResolutionProviderImpl>,
+    nominatim_email: Option<&String>,
+) -> Result<String, ClineupError> {
+    let placeholders = get_template_placeholders(format)?;
+    check_reverse_geocoding(&placeholders, reverse_geocoding)?;
+
+    let mut path_formatter = PathFormatter::new(
+        format,
+        &placeholders,
+        get_reverse_geocoding_provider(reverse_geocoding, nominatim_email)
+            .map(ReverseGeocoder::new),
+        false,
+    )?;
+    let mut report = String::new();
+
+    for file in files {
+        let (formatted_path, values) = match path_formatter.get_formatted_path_with_values(file) {
+            Ok(result) => result,
+            Err(err) => {
+                report.push_str(&format!("{}: {}\n", file.display(), err));
+                continue;
+            }
+        };
+        report.push_str(&format!(
+            "{} -> {}\n",
+            file.display(),
+            formatted_path.display()
+        ));
+
+        for (full_text, value) in &values {
+            let text = value.text().replace(COUNTER_MARKER, "<counter>");
+            let note = if value.is_fallback() {
+                " (fallback)".to_string()
+            } else if value.chain_index() > 0 {
+                match placeholders[full_text].get_index(value.chain_index()) {
+                    Some((_, Placeholder::Fallback)) => " (fallback text)".to_string(),
+                    Some((used, _)) => format!(" (fallback to {})", used),
+                    None => String::new(),
+                }
+            } else {
+                String::new()
+            };
+            report.push_str(&format!("    {} = {}{}\n", full_text, text, note));
+        }
+    }
+    Ok(report)
+}
+
+/// Runs `clineup template check` or `clineup template preview`, then exits.
+pub fn run_template_command(matches: &ArgMatches) -> ! {
+    let report = match matches.subcommand() {
+        ("check", Some(check_matches)) => {
+            let reverse_geocoding = get_geocoding_enum(check_matches.value_of("reverse-geocoding"))
+                .unwrap_or_else(print_error);
+            describe_template(
+                check_matches.value_of("format").unwrap(),
+                reverse_geocoding.as_ref(),
+            )
+        }
+        ("preview", Some(preview_matches)) => {
+            let reverse_geocoding =
+                get_geocoding_enum(preview_matches.value_of("reverse-geocoding"))
+                    .unwrap_or_else(print_error);
+            let files: Vec<PathBuf> = preview_matches
+                .values_of("files")
+                .unwrap()
+                .map(PathBuf::from)
+                .collect();
+            preview_template(
+                preview_matches.value_of("format").unwrap(),
+                &files,
+                reverse_geocoding.as_ref(),
+                preview_matches
+                    .value_of("nominatim-email")
+                    .map(|email| email.to_string())
+                    .as_ref(),
+            )
+        }
+        _ => unreachable!("clap requires a template subcommand"),
+    };
+
+    match report {
+        Ok(report) => {
+            print!("{}", report);
+            exit(0)
+        }
+        Err(err) => {
+            // Printed without the logger, which is only enabled with -v
+            println!("{}", err);
+            exit(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::path::parser::parse_placeholders;
+
+    fn placeholders(format: &str) -> IndexMap<String, IndexMap<String, Placeholder>> {
+        map_placeholders_to_enums(&parse_placeholders(format).unwrap())
+    }
+
+    #[test]
+    fn test_get_template_warnings() {
+        let location = placeholders("%year/{%city|%country|Unknown}");
+        assert_eq!(
+            get_template_warnings(&location, None),
+            vec!["Location tag found but reverse geocoding provider is not set"]
+        );
+        assert!(
+            get_template_warnings(&location, Some(&GpsResolutionProviderImpl::Nominatim))
+                .is_empty()
+        );
+
+        let unreachable = placeholders("{%camera_model|Unknown|%camera_brand}");
+        assert_eq!(
+            get_template_warnings(&unreachable, None),
+            vec!["%camera_brand is never used in {%camera_model|Unknown|%camera_brand} since it comes after the fallback text 'Unknown'"]
+        );
+    }
+
+    #[test]
+    fn test_describe_template() {
+        let report = describe_template("%year/{%city|Unknown}/%original_filename", None).unwrap();
+        assert_eq!(
+            report,
+            "Template %year/{%city|Unknown}/%original_filename is valid\n\
+            %year: EXIF\n\
+            {%city|Unknown}:\n    %city: geocoding\n    'Unknown': fallback text\n\
+            %original_filename: filesystem\n\
+            Warning: Location tag found but reverse geocoding provider is not set\n"
+        );
+        assert!(describe_template("%year/{%month", None).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use chrono::prelude::{DateTime, Local};
+use chrono::{Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use tzf_rs::DefaultFinder;
+
+use crate::errors::ClineupError;
+use crate::exif_extractor::ExifExtractor;
+
+const QUARTER_OF_AN_HOUR: i64 = 15 * 60;
+const MAX_OFFSET_SECONDS: i64 = 14 * 3600;
+
+/// Timezone in which the dates are expressed before computing the date placeholders
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimezoneTarget {
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+    /// Time zone of the GPS position, with its daylight saving time at the date of the media
+    Gps,
+}
+
+impl TimezoneTarget {
+    pub fn from_string<S: Into<String>>(chain: S) -> Result<TimezoneTarget, ClineupError> {
+        let timezone = chain.into();
+        match timezone.to_ascii_lowercase().as_str() {
+            "local" => Ok(TimezoneTarget::Local),
+            "utc" | "z" => Ok(TimezoneTarget::Utc),
+            "gps" => Ok(TimezoneTarget::Gps),
+            _ => parse_offset(&timezone)
+                .map(TimezoneTarget::Fixed)
+                .map_err(|_| ClineupError::InvalidTimezone(timezone)),
+        }
+    }
+}
+
+/// Parses an offset as written in the EXIF `OffsetTime*` tags.
+///
+/// # Arguments
+///
+/// * `offset` - The offset, like `+02:00`, `-0530` or `Z`.
+///
+/// # Returns
+///
+/// The corresponding `FixedOffset`, or an error if the offset is malformed.
+pub fn parse_offset(offset: &str) -> Result<FixedOffset, ClineupError> {
+    let trimmed = offset.trim();
+    if trimmed.eq_ignore_ascii_case("z") {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+
+    let invalid = || ClineupError::InvalidTimezone(offset.to_string());
+
+    let sign = match trimmed.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return Err(invalid()),
+    };
+    let digits: String = trimmed[1..].chars().filter(|c| *c != ':').collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.as_str(), "0"),
+        4 => digits.split_at(2),
+        _ => return Err(invalid()),
+    };
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if minutes >= 60 {
+        return Err(invalid());
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+/// Computes the offset between a local wall clock time and the UTC time of the same instant.
+/// The difference is rounded to the nearest quarter of an hour because the GPS time is
+/// usually recorded a few seconds apart from the camera clock.
+///
+/// # Returns
+///
+/// The offset, or `None` if the difference is not a plausible timezone offset.
+pub fn offset_from_utc_reference(
+    local: &NaiveDateTime,
+    utc: &NaiveDateTime,
+) -> Option<FixedOffset> {
+    let difference = local.signed_duration_since(*utc).num_seconds();
+    let rounded = (difference as f64 / QUARTER_OF_AN_HOUR as f64).round() as i64;
+    let offset = rounded * QUARTER_OF_AN_HOUR;
+
+    if offset.abs() > MAX_OFFSET_SECONDS {
+        return None;
+    }
+    FixedOffset::east_opt(offset as i32)
+}
+
+/// Finds the time zone of a position offline, from the time zone boundaries bundled in the binary.
+/// The boundaries are only loaded on the first lookup.
+///
+/// # Arguments
+///
+/// * `latitude` - The latitude, negative in the southern hemisphere.
+/// * `longitude` - The longitude, negative west of Greenwich.
+///
+/// # Returns
+///
+/// The time zone, or `None` if the position is not part of one, like at sea.
+pub fn timezone_from_position(latitude: f64, longitude: f64) -> Option<Tz> {
+    static FINDER: OnceLock<DefaultFinder> = OnceLock::new();
+    let finder = FINDER.get_or_init(DefaultFinder::new);
+    finder.get_tz_name(longitude, latitude).parse().ok()
+}
+
+/// Reads a GPS coordinate, negated when its reference is the given one, like `S` or `W`.
+fn get_signed_coordinate(
+    exif_extractor: &ExifExtractor,
+    tag: exif::Tag,
+    reference_tag: exif::Tag,
+    negative_reference: &str,
+) -> Result<f64, ClineupError> {
+    let coordinate = exif_extractor.get_gps_float_value(tag)? as f64;
+    let is_negative = exif_extractor
+        .get_string_value(reference_tag)
+        .is_ok_and(|reference| reference.eq_ignore_ascii_case(negative_reference));
+    Ok(if is_negative { -coordinate } else { coordinate })
+}
+
+/// Finds the time zone of the place where the media was taken from its GPS coordinates.
+pub fn get_location_timezone(exif_extractor: &ExifExtractor) -> Result<Tz, ClineupError> {
+    let latitude = get_signed_coordinate(
+        exif_extractor,
+        exif::Tag::GPSLatitude,
+        exif::Tag::GPSLatitudeRef,
+        "S",
+    )?;
+    let longitude = get_signed_coordinate(
+        exif_extractor,
+        exif::Tag::GPSLongitude,
+        exif::Tag::GPSLongitudeRef,
+        "W",
+    )?;
+    timezone_from_position(latitude, longitude).ok_or(ClineupError::MissingTimezone)
+}
+
+/// Builds the UTC date time from the EXIF `GPSDateStamp` and `GPSTimeStamp` values.
+pub fn get_gps_datetime(date: &str, time: &[f64]) -> Result<NaiveDateTime, ClineupError> {
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")?;
+    let seconds = time
+        .iter()
+        .zip([3600.0, 60.0, 1.0])
+        .map(|(value, multiplier)| value * multiplier)
+        .sum::<f64>()
+        .round() as i64;
+    Ok(date.and_time(NaiveTime::MIN) + Duration::seconds(seconds))
+}
+
+/// A wall clock date time along with its offset from UTC, when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureDate {
+    datetime: NaiveDateTime,
+    offset: Option<FixedOffset>,
+}
+
+impl CaptureDate {
+    pub fn new(datetime: NaiveDateTime, offset: Option<FixedOffset>) -> Self {
+        CaptureDate { datetime, offset }
+    }
+
+    pub fn datetime(&self) -> &NaiveDateTime {
+        &self.datetime
+    }
+
+    pub fn offset(&self) -> Option<&FixedOffset> {
+        self.offset.as_ref()
+    }
+
+    pub fn format(&self, fmt: &str) -> String {
+        self.datetime.format(fmt).to_string()
+    }
+
+    /// Returns the offset formatted as `+HHMM`, which is safe to use in a path
+    pub fn timezone(&self) -> Result<String, ClineupError> {
+        let offset = self.offset.ok_or(ClineupError::MissingTimezone)?;
+        let seconds = offset.local_minus_utc();
+        let sign = if seconds < 0 { '-' } else { '+' };
+        let seconds = seconds.abs();
+        Ok(format!(
+            "{}{:02}{:02}",
+            sign,
+            seconds / 3600,
+            (seconds % 3600) / 60
+        ))
+    }
+
+    /// Expresses the date in the target timezone.
+    ///
+    /// A date without a known offset is considered to already be in the target timezone,
+    /// since there is no way to know which instant it refers to.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The timezone to convert to.
+    /// * `location_timezone` - The time zone of the place where the media was taken, used by `TimezoneTarget::Gps`.
+    pub fn to_timezone(
+        &self,
+        target: &TimezoneTarget,
+        location_timezone: Option<Tz>,
+    ) -> CaptureDate {
+        match (target, location_timezone) {
+            (TimezoneTarget::Local, _) => self.in_timezone(&Local),
+            (TimezoneTarget::Utc, _) => self.in_timezone(&Utc),
+            (TimezoneTarget::Fixed(offset), _) => self.in_timezone(offset),
+            (TimezoneTarget::Gps, Some(timezone)) => self.in_timezone(&timezone),
+            (TimezoneTarget::Gps, None) => self.clone(),
+        }
+    }
+
+    /// Expresses the date in a timezone, whose offset, like the daylight saving time, depends on the date
+    fn in_timezone<Z: TimeZone>(&self, timezone: &Z) -> CaptureDate {
+        match self.offset {
+            None => {
+                let offset = timezone
+                    .offset_from_local_datetime(&self.datetime)
+                    .earliest()
+                    .map(|offset| offset.fix());
+                CaptureDate::new(self.datetime, offset)
+            }
+            Some(offset) => {
+                let instant = offset.from_local_datetime(&self.datetime).unwrap();
+                CaptureDate::from(instant.with_timezone(timezone))
+            }
+        }
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for CaptureDate {
+    fn from(date: DateTime<Tz>) -> Self {
+        CaptureDate::new(date.naive_local(), Some(date.offset().fix()))
+    }
+}
+
+impl From<SystemTime> for CaptureDate {
+    fn from(time: SystemTime) -> Self {
+        CaptureDate::from(DateTime::<Local>::from(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(date: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_timezone_target_from_string() {
+        assert_eq!(
+            TimezoneTarget::from_string("local").unwrap(),
+            TimezoneTarget::Local
+        );
+        assert_eq!(
+            TimezoneTarget::from_string("UTC").unwrap(),
+            TimezoneTarget::Utc
+        );
+        assert_eq!(
+            TimezoneTarget::from_string("gps").unwrap(),
+            TimezoneTarget::Gps
+        );
+        assert!(TimezoneTarget::from_string("nautical").is_err());
+        assert_eq!(
+            TimezoneTarget::from_string("+05:30").unwrap(),
+            TimezoneTarget::Fixed(FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap())
+        );
+        assert!(TimezoneTarget::from_string("Europe/Paris").is_err());
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(
+            parse_offset("+02:00").unwrap(),
+            FixedOffset::east_opt(7200).unwrap()
+        );
+        assert_eq!(
+            parse_offset("-0330").unwrap(),
+            FixedOffset::west_opt(3 * 3600 + 30 * 60).unwrap()
+        );
+        assert_eq!(
+            parse_offset("Z").unwrap(),
+            FixedOffset::east_opt(0).unwrap()
+        );
+        assert!(parse_offset("02:00").is_err());
+        assert!(parse_offset("+02:75").is_err());
+    }
+
+    #[test]
+    fn test_offset_from_utc_reference() {
+        let offset =
+            offset_from_utc_reference(&naive("2023-03-04 14:00:12"), &naive("2023-03-04 12:00:00"));
+        assert_eq!(offset, FixedOffset::east_opt(7200));
+
+        let implausible =
+            offset_from_utc_reference(&naive("2023-03-05 14:00:00"), &naive("2023-03-04 12:00:00"));
+        assert_eq!(implausible, None);
+    }
+
+    #[test]
+    fn test_timezone_from_position() {
+        let paris = timezone_from_position(48.86, 2.35).unwrap();
+        assert_eq!(paris, chrono_tz::Europe::Paris);
+        // The civil time zones differ from the longitude bands
+        assert_eq!(
+            timezone_from_position(40.42, -3.70).unwrap(),
+            chrono_tz::Europe::Madrid
+        );
+        assert_eq!(
+            timezone_from_position(28.61, 77.21).unwrap(),
+            chrono_tz::Asia::Kolkata
+        );
+        assert_eq!(
+            timezone_from_position(-33.87, 151.21).unwrap(),
+            chrono_tz::Australia::Sydney
+        );
+    }
+
+    #[test]
+    fn test_get_gps_datetime() {
+        let date = get_gps_datetime("2023-03-04", &[23.0, 59.0, 59.6]).unwrap();
+        assert_eq!(date, naive("2023-03-05 00:00:00"));
+    }
+
+    #[test]
+    fn test_capture_date_to_timezone() {
+        let tokyo = FixedOffset::east_opt(9 * 3600).unwrap();
+        let date = CaptureDate::new(naive("2023-03-04 23:30:00"), Some(tokyo));
+
+        let utc = date.to_timezone(&TimezoneTarget::Utc, None);
+        assert_eq!(utc.format("%Y-%m-%d %H:%M"), "2023-03-04 14:30");
+        assert_eq!(utc.timezone().unwrap(), "+0000");
+
+        let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+        let fixed = date.to_timezone(&TimezoneTarget::Fixed(new_york), None);
+        assert_eq!(fixed.format("%d %H"), "04 09");
+        assert_eq!(fixed.timezone().unwrap(), "-0500");
+
+        let unchanged = date.to_timezone(&TimezoneTarget::Gps, None);
+        assert_eq!(unchanged, date);
+    }
+
+    #[test]
+    fn test_capture_date_to_location_timezone() {
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let madrid = timezone_from_position(40.42, -3.70);
+
+        // Daylight saving time in summer, although Madrid is west of Greenwich
+        let summer = CaptureDate::new(naive("2023-07-01 10:00:00"), Some(utc));
+        let summer = summer.to_timezone(&TimezoneTarget::Gps, madrid);
+        assert_eq!(summer.format("%H:%M"), "12:00");
+        assert_eq!(summer.timezone().unwrap(), "+0200");
+
+        let winter = CaptureDate::new(naive("2023-01-02 10:00:00"), Some(utc));
+        let winter = winter.to_timezone(&TimezoneTarget::Gps, madrid);
+        assert_eq!(winter.timezone().unwrap(), "+0100");
+
+        // Half an hour apart from the longitude band
+        let kolkata = timezone_from_position(28.61, 77.21);
+        let date = CaptureDate::new(naive("2023-07-01 12:00:00"), None);
+        let date = date.to_timezone(&TimezoneTarget::Gps, kolkata);
+        assert_eq!(date.format("%H:%M"), "12:00");
+        assert_eq!(date.timezone().unwrap(), "+0530");
+    }
+
+    #[test]
+    fn test_capture_date_without_offset() {
+        let date = CaptureDate::new(naive("2023-03-04 23:30:00"), None);
+        assert!(date.timezone().is_err());
+
+        let utc = date.to_timezone(&TimezoneTarget::Utc, None);
+        assert_eq!(utc.datetime(), date.datetime());
+        assert_eq!(utc.timezone().unwrap(), "+0000");
+    }
+}
@@ -12,7 +12,7 @@ use crate::organizer::OrganizationMode;
 use crate::organizer::OrganizationStrategy;
 use crate::organizer::SymlinksStrategy;
 use crate::{errors::ClineupError, placeholders::Placeholder};
-use path_clean::{clean, PathClean};
+use path_clean::PathClean;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -35,6 +35,17 @@ pub fn is_there_a_exif_placeholder(
     is_there_a_date_placeholder
 }
 
+/// Checks if there is a `%burst_id` placeholder in the given placeholders.
+pub fn is_there_a_burst_placeholder(
+    placeholders: &IndexMap<String, IndexMap<String, Placeholder>>,
+) -> bool {
+    placeholders.values().any(|chain| {
+        chain
+            .values()
+            .any(|placeholder| *placeholder == Placeholder::BurstId)
+    })
+}
+
 /// Checks if there is an metadata placeholder in the given placeholders.
 /// Returns `true` if there is at least one metadata placeholder, `false` otherwise.
 pub fn is_there_a_metadata_placeholder(
@@ -68,6 +79,19 @@ pub fn is_there_a_location_placeholder(
     is_there_a_location_placeholder
 }
 
+/// Formats a size in bytes with the largest unit keeping at least one unit, like `5.3KB`.
+/// The units are powers of 1024, like the ones accepted by `--size-greater`.
+pub fn format_size_human(size: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB", "PB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{}{}", (value * 10.0).round() / 10.0, units[unit])
+}
+
 pub fn print_error<T>(e: ClineupError) -> T {
     error!("{e}");
     exit(1)
@@ -115,18 +139,36 @@ pub fn get_full_format_path(
 ///
 /// An `Option<Box<dyn GpsResolutionProvider>>` representing the reverse geocoding provider.
 pub fn get_reverse_geocoding(config: &Config) -> Option<Box<dyn GpsResolutionProvider>> {
-    match &config.reverse_geocoding {
+    get_reverse_geocoding_provider(
+        config.reverse_geocoding.as_ref(),
+        config.nominatim_email.as_ref(),
+    )
+}
+
+/// Creates the reverse geocoding provider, exiting if its mandatory settings are missing.
+///
+/// # Arguments
+///
+/// * `provider` - The provider chosen with `--reverse-geocoding`.
+/// * `nominatim_email` - The email given with `--nominatim-email`.
+///
+/// # Returns
+///
+/// An `Option<Box<dyn GpsResolutionProvider>>`, `None` if no provider is chosen.
+pub fn get_reverse_geocoding_provider(
+    provider: Option<&GpsResolutionProviderImpl>,
+    nominatim_email: Option<&String>,
+) -> Option<Box<dyn GpsResolutionProvider>> {
+    match provider {
         Some(provider) => match provider {
             GpsResolutionProviderImpl::Nominatim => {
                 // Check if Nominatim email is provided
-                if config.nominatim_email.is_none() {
+                if nominatim_email.is_none() {
                     println!("Nominatim email is required when using Nominatim as a reverse geocoding provider.");
                     exit(1)
                 }
                 // Create a new Nominatim instance with the provided email
-                Some(Box::new(Nominatim::new(
-                    config.nominatim_email.clone().unwrap(),
-                )))
+                Some(Box::new(Nominatim::new(nominatim_email.unwrap().clone())))
             }
         },
         None => None,
@@ -192,6 +234,13 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_size_human() {
+        assert_eq!(format_size_human(0), "0B");
+        assert_eq!(format_size_human(5384), "5.3KB");
+        assert_eq!(format_size_human(12 * 1024 * 1024), "12MB");
+    }
+
     #[test]
     fn test_get_organization_strategy_copy() {
         let strategy = OrganizationMode::Copy;
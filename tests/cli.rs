@@ -1,4 +1,4 @@
-use std::{fs::File, path::Path};
+use std::path::Path;
 
 fn copy_directory(source: &Path, target: &Path) -> std::io::Result<()> {
     let mut stack = Vec::new();
@@ -25,11 +25,23 @@ fn copy_directory(source: &Path, target: &Path) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Lists the names of the organized files of a folder, sorted, without the hash index of the destination
+fn list_organized(folder: &Path) -> Vec<String> {
+    let mut organized: Vec<String> = std::fs::read_dir(folder)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| !clineup::path::hash_index::is_hash_index_file(path))
+        .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    organized.sort();
+    organized
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use assert_cmd::Command;
-    use predicates::prelude::*;
+    use predicates::prelude::PredicateBooleanExt;
     use std::path::PathBuf;
     use tempdir::TempDir;
     #[test]
@@ -71,11 +83,11 @@ mod tests {
         let output_tmp_dir = TempDir::new("output_copy_strategy").unwrap();
 
         // Copy content of data folder inside tmp dir :
-        copy_directory(&data_path, &input_tmp_dir.path()).unwrap();
+        copy_directory(&data_path, input_tmp_dir.path()).unwrap();
         let assert = cmd
             .arg(format!(
                 "--source={}",
-                format!("{}", input_tmp_dir.path().to_string_lossy())
+                input_tmp_dir.path().to_string_lossy()
             ))
             .arg(format!(
                 "--destination={}",
@@ -114,11 +126,11 @@ mod tests {
         let output_tmp_dir = TempDir::new("output_move_strategy").unwrap();
 
         // Copy content of data folder inside tmp dir :
-        copy_directory(&data_path, &input_tmp_dir.path()).unwrap();
+        copy_directory(&data_path, input_tmp_dir.path()).unwrap();
         let assert = cmd
             .arg(format!(
                 "--source={}",
-                format!("{}", input_tmp_dir.path().to_string_lossy())
+                input_tmp_dir.path().to_string_lossy()
             ))
             .arg(format!(
                 "--destination={}",
@@ -158,11 +170,11 @@ mod tests {
         let output_tmp_dir = TempDir::new("output_symlink_strategy").unwrap();
 
         // Copy content of data folder inside tmp dir :
-        copy_directory(&data_path, &input_tmp_dir.path()).unwrap();
+        copy_directory(&data_path, input_tmp_dir.path()).unwrap();
         let assert = cmd
             .arg(format!(
                 "--source={}",
-                format!("{}", input_tmp_dir.path().to_string_lossy())
+                input_tmp_dir.path().to_string_lossy()
             ))
             .arg(format!(
                 "--destination={}",
@@ -193,11 +205,11 @@ mod tests {
         let output_tmp_dir = TempDir::new("output_copy_strategy").unwrap();
 
         // Copy content of data folder inside tmp dir :
-        copy_directory(&data_path, &input_tmp_dir.path()).unwrap();
+        copy_directory(&data_path, input_tmp_dir.path()).unwrap();
         let assert = cmd
             .arg(format!(
                 "--source={}",
-                format!("{}", input_tmp_dir.path().to_string_lossy())
+                input_tmp_dir.path().to_string_lossy()
             ))
             .arg(format!(
                 "--destination={}",
@@ -222,11 +234,11 @@ mod tests {
         let output_tmp_dir = TempDir::new("output_copy_strategy").unwrap();
 
         // Copy content of data folder inside tmp dir :
-        copy_directory(&data_path, &input_tmp_dir.path()).unwrap();
+        copy_directory(&data_path, input_tmp_dir.path()).unwrap();
         let assert = cmd
             .arg(format!(
                 "--source={}",
-                format!("{}", input_tmp_dir.path().to_string_lossy())
+                input_tmp_dir.path().to_string_lossy()
             ))
             .arg(format!(
                 "--destination={}",
@@ -254,11 +266,11 @@ mod tests {
         let output_tmp_dir = TempDir::new("output_copy_strategy").unwrap();
 
         // Copy content of data folder inside tmp dir :
-        copy_directory(&data_path, &input_tmp_dir.path()).unwrap();
+        copy_directory(&data_path, input_tmp_dir.path()).unwrap();
         let assert = cmd
             .arg(format!(
                 "--source={}",
-                format!("{}", input_tmp_dir.path().to_string_lossy())
+                input_tmp_dir.path().to_string_lossy()
             ))
             .arg(format!(
                 "--destination={}",
@@ -280,4 +292,833 @@ mod tests {
             assert!(!path.exists())
         }
     }
+
+    #[test]
+    fn test_cmd_rules() {
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let data_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/");
+        let input_tmp_dir = TempDir::new("input_rules").unwrap();
+        let output_tmp_dir = TempDir::new("output_rules").unwrap();
+        copy_directory(&data_path, input_tmp_dir.path()).unwrap();
+
+        let config_path = output_tmp_dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+            [[rules]]
+            name = "screenshots"
+            extensions = ["png"]
+            folder_format = "screens"
+            strategy = "move"
+
+            [[rules]]
+            name = "empty"
+            size_lower = "1KB"
+            folder_format = "empty"
+            "#,
+        )
+        .unwrap();
+
+        let assert = cmd
+            .arg(format!(
+                "--source={}",
+                input_tmp_dir.path().to_string_lossy()
+            ))
+            .arg(format!(
+                "--destination={}",
+                output_tmp_dir.path().to_string_lossy()
+            ))
+            .arg(format!("--config={}", config_path.to_string_lossy()))
+            .arg("--folder-format={%year}")
+            .arg("--strategy=copy")
+            .assert();
+        assert.success();
+
+        let output = Path::new(output_tmp_dir.path());
+        assert!(output.join("screens").join("Paris.png").exists());
+        assert!(!input_tmp_dir.path().join("Paris.png").exists());
+        assert!(output.join("empty").join("empty.txt").exists());
+        assert!(output.join("2023").join("Paris-20230304.jpg").exists());
+        assert!(input_tmp_dir.path().join("Paris-20230304.jpg").exists());
+    }
+
+    #[test]
+    fn test_cmd_template_check() {
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd
+            .arg("template")
+            .arg("check")
+            .arg("%year/{%city|Unknown}")
+            .assert();
+        assert
+            .success()
+            .stdout(predicates::str::contains("%year: EXIF"))
+            .stdout(predicates::str::contains("%city: geocoding"))
+            .stdout(predicates::str::contains(
+                "Warning: Location tag found but reverse geocoding provider is not set",
+            ));
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd.arg("template").arg("check").arg("%yaer").assert();
+        assert
+            .failure()
+            .stdout(predicates::str::contains("did you mean %year?"));
+    }
+
+    #[test]
+    fn test_cmd_template_preview() {
+        let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd
+            .arg("template")
+            .arg("preview")
+            .arg("%year/{%camera_model|No model}")
+            .arg(data.join("Paris-20230304.jpg"))
+            .arg(data.join("empty.txt"))
+            .assert();
+        assert
+            .success()
+            .stdout(predicates::str::contains("-> 2023/rusttest\n"))
+            .stdout(predicates::str::contains("-> Unknown Year/No model\n"))
+            .stdout(predicates::str::contains("%year = Unknown Year (fallback)"))
+            .stdout(predicates::str::contains(
+                "{%camera_model|No model} = No model (fallback text)",
+            ));
+    }
+
+    #[test]
+    fn test_cmd_plan_output_and_apply() {
+        let input_tmp_dir = TempDir::new("input").unwrap();
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let plan_tmp_dir = TempDir::new("plan").unwrap();
+        let plan_path = plan_tmp_dir.path().join("plan.json");
+        copy_directory(Path::new("tests/data"), input_tmp_dir.path()).unwrap();
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd
+            .arg(format!(
+                "--source={}",
+                input_tmp_dir.path().to_string_lossy()
+            ))
+            .arg(format!(
+                "--destination={}",
+                output_tmp_dir.path().to_string_lossy()
+            ))
+            .arg("--folder-format={%year|Unknown}")
+            .arg("--drop-duplicates")
+            .arg(format!("--plan-output={}", plan_path.to_string_lossy()))
+            .assert();
+        assert.success();
+
+        // Nothing is organized until the plan is applied
+        let output = Path::new(output_tmp_dir.path());
+        assert!(!output.join("2023").exists());
+
+        let plan: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&plan_path).unwrap()).unwrap();
+        let entries = plan.as_array().unwrap();
+        let empty = entries
+            .iter()
+            .find(|entry| entry["source"].as_str().unwrap().ends_with("empty.txt"))
+            .unwrap();
+        assert_eq!(empty["strategy"], "copy");
+        assert_eq!(empty["conflict"], "none");
+        assert_eq!(empty["fallbacks"], serde_json::json!(["{%year|Unknown}"]));
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|entry| entry["duplicate"] == true)
+                .count(),
+            2
+        );
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd
+            .arg("apply")
+            .arg(format!("--plan={}", plan_path.to_string_lossy()))
+            .assert();
+        assert
+            .success()
+            .stdout(predicates::str::contains("Done. 5 file(s) processed."));
+        assert!(output.join("2023").join("London-20230102.jpg").exists());
+        assert!(output.join("Unknown").join("empty.txt").exists());
+
+        // Applied again, every destination is taken
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("apply")
+            .arg(format!("--plan={}", plan_path.to_string_lossy()))
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("Done. 0 file(s) processed."))
+            .stdout(predicates::str::contains(
+                "Skipped 0 conflicting file(s), 0 missing source(s) and 5 failed file(s).",
+            ));
+    }
+
+    #[test]
+    fn test_cmd_timezone() {
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format={%timezone|Unknown}")
+            .arg("--filename-format=%year%month%day-%original_filename")
+            .arg("--extension=jpg")
+            .arg("--timezone=-05:00")
+            .arg("--dry-run")
+            .assert()
+            .success()
+            .stderr(predicates::str::contains(
+                "/destination/-0500/20230102-London-20230102.jpg",
+            ));
+
+        // Without a timezone, the offset of the EXIF dates is unknown
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format={%timezone|Unknown}")
+            .arg("--filename-format=%original_filename")
+            .arg("--extension=jpg")
+            .arg("--dry-run")
+            .assert()
+            .success()
+            .stderr(predicates::str::contains(
+                "/destination/Unknown/London-20230102.jpg",
+            ));
+
+        // Paris is in the time zone of Central Europe, although it is in the longitude band of Greenwich
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format={%timezone|Unknown}")
+            .arg("--filename-format=%original_filename")
+            .arg("--extension=jpg")
+            .arg("--timezone=gps")
+            .arg("--dry-run")
+            .assert()
+            .success()
+            .stderr(predicates::str::contains(
+                "/destination/+0000/London-20230102.jpg",
+            ))
+            .stderr(predicates::str::contains(
+                "/destination/+0100/Paris-20230304.jpg",
+            ));
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format=%timezone")
+            .arg("--timezone=nautical")
+            .arg("--dry-run")
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_cmd_dry_run_summary() {
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd
+            .arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format={%year|Unknown}")
+            .arg("--drop-duplicates")
+            .arg("--dry-run")
+            .arg("--dry-run-number-of-files=1")
+            .assert();
+        assert
+            .success()
+            .stdout(predicates::str::contains("Summary of 5 file(s)"))
+            .stdout(predicates::str::contains("    /destination/2023: 2\n"))
+            .stdout(predicates::str::contains("    /destination/Unknown: 3\n"))
+            .stdout(predicates::str::contains("    {%year|Unknown}: 3\n"))
+            .stdout(predicates::str::contains("Duplicates: 2"));
+    }
+
+    #[test]
+    fn test_cmd_dry_run_summary_tree() {
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd
+            .arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format={%year|Unknown}")
+            .arg("--drop-duplicates")
+            .arg("--dry-run")
+            .arg("--summary=tree")
+            .assert();
+        assert
+            .success()
+            .stdout(predicates::str::contains("/destination (5 file(s), "))
+            .stdout(predicates::str::contains("├── 2023 (2 file(s), "))
+            .stdout(predicates::str::contains(
+                "└── Unknown [fallback] (3 file(s), ",
+            ))
+            .stdout(predicates::str::contains("Files in fallback folders: 3\n"))
+            .stdout(predicates::str::contains("Duplicates: 2"))
+            .stdout(predicates::str::contains(" -> ").not());
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format=%year")
+            .arg("--summary=tree")
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_cmd_dedupe_against_destination() {
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = Path::new(output_tmp_dir.path());
+        std::fs::create_dir_all(output.join("old")).unwrap();
+        std::fs::copy(
+            "tests/data/London-20230102.jpg",
+            output.join("old/already_imported.jpg"),
+        )
+        .unwrap();
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        let assert = cmd
+            .arg("--source=tests/data")
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .arg("--filename-format={%original_filename}")
+            .arg("--dedupe-against")
+            .assert();
+        assert.success();
+
+        assert!(!output.join("London-20230102.jpg").exists());
+        assert!(output.join("5384_bytes.png").exists());
+        // The duplicates of the run are dropped as well
+        assert!(
+            !output.join("Paris-20230304-duplicated.jpg").exists()
+                || !output.join("Paris-20230304.jpg").exists()
+        );
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .arg("--filename-format={%original_filename}")
+            .arg("--dedupe-against=tests/missing")
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_cmd_dedupe_against_overlapping_sources() {
+        let library_tmp_dir = TempDir::new("library").unwrap();
+        let library = library_tmp_dir.path();
+        std::fs::create_dir_all(library.join("import")).unwrap();
+        std::fs::write(library.join("import/a.txt"), "a").unwrap();
+        std::fs::write(library.join("import/b.txt"), "b").unwrap();
+        std::fs::write(library.join("copy_of_b.txt"), "b").unwrap();
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = output_tmp_dir.path();
+
+        // Both sources reach the same files, which are part of the library
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg(format!(
+            "--source={}",
+            library.join("import").to_string_lossy()
+        ))
+        .arg(format!(
+            "--source={}",
+            library.join("import/../import").to_string_lossy()
+        ))
+        .arg(format!("--destination={}", output.to_string_lossy()))
+        .arg("--filename-format=%original_filename")
+        .arg(format!("--dedupe-against={}", library.to_string_lossy()))
+        .assert()
+        .success();
+        assert_eq!(list_organized(output), vec!["a.txt"]);
+    }
+
+    #[test]
+    fn test_cmd_hash_index() {
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = Path::new(output_tmp_dir.path());
+
+        // Every run records the organized files, even without --dedupe-against
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .arg("--filename-format={%original_filename}")
+            .assert()
+            .success();
+        assert!(output.join(".clineup-index.sqlite").exists());
+
+        // The organized files are recorded while they are written
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("index")
+            .arg("verify")
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("Corrupted: 0\n"))
+            .stdout(predicates::str::contains("Not indexed: 0\n"));
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("index")
+            .arg("rebuild")
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("file(s) indexed"));
+
+        // Another library keeps its own index, with the hashes of the algorithm of the run.
+        // Its file has the same size, start and end as the imported one, so that both are fully hashed
+        let library_tmp_dir = TempDir::new("library").unwrap();
+        let library = library_tmp_dir.path();
+        let source_tmp_dir = TempDir::new("source").unwrap();
+        let source = source_tmp_dir.path();
+        let mut content = vec![0u8; 3 * 4096];
+        std::fs::write(library.join("original.bin"), &content).unwrap();
+        content[3 * 4096 / 2] = 1;
+        std::fs::write(source.join("imported.bin"), &content).unwrap();
+        let new_output_tmp_dir = TempDir::new("output").unwrap();
+        let new_output = new_output_tmp_dir.path();
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg(format!("--source={}", source.to_string_lossy()))
+            .arg(format!("--destination={}", new_output.to_string_lossy()))
+            .arg("--filename-format={%original_filename}")
+            .arg(format!("--dedupe-against={}", library.to_string_lossy()))
+            .arg("--duplicates-hash-algorithm=xxh3")
+            .assert()
+            .success();
+        assert!(library.join(".clineup-index.sqlite").exists());
+        assert!(new_output.join("imported.bin").exists());
+        assert!(!library.join("imported.bin").exists());
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("index")
+            .arg("verify")
+            .arg(format!("--destination={}", library.to_string_lossy()))
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("1 file(s) verified\n"));
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("index")
+            .arg("verify")
+            .arg(format!("--destination={}", new_output.to_string_lossy()))
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("1 file(s) verified\n"))
+            .stdout(predicates::str::contains("Not indexed: 0\n"));
+    }
+
+    #[test]
+    fn test_cmd_duplicates_quarantine() {
+        let tmp_dir = TempDir::new("duplicates").unwrap();
+        let folder = tmp_dir.path();
+        std::fs::create_dir_all(folder.join("2023/03")).unwrap();
+        std::fs::copy("tests/data/Paris-20230304.jpg", folder.join("Paris.jpg")).unwrap();
+        std::fs::copy(
+            "tests/data/Paris-20230304.jpg",
+            folder.join("2023/03/IMG_0001.jpg"),
+        )
+        .unwrap();
+        std::fs::copy("tests/data/London-20230102.jpg", folder.join("London.jpg")).unwrap();
+        let report = folder.join("report.csv");
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("duplicates")
+            .arg(folder)
+            .arg("--action=quarantine")
+            .arg("--keep=preferred")
+            .arg("--prefer-regex=/2023/")
+            .arg(format!("--report={}", report.to_string_lossy()))
+            .assert()
+            .success()
+            .stdout(predicates::str::contains(
+                "1 duplicate group(s), 1 duplicate(s)",
+            ))
+            .stdout(predicates::str::contains("1 duplicate(s) moved to"));
+
+        assert!(folder.join("2023/03/IMG_0001.jpg").exists());
+        assert!(folder.join("_duplicates/Paris.jpg").exists());
+        assert!(!folder.join("Paris.jpg").exists());
+        assert!(folder.join("London.jpg").exists());
+        assert!(std::fs::read_to_string(report)
+            .unwrap()
+            .starts_with("group,path,size,kept\n"));
+    }
+
+    #[test]
+    fn test_cmd_limit_and_offset() {
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = Path::new(output_tmp_dir.path());
+
+        for offset in ["0", "2"] {
+            let mut cmd = Command::cargo_bin("clineup").unwrap();
+            let assert = cmd
+                .arg("--source=tests/data")
+                .arg(format!("--destination={}", output.to_string_lossy()))
+                .arg("--filename-format={%original_filename}")
+                .arg("--limit=2")
+                .arg(format!("--offset={}", offset))
+                .assert();
+            assert.success();
+        }
+
+        // The files are sorted by path, the second run resuming the first one
+        let organized = list_organized(output);
+        assert_eq!(
+            organized,
+            vec![
+                "5384_bytes.png",
+                "London-20230102.jpg",
+                "London.png",
+                "Paris-20230304-duplicated.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cmd_limit_and_offset_with_move() {
+        let source_tmp_dir = TempDir::new("source").unwrap();
+        let source = source_tmp_dir.path();
+        for name in ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt", "f.txt"] {
+            std::fs::write(source.join(name), name).unwrap();
+        }
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = Path::new(output_tmp_dir.path());
+        let run = |args: &[&str]| {
+            let mut cmd = Command::cargo_bin("clineup").unwrap();
+            cmd.arg(format!("--source={}", source.to_string_lossy()))
+                .arg(format!("--destination={}", output.to_string_lossy()))
+                .arg("--filename-format=%original_filename")
+                .arg("--strategy=move")
+                .args(args)
+                .assert()
+                .success();
+        };
+
+        // The moved files left the source, so the next run resumes without offset
+        run(&["--limit=2"]);
+        run(&["--limit=2"]);
+        assert_eq!(
+            list_organized(output),
+            vec!["a.txt", "b.txt", "c.txt", "d.txt"]
+        );
+
+        // An offset skips the first files which are left, never organized
+        run(&["--limit=1", "--offset=1"]);
+        assert!(output.join("f.txt").exists());
+        assert!(source.join("e.txt").exists());
+    }
+
+    #[test]
+    fn test_cmd_bursts() {
+        // The two copies of the Paris photo have the same camera and capture date
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = Path::new(output_tmp_dir.path());
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .arg("--extension=jpg")
+            .arg("--filename-format={%burst_id|Single}_%original_filename")
+            .arg("--bursts=subfolder")
+            .assert()
+            .success();
+        let burst = output.join("20230304-000000_Paris-20230304-duplicated");
+        assert!(burst
+            .join("20230304-000000_Paris-20230304-duplicated_Paris-20230304.jpg")
+            .exists());
+        assert!(burst
+            .join("20230304-000000_Paris-20230304-duplicated_Paris-20230304-duplicated.jpg")
+            .exists());
+        assert!(output.join("Single_London-20230102.jpg").exists());
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--extension=jpg")
+            .arg("--folder-format=%year")
+            .arg("--bursts=first")
+            .arg("--dry-run")
+            .assert()
+            .success()
+            .stdout(predicates::str::contains("Summary of 2 file(s)"))
+            .stdout(predicates::str::contains("Dropped burst frames: 1\n"));
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg("--destination=/destination")
+            .arg("--folder-format=%year")
+            .arg("--burst-interval=soon")
+            .assert()
+            .failure();
+    }
+
+    #[test]
+    fn test_cmd_counter_second_run() {
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = output_tmp_dir.path();
+        for _ in 0..2 {
+            let mut cmd = Command::cargo_bin("clineup").unwrap();
+            cmd.arg("--source=tests/data")
+                .arg(format!("--destination={}", output.to_string_lossy()))
+                .arg("--extension=png")
+                .arg("--filename-format=photo_%counter.%extension")
+                .assert()
+                .success();
+        }
+        // The second run goes on with the sequence of the first one
+        let organized = list_organized(output);
+        assert_eq!(
+            organized,
+            (1..=6)
+                .map(|counter| format!("photo_{:04}.png", counter))
+                .collect::<Vec<String>>()
+        );
+    }
+
+    #[test]
+    fn test_cmd_counter_after_placeholder() {
+        // The `_` after `%day` is not part of the placeholder name
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = output_tmp_dir.path();
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--source=tests/data")
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .arg("--extension=jpg")
+            .arg("--filename-format=%year%month%day_%counter.%ext_lower")
+            .assert()
+            .success();
+        let organized = list_organized(output);
+        assert_eq!(
+            organized,
+            vec![
+                "20230102_0001.jpg",
+                "20230304_0002.jpg",
+                "20230304_0003.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cmd_live_photo_pairing() {
+        let source_tmp_dir = TempDir::new("source").unwrap();
+        let source = source_tmp_dir.path();
+        std::fs::copy("tests/data/Paris-20230304.jpg", source.join("IMG_0001.JPG")).unwrap();
+        // The video of the Live Photo has no EXIF date
+        std::fs::write(source.join("IMG_0001.MOV"), "video").unwrap();
+
+        for (pairing, video) in [
+            (true, "2023/Paris_0001.MOV"),
+            (false, "Unknown Year/Paris_0001.MOV"),
+        ] {
+            let output_tmp_dir = TempDir::new("output").unwrap();
+            let output = output_tmp_dir.path();
+            let mut cmd = Command::cargo_bin("clineup").unwrap();
+            cmd.arg(format!("--source={}", source.to_string_lossy()))
+                .arg(format!("--destination={}", output.to_string_lossy()))
+                .arg("--folder-format=%year")
+                .arg("--filename-format=Paris_%counter.%extension");
+            if !pairing {
+                cmd.arg("--no-live-photo-pairing");
+            }
+            cmd.assert().success();
+            assert!(output.join("2023/Paris_0001.JPG").exists());
+            assert!(output.join(video).exists());
+        }
+    }
+
+    #[test]
+    fn test_cmd_companion_extensions() {
+        let source_tmp_dir = TempDir::new("source").unwrap();
+        let source = source_tmp_dir.path();
+        std::fs::copy("tests/data/Paris-20230304.jpg", source.join("IMG_0001.JPG")).unwrap();
+        std::fs::write(source.join("IMG_0001.xmp"), "sidecar").unwrap();
+        std::fs::write(source.join("IMG_0001.JPG.thm"), "thumbnail").unwrap();
+
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = output_tmp_dir.path();
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg(format!("--source={}", source.to_string_lossy()))
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .arg("--folder-format=%year")
+            .arg("--filename-format=Paris_%counter.%extension")
+            .assert()
+            .success();
+        assert!(output.join("2023/Paris_0001.JPG").exists());
+        assert!(output.join("2023/Paris_0001.xmp").exists());
+        assert!(output.join("2023/Paris_0001.JPG.thm").exists());
+
+        // Without companions, the sidecars have no EXIF date
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let output = output_tmp_dir.path();
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg(format!("--source={}", source.to_string_lossy()))
+            .arg(format!("--destination={}", output.to_string_lossy()))
+            .arg("--folder-format=%year")
+            .arg("--filename-format=%original_filename")
+            .arg("--companion-extension=none")
+            .assert()
+            .success();
+        assert!(output.join("Unknown Year/IMG_0001.xmp").exists());
+        assert!(output.join("Unknown Year/IMG_0001.JPG.thm").exists());
+    }
+
+    #[test]
+    fn test_cmd_walk_options() {
+        let source_tmp_dir = TempDir::new("source").unwrap();
+        let source = source_tmp_dir.path();
+        for name in [
+            "a.txt",
+            ".hidden.txt",
+            "sub/b.txt",
+            "sub/deep/c.txt",
+            "ignored/d.txt",
+            "@eaDir/e.txt",
+            "sub/@eaDir/f.txt",
+        ] {
+            std::fs::create_dir_all(source.join(name).parent().unwrap()).unwrap();
+            std::fs::write(source.join(name), name).unwrap();
+        }
+        std::fs::write(source.join(".clineupignore"), "ignored/\n").unwrap();
+        #[cfg(target_family = "unix")]
+        std::os::unix::fs::symlink(source, source.join("sub/loop")).unwrap();
+
+        let organized = |args: &[&str]| {
+            let output_tmp_dir = TempDir::new("output").unwrap();
+            let mut cmd = Command::cargo_bin("clineup").unwrap();
+            cmd.arg(format!("--source={}", source.to_string_lossy()))
+                .arg(format!(
+                    "--destination={}",
+                    output_tmp_dir.path().to_string_lossy()
+                ))
+                .arg("--filename-format=%original_filename")
+                .args(args)
+                .assert()
+                .success();
+            list_organized(output_tmp_dir.path())
+        };
+
+        assert_eq!(organized(&[]), vec![".hidden.txt", "a.txt"]);
+        assert_eq!(
+            organized(&[
+                "--recursive",
+                "--skip-hidden",
+                "--exclude-glob=@eaDir/",
+                "--follow-symlinks"
+            ]),
+            vec!["a.txt", "b.txt", "c.txt"]
+        );
+        assert_eq!(
+            organized(&["--recursive", "--max-depth=2", "--exclude-glob=*.txt"]),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            organized(&["--recursive", "--max-depth=2", "--skip-hidden"]),
+            vec!["a.txt", "b.txt", "e.txt"]
+        );
+    }
+
+    #[test]
+    fn test_cmd_multiple_sources_and_files_from() {
+        let source_tmp_dir = TempDir::new("source").unwrap();
+        let source = source_tmp_dir.path();
+        for name in ["first/a.txt", "first/b.txt", "second/c.txt", "third/d.txt"] {
+            std::fs::create_dir_all(source.join(name).parent().unwrap()).unwrap();
+            std::fs::write(source.join(name), name).unwrap();
+        }
+
+        let organized = |args: &[String], stdin: &str| {
+            let output_tmp_dir = TempDir::new("output").unwrap();
+            let mut cmd = Command::cargo_bin("clineup").unwrap();
+            cmd.arg(format!(
+                "--destination={}",
+                output_tmp_dir.path().to_string_lossy()
+            ))
+            .arg("--filename-format=%original_filename")
+            .args(args)
+            .write_stdin(stdin)
+            .assert()
+            .success();
+            list_organized(output_tmp_dir.path())
+        };
+        let path = |name: &str| source.join(name).to_string_lossy().to_string();
+
+        // A directory, a file and a file of the directory again
+        assert_eq!(
+            organized(
+                &[
+                    format!("--source={}", path("first")),
+                    format!("--source={}", path("second/c.txt")),
+                    format!("--source={}", path("first/a.txt")),
+                ],
+                ""
+            ),
+            vec!["a.txt", "b.txt", "c.txt"]
+        );
+
+        // The listed directories are skipped
+        assert_eq!(
+            organized(
+                &["--files-from=-".to_string()],
+                &format!(
+                    "{}\n{}\n\n{}\n",
+                    path("first"),
+                    path("first/b.txt"),
+                    path("third/d.txt")
+                )
+            ),
+            vec!["b.txt", "d.txt"]
+        );
+
+        let list_tmp_dir = TempDir::new("list").unwrap();
+        let list = list_tmp_dir.path().join("files.txt");
+        std::fs::write(
+            &list,
+            format!("{}\0{}\0", path("second/c.txt"), path("first/a.txt")),
+        )
+        .unwrap();
+        assert_eq!(
+            organized(
+                &[
+                    format!("--files-from={}", list.to_string_lossy()),
+                    format!("--source={}", path("third")),
+                ],
+                ""
+            ),
+            vec!["a.txt", "c.txt", "d.txt"]
+        );
+
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg("--destination=whatever")
+            .arg("--filename-format=%original_filename")
+            .arg("--files-from=missing.txt")
+            .assert()
+            .failure();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_cmd_files_from_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let source_tmp_dir = TempDir::new("source").unwrap();
+        let source = source_tmp_dir.path();
+        let photo = source.join(OsStr::from_bytes(b"caf\xe9.txt"));
+        std::fs::write(&photo, "photo").unwrap();
+        std::fs::write(source.join("other.txt"), "other").unwrap();
+
+        let mut list = photo.as_os_str().as_bytes().to_vec();
+        list.push(b'\n');
+        list.extend_from_slice(source.join("other.txt").as_os_str().as_bytes());
+        list.push(b'\n');
+
+        let output_tmp_dir = TempDir::new("output").unwrap();
+        let mut cmd = Command::cargo_bin("clineup").unwrap();
+        cmd.arg(format!(
+            "--destination={}",
+            output_tmp_dir.path().to_string_lossy()
+        ))
+        .arg("--filename-format=%original_filename")
+        .arg("--files-from=-")
+        .write_stdin(list)
+        .assert()
+        .success();
+        assert_eq!(list_organized(output_tmp_dir.path()).len(), 2);
+    }
 }
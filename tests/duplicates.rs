@@ -1,8 +1,13 @@
 #[cfg(test)]
 mod tests {
-    use std::{fs::File, path::PathBuf};
+    use std::{cell::RefCell, fs::File, path::PathBuf, rc::Rc};
+    use tempdir::TempDir;
 
-    use clineup::path::duplicates_finder::{get_hash_of_file, DuplicatesFinder};
+    use clineup::path::duplicates_finder::{
+        get_hash_of_file, get_hash_of_file_with, get_partial_hash_of_file_with, DuplicatesFinder,
+        HashAlgorithm, PARTIAL_HASH_SIZE,
+    };
+    use clineup::path::hash_index::HashIndex;
 
     #[test]
     fn test_get_hash_of_file() {
@@ -16,6 +21,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_hash_of_file_sha512() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/London.png");
+        let file = File::open(path).unwrap();
+        let hash = get_hash_of_file_with(&file, HashAlgorithm::Sha512).unwrap();
+        assert_eq!(hash.len(), 128);
+    }
+
     #[test]
     fn test_duplicates_finder() {
         let mut duplicates_finder = DuplicatesFinder::new();
@@ -36,4 +49,180 @@ mod tests {
         let is_duplicate3 = duplicates_finder.is_duplicate(&path3).unwrap();
         assert!(is_duplicate3);
     }
+
+    #[test]
+    fn test_duplicates_finder_against_library() {
+        let library = TempDir::new("library").unwrap();
+        let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        std::fs::create_dir_all(library.path().join("2023/03")).unwrap();
+        std::fs::copy(
+            data.join("Paris-20230304.jpg"),
+            library.path().join("2023/03/renamed.jpg"),
+        )
+        .unwrap();
+        std::fs::write(library.path().join("empty.txt"), "").unwrap();
+
+        let mut duplicates_finder = DuplicatesFinder::new();
+        assert_eq!(duplicates_finder.index_library(library.path()).unwrap(), 1);
+
+        // Already in the library, whatever its name
+        assert!(duplicates_finder
+            .is_duplicate(&data.join("Paris-20230304.jpg"))
+            .unwrap());
+        assert!(!duplicates_finder
+            .is_duplicate(&data.join("London.png"))
+            .unwrap());
+        // A file of the library is not a duplicate of itself
+        let mut duplicates_finder = DuplicatesFinder::new();
+        duplicates_finder.index_library(library.path()).unwrap();
+        assert!(!duplicates_finder
+            .is_duplicate(&library.path().join("2023/03/renamed.jpg"))
+            .unwrap());
+
+        let mut duplicates_finder = DuplicatesFinder::new();
+        assert_eq!(
+            duplicates_finder
+                .index_library(&library.path().join("missing"))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_duplicates_finder_overlapping_sources() {
+        let source = TempDir::new("source").unwrap();
+        std::fs::create_dir_all(source.path().join("2023")).unwrap();
+        let photo = source.path().join("2023/photo.jpg");
+        std::fs::write(&photo, "photo").unwrap();
+        std::fs::write(source.path().join("copy.jpg"), "photo").unwrap();
+
+        // The same file reached again through another source, like `source` and `source/2023/../2023`
+        let mut duplicates_finder = DuplicatesFinder::new();
+        assert!(!duplicates_finder
+            .is_duplicate(&source.path().join("2023/../2023/photo.jpg"))
+            .unwrap());
+        assert!(!duplicates_finder.is_duplicate(&photo).unwrap());
+        assert_eq!(
+            duplicates_finder
+                .find_duplicate(&source.path().join("copy.jpg"))
+                .unwrap(),
+            Some(photo.canonicalize().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_get_hash_of_file_fast_algorithms() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/London.png");
+        for (algorithm, length) in [(HashAlgorithm::Xxh3, 32), (HashAlgorithm::Blake3, 64)] {
+            let hash = get_hash_of_file_with(&File::open(&path).unwrap(), algorithm).unwrap();
+            assert_eq!(hash.len(), length);
+            assert_eq!(
+                hash,
+                get_hash_of_file_with(&File::open(&path).unwrap(), algorithm).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_partial_hash_of_file() {
+        let tmp_dir = TempDir::new("partial").unwrap();
+        let small = tmp_dir.path().join("small.bin");
+        std::fs::write(&small, "small").unwrap();
+        // The partial hash of a small file is its full hash
+        assert_eq!(
+            get_partial_hash_of_file_with(&File::open(&small).unwrap(), HashAlgorithm::Sha256)
+                .unwrap(),
+            get_hash_of_file(&File::open(&small).unwrap()).unwrap()
+        );
+
+        let mut content = vec![0u8; 3 * PARTIAL_HASH_SIZE as usize];
+        let large = tmp_dir.path().join("large.bin");
+        std::fs::write(&large, &content).unwrap();
+        content[PARTIAL_HASH_SIZE as usize + 1] = 1;
+        let other_middle = tmp_dir.path().join("other_middle.bin");
+        std::fs::write(&other_middle, &content).unwrap();
+        assert_eq!(
+            get_partial_hash_of_file_with(&File::open(&large).unwrap(), HashAlgorithm::Xxh3)
+                .unwrap(),
+            get_partial_hash_of_file_with(&File::open(&other_middle).unwrap(), HashAlgorithm::Xxh3)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_duplicates_finder_stages() {
+        let tmp_dir = TempDir::new("stages").unwrap();
+        let size = 3 * PARTIAL_HASH_SIZE as usize;
+        let mut content = vec![0u8; size];
+        std::fs::write(tmp_dir.path().join("original.bin"), &content).unwrap();
+        std::fs::write(tmp_dir.path().join("copy.bin"), &content).unwrap();
+        // Same size, start and end, so only the full hash tells them apart
+        content[size / 2] = 1;
+        std::fs::write(tmp_dir.path().join("other_middle.bin"), &content).unwrap();
+        // Same size, but another start
+        content[0] = 1;
+        std::fs::write(tmp_dir.path().join("other_start.bin"), &content).unwrap();
+
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Xxh3,
+            HashAlgorithm::Blake3,
+        ] {
+            let mut duplicates_finder = DuplicatesFinder::new().with_hash_algorithm(algorithm);
+            let is_duplicate = |duplicates_finder: &mut DuplicatesFinder, name: &str| {
+                duplicates_finder
+                    .is_duplicate(&tmp_dir.path().join(name))
+                    .unwrap()
+            };
+            assert!(!is_duplicate(&mut duplicates_finder, "original.bin"));
+            assert!(!is_duplicate(&mut duplicates_finder, "other_start.bin"));
+            assert!(!is_duplicate(&mut duplicates_finder, "other_middle.bin"));
+            assert!(is_duplicate(&mut duplicates_finder, "copy.bin"));
+        }
+    }
+
+    #[test]
+    fn test_duplicates_finder_indexes_only_the_library() {
+        let library = TempDir::new("library").unwrap();
+        let source = TempDir::new("source").unwrap();
+        let size = 3 * PARTIAL_HASH_SIZE as usize;
+        let mut content = vec![0u8; size];
+        std::fs::write(library.path().join("original.bin"), &content).unwrap();
+        std::fs::write(source.path().join("copy.bin"), &content).unwrap();
+        // Same size, start and end as the original, so they are fully hashed
+        content[size / 2] = 1;
+        std::fs::write(source.path().join("first.bin"), &content).unwrap();
+        content[size / 2] = 2;
+        std::fs::write(source.path().join("second.bin"), &content).unwrap();
+
+        let hash_index = Rc::new(RefCell::new(HashIndex::open(library.path()).unwrap()));
+        let mut duplicates_finder = DuplicatesFinder::new().with_hash_index(hash_index.clone());
+        duplicates_finder.index_library(library.path()).unwrap();
+        for name in ["first.bin", "second.bin"] {
+            let path = source.path().join(name);
+            assert!(!duplicates_finder.is_duplicate(&path).unwrap());
+            let destination = library.path().join(name);
+            std::fs::copy(&path, &destination).unwrap();
+            // Fully hashed against the original, so its copy is not hashed again
+            let known_hash = duplicates_finder.take_hash_of(&path);
+            assert!(known_hash.is_some());
+            hash_index
+                .borrow_mut()
+                .record(&destination, known_hash)
+                .unwrap();
+        }
+        assert!(duplicates_finder
+            .is_duplicate(&source.path().join("copy.bin"))
+            .unwrap());
+        drop(duplicates_finder);
+        drop(hash_index);
+
+        // Only the original and the organized files are indexed, not the sources
+        let verification = HashIndex::open(library.path())
+            .unwrap()
+            .verify(library.path())
+            .unwrap();
+        assert_eq!(verification.verified, 3);
+        assert!(verification.not_indexed.is_empty());
+    }
 }
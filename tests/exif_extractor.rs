@@ -3,6 +3,10 @@ mod tests {
     use std::path::PathBuf;
 
     use chrono::NaiveDateTime;
+    use clineup::exif_extractor::format_aperture;
+    use clineup::exif_extractor::format_focal_length;
+    use clineup::exif_extractor::format_shutter_speed;
+    use clineup::exif_extractor::parse_fujifilm_sequence_number;
     use clineup::exif_extractor::ExifExtractor;
 
     #[test]
@@ -67,4 +71,66 @@ mod tests {
         assert!(model.is_ok());
         assert!(model.unwrap() == expected_brand);
     }
+    #[test]
+    fn test_get_software() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/Paris-20230304.jpg");
+        let extractor = ExifExtractor::new(&path).unwrap();
+
+        assert_eq!(extractor.get_software().unwrap(), "GIMP 2.10.34");
+        assert!(extractor.get_lens_model().is_err());
+    }
+    #[test]
+    fn test_find_tag() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/Paris-20230304.jpg");
+        let extractor = ExifExtractor::new(&path).unwrap();
+
+        assert_eq!(extractor.find_tag("software").unwrap(), exif::Tag::Software);
+        assert_eq!(extractor.find_tag("305").unwrap(), exif::Tag::Software);
+        assert_eq!(extractor.find_tag("0x131").unwrap(), exif::Tag::Software);
+        assert!(extractor.find_tag("LensModel").is_err());
+    }
+    #[test]
+    fn test_format_shutter_speed() {
+        assert_eq!(format_shutter_speed(1.0 / 250.0), "1-250s");
+        assert_eq!(format_shutter_speed(0.004), "1-250s");
+        assert_eq!(format_shutter_speed(0.5), "1-2s");
+        assert_eq!(format_shutter_speed(1.0), "1s");
+        assert_eq!(format_shutter_speed(2.5), "2.5s");
+        assert_eq!(format_shutter_speed(30.0), "30s");
+    }
+    #[test]
+    fn test_format_aperture() {
+        assert_eq!(format_aperture(2.8), "f2.8");
+        assert_eq!(format_aperture(1.7999), "f1.8");
+        assert_eq!(format_aperture(16.0), "f16");
+    }
+    #[test]
+    fn test_format_focal_length() {
+        assert_eq!(format_focal_length(50.0), "50mm");
+        assert_eq!(format_focal_length(4.25), "4.3mm");
+        assert_eq!(format_focal_length(6.86), "6.9mm");
+    }
+    #[test]
+    fn test_get_burst_uuid() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/bursts/IMG_0001.jpg");
+        let entry = ExifExtractor::new(&path).unwrap();
+        assert_eq!(
+            entry.get_burst_uuid().unwrap(),
+            "5D9A3C1E-7B2F-4A61-9E08-C4F1B2A3D456"
+        );
+        assert!(entry.get_sequence_number().is_err());
+    }
+    #[test]
+    fn test_parse_fujifilm_sequence_number() {
+        let mut maker_note = b"FUJIFILM".to_vec();
+        maker_note.extend_from_slice(&12u32.to_le_bytes());
+        maker_note.extend_from_slice(&2u16.to_le_bytes());
+        // Another entry, then the sequence number written in its entry
+        maker_note.extend_from_slice(&[0x00, 0x10, 2, 0, 8, 0, 0, 0, 38, 0, 0, 0]);
+        maker_note.extend_from_slice(&[0x01, 0x11, 3, 0, 1, 0, 0, 0, 3, 0, 0, 0]);
+        maker_note.extend_from_slice(b"0130\0\0\0\0");
+        assert_eq!(parse_fujifilm_sequence_number(&maker_note), Some(3));
+        assert_eq!(parse_fujifilm_sequence_number(&maker_note[..30]), None);
+        assert_eq!(parse_fujifilm_sequence_number(b"Apple iOS\0"), None);
+    }
 }
@@ -2,15 +2,25 @@
 mod tests {
     use std::path::PathBuf;
 
+    use chrono::Duration;
+    use clineup::clock_correction::ClockCorrection;
+    use clineup::errors::ClineupError;
+    use clineup::gps::base::GpsResolutionProvider;
+    use clineup::gps::location::LocationInfo;
+    use clineup::path::duplicates_finder::HashAlgorithm;
+    use clineup::path::formatter::Counters;
     use clineup::path::formatter::PathFormatter;
+    use clineup::path::formatter::ReverseGeocoder;
     use clineup::path::parser::{map_placeholders_to_enums, parse_placeholders};
+    use std::cell::Cell;
+    use std::rc::Rc;
 
     #[test]
     fn test_instantiate() {
         let path_to_format = "tests/data/output/{%year}/{%camera_brand|camera_model}".to_string();
-        let _placeholders = parse_placeholders(&path_to_format);
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
         let placeholders = map_placeholders_to_enums(&_placeholders);
-        PathFormatter::new(&path_to_format, &placeholders, None, false);
+        PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
     }
     #[test]
     fn test_get_formatted_path() {
@@ -19,9 +29,10 @@ mod tests {
         let path_to_format =
             "tests/data/output/{%typo}_{%year}/{%camera_brand|%camera_model}/{%camera_brand|fallback}"
                 .to_string();
-        let _placeholders = parse_placeholders(&path_to_format);
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
         let placeholders = map_placeholders_to_enums(&_placeholders);
-        let mut path_formatter = PathFormatter::new(&path_to_format, &placeholders, None, false);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
         let formatted_path = path_formatter.get_formatted_path(&path);
         let expected_path = PathBuf::from("tests/data/output/%typo_2023/rusttest/fallback");
         assert!(formatted_path.is_ok());
@@ -32,12 +43,305 @@ mod tests {
         let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("tests/data/Paris-20230304-duplicated.jpg");
         let path_to_format = "".to_string();
-        let _placeholders = parse_placeholders(&path_to_format);
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
         let placeholders = map_placeholders_to_enums(&_placeholders);
-        let mut path_formatter = PathFormatter::new(&path_to_format, &placeholders, None, false);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
         let formatted_path = path_formatter.get_formatted_path(&path);
         let expected_path = PathBuf::from("");
         assert!(formatted_path.is_ok());
         assert!(formatted_path.unwrap() == expected_path);
     }
+    #[test]
+    fn test_get_formatted_path_clock_correction() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/Paris-20230304-duplicated.jpg");
+        let path_to_format = "{%year}/{%month}/{%day}".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let clock_corrections = vec![
+            ClockCorrection::new(
+                None,
+                Some("another model".to_string()),
+                None,
+                Duration::days(2),
+            ),
+            ClockCorrection::new(
+                None,
+                Some("rusttest".to_string()),
+                None,
+                -Duration::hours(1),
+            ),
+        ];
+        let mut path_formatter = PathFormatter::new(&path_to_format, &placeholders, None, false)
+            .unwrap()
+            .with_clock_corrections(clock_corrections);
+        let formatted_path = path_formatter.get_formatted_path(&path);
+        assert_eq!(formatted_path.unwrap(), PathBuf::from("2023/03/03"));
+    }
+    #[test]
+    fn test_get_formatted_path_chain_after_a_fallback() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/Paris-20230304-duplicated.jpg");
+        // The brand is missing, the model is kept instead of the last placeholder of the chain
+        let path_to_format = "{%camera_brand|%camera_model|Unknown}".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+        let formatted_path = path_formatter.get_formatted_path(&path);
+        assert_eq!(formatted_path.unwrap(), PathBuf::from("rusttest"));
+    }
+    #[test]
+    fn test_get_formatted_path_photographic_settings() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/Paris-20230304-duplicated.jpg");
+        let path_to_format =
+            "{%lens_model|No lens}/{%iso|No ISO}_{%aperture|}/%software".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+        let formatted_path = path_formatter.get_formatted_path(&path);
+        assert_eq!(
+            formatted_path.unwrap(),
+            PathBuf::from("No lens/No ISO_/GIMP 2.10.34")
+        );
+    }
+    #[test]
+    fn test_get_formatted_path_photographic_settings_without_braces() {
+        let path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/settings/IMG_0001.jpg");
+        let path_to_format =
+            "%camera_brand/%iso_%aperture_%shutter_speed_%focal_length".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+        let formatted_path = path_formatter.get_formatted_path(&path);
+        assert_eq!(
+            formatted_path.unwrap(),
+            PathBuf::from("Canon/200_f2.8_1-250s_50mm")
+        );
+    }
+    #[test]
+    fn test_get_formatted_path_video_dimensions() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/videos/portrait.mp4");
+        let path_to_format = "%resolution_class/%orientation/%megapixels".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+        let formatted_path = path_formatter.get_formatted_path(&path);
+        // A landscape track turned by a quarter, like a portrait video of a phone
+        assert_eq!(formatted_path.unwrap(), PathBuf::from("1080p/portrait/2.1"));
+    }
+    #[test]
+    fn test_get_formatted_path_raw_exif_tag() {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data/Paris-20230304-duplicated.jpg");
+        let path_to_format =
+            "{%exif:Software}/{%exif:0x131}/{%exif:LensModel|%camera_model|Unknown}".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+        let formatted_path = path_formatter.get_formatted_path(&path);
+        assert_eq!(
+            formatted_path.unwrap(),
+            PathBuf::from("GIMP 2.10.34/GIMP 2.10.34/rusttest")
+        );
+    }
+    #[test]
+    fn test_get_formatted_path_media_class() {
+        let path_to_format = "%media_type/{%orientation|Unknown}".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+
+        let photo = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/Paris.png");
+        assert_eq!(
+            path_formatter.get_formatted_path(&photo).unwrap(),
+            PathBuf::from("photo/Unknown")
+        );
+
+        let other = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/empty.txt");
+        assert_eq!(
+            path_formatter.get_formatted_path(&other).unwrap(),
+            PathBuf::from("other/Unknown")
+        );
+    }
+    #[test]
+    fn test_get_formatted_path_file_identity() {
+        let path_to_format = "%ext_lower/{%original_stem}_{%size}_{%hash}.%extension".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter = PathFormatter::new(&path_to_format, &placeholders, None, false)
+            .unwrap()
+            .with_hash(HashAlgorithm::Sha256, Some(8));
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/London.png");
+        assert_eq!(
+            path_formatter.get_formatted_path(&path).unwrap(),
+            PathBuf::from("png/London_5384_bb8f2afe.png")
+        );
+    }
+    #[test]
+    fn test_get_formatted_path_counter() {
+        let path_to_format = "%ext_lower/%counter.%ext_lower".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter = PathFormatter::new(&path_to_format, &placeholders, None, false)
+            .unwrap()
+            .with_counter_width(3);
+
+        let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        let formatted_paths: Vec<PathBuf> = ["Paris.png", "London-20230102.jpg", "London.png"]
+            .iter()
+            .map(|file| path_formatter.get_formatted_path(&data.join(file)).unwrap())
+            .collect();
+        assert_eq!(
+            formatted_paths,
+            vec![
+                PathBuf::from("png/001.png"),
+                PathBuf::from("jpg/001.jpg"),
+                PathBuf::from("png/002.png")
+            ]
+        );
+    }
+    #[test]
+    fn test_get_formatted_path_shared_counters() {
+        let destination = tempdir::TempDir::new("destination").unwrap();
+        std::fs::create_dir_all(destination.path().join("photos")).unwrap();
+        std::fs::write(destination.path().join("photos/0001.png"), "first run").unwrap();
+        let counters = Counters::new(destination.path());
+
+        let png_format = "photos/%counter.%ext_lower".to_string();
+        let jpg_format = "photos/%counter.%ext_lower".to_string();
+        let png_placeholders = map_placeholders_to_enums(&parse_placeholders(&png_format).unwrap());
+        let jpg_placeholders = map_placeholders_to_enums(&parse_placeholders(&jpg_format).unwrap());
+        let mut png_formatter = PathFormatter::new(&png_format, &png_placeholders, None, false)
+            .unwrap()
+            .with_counters(counters.clone());
+        let mut jpg_formatter = PathFormatter::new(&jpg_format, &jpg_placeholders, None, false)
+            .unwrap()
+            .with_counters(counters.clone());
+
+        let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        // The existing file is skipped, and the rules go on with the same sequence
+        assert_eq!(
+            png_formatter
+                .get_formatted_path(&data.join("Paris.png"))
+                .unwrap(),
+            PathBuf::from("photos/0002.png")
+        );
+        assert_eq!(
+            jpg_formatter
+                .get_formatted_path(&data.join("London-20230102.jpg"))
+                .unwrap(),
+            PathBuf::from("photos/0003.jpg")
+        );
+        assert_eq!(
+            png_formatter
+                .get_formatted_path(&data.join("London.png"))
+                .unwrap(),
+            PathBuf::from("photos/0004.png")
+        );
+    }
+    struct CountingProvider {
+        calls: Rc<Cell<usize>>,
+    }
+
+    impl GpsResolutionProvider for CountingProvider {
+        fn get_location(&self, _lat: f32, _lon: f32) -> Result<LocationInfo, ClineupError> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(LocationInfo::new(
+                Some("France".to_string()),
+                None,
+                None,
+                None,
+                Some("Paris".to_string()),
+            ))
+        }
+    }
+
+    #[test]
+    fn test_get_formatted_path_shared_reverse_geocoder() {
+        let calls = Rc::new(Cell::new(0));
+        let reverse_geocoder = ReverseGeocoder::new(Box::new(CountingProvider {
+            calls: calls.clone(),
+        }));
+
+        let city_format = "%city".to_string();
+        let country_format = "%country/%city".to_string();
+        let city_placeholders =
+            map_placeholders_to_enums(&parse_placeholders(&city_format).unwrap());
+        let country_placeholders =
+            map_placeholders_to_enums(&parse_placeholders(&country_format).unwrap());
+        let mut city_formatter = PathFormatter::new(
+            &city_format,
+            &city_placeholders,
+            Some(reverse_geocoder.clone()),
+            false,
+        )
+        .unwrap();
+        let mut country_formatter = PathFormatter::new(
+            &country_format,
+            &country_placeholders,
+            Some(reverse_geocoder.clone()),
+            false,
+        )
+        .unwrap();
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/Paris-20230304.jpg");
+        assert_eq!(
+            city_formatter.get_formatted_path(&path).unwrap(),
+            PathBuf::from("Paris")
+        );
+        assert_eq!(
+            country_formatter.get_formatted_path(&path).unwrap(),
+            PathBuf::from("France/Paris")
+        );
+        // The rules share the provider and the locations it resolved
+        assert_eq!(calls.get(), 1);
+    }
+    #[test]
+    fn test_get_formatted_path_filters() {
+        let path_to_format =
+            "{%software:lower:replace( ,_)}/{%camera_model:upper:truncate(4)}/{%lens_model:slug|Unknown}"
+                .to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/Paris-20230304.jpg");
+        assert_eq!(
+            path_formatter.get_formatted_path(&path).unwrap(),
+            PathBuf::from("gimp_2.10.34/RUST/Unknown")
+        );
+    }
+    #[test]
+    fn test_get_formatted_path_conditionals() {
+        let path_to_format = "{if %media_type == video}Videos{elif %size > 5KB}Large {%ext_lower}{else}Photos{end}/{%has_gps ? %year : No location}".to_string();
+        let _placeholders = parse_placeholders(&path_to_format).unwrap();
+        let placeholders = map_placeholders_to_enums(&_placeholders);
+        let mut path_formatter =
+            PathFormatter::new(&path_to_format, &placeholders, None, false).unwrap();
+
+        let data = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+        assert_eq!(
+            path_formatter
+                .get_formatted_path(&data.join("Paris-20230304.jpg"))
+                .unwrap(),
+            PathBuf::from("Large jpg/2023")
+        );
+        assert_eq!(
+            path_formatter
+                .get_formatted_path(&data.join("Paris.png"))
+                .unwrap(),
+            PathBuf::from("Photos/No location")
+        );
+    }
 }
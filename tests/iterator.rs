@@ -2,8 +2,98 @@
 mod tests {
     use std::path::PathBuf;
 
+    use clineup::path::iterator::get_exclude_globs;
     use clineup::path::iterator::is_allowed_extension;
     use clineup::path::iterator::is_allowed_size;
+    use clineup::path::iterator::parse_file_list;
+    use clineup::path::iterator::select_files;
+
+    fn files(number: usize) -> Vec<PathBuf> {
+        (0..number)
+            .map(|index| PathBuf::from(format!("IMG_{:04}.jpg", index)))
+            .collect()
+    }
+
+    #[test]
+    fn test_select_files_offset_and_limit() {
+        let selected: Vec<PathBuf> =
+            select_files(files(10).into_iter(), 4, None, None, Some(3)).collect();
+        assert_eq!(selected, files(7)[4..]);
+
+        let selected: Vec<PathBuf> =
+            select_files(files(10).into_iter(), 8, None, None, Some(3)).collect();
+        assert_eq!(selected, files(10)[8..]);
+    }
+
+    #[test]
+    fn test_get_exclude_globs() {
+        let exclude_globs =
+            get_exclude_globs("/source", &["@eaDir/".to_string(), "*.tmp".to_string()]).unwrap();
+        assert!(exclude_globs
+            .matched("/source/2023/@eaDir", true)
+            .is_ignore());
+        assert!(!exclude_globs
+            .matched("/source/2023/@eaDir", false)
+            .is_ignore());
+        assert!(exclude_globs
+            .matched("/source/photo.tmp", false)
+            .is_ignore());
+        assert!(!exclude_globs
+            .matched("/source/photo.jpg", false)
+            .is_ignore());
+
+        assert!(get_exclude_globs("/source", &["photo[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        assert_eq!(
+            parse_file_list("a.jpg\nphotos/b c.jpg\r\n\n"),
+            vec![PathBuf::from("a.jpg"), PathBuf::from("photos/b c.jpg")]
+        );
+        // With NUL separators, the paths may contain new lines
+        assert_eq!(
+            parse_file_list("a.jpg\0new\nline.jpg\0"),
+            vec![PathBuf::from("a.jpg"), PathBuf::from("new\nline.jpg")]
+        );
+        assert!(parse_file_list("").is_empty());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_parse_file_list_not_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        assert_eq!(
+            parse_file_list(b"caf\xe9.jpg\nb.jpg\n"),
+            vec![
+                PathBuf::from(OsStr::from_bytes(b"caf\xe9.jpg")),
+                PathBuf::from("b.jpg")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_select_files_sample() {
+        let sample: Vec<PathBuf> =
+            select_files(files(100).into_iter(), 0, Some(10), Some(42), None).collect();
+        assert_eq!(sample.len(), 10);
+        // Sorted as the files, without duplicates
+        assert!(sample.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let same_sample: Vec<PathBuf> =
+            select_files(files(100).into_iter(), 0, Some(10), Some(42), None).collect();
+        assert_eq!(sample, same_sample);
+
+        let limited: Vec<PathBuf> =
+            select_files(files(100).into_iter(), 0, Some(10), Some(42), Some(2)).collect();
+        assert_eq!(limited, sample[..2]);
+
+        let all: Vec<PathBuf> =
+            select_files(files(5).into_iter(), 0, Some(10), None, None).collect();
+        assert_eq!(all, files(5));
+    }
 
     #[test]
     fn test_is_allowed_extension_allowed() {